@@ -28,6 +28,7 @@ impl<T: Copy + Send> ComputeNode for InterleaveChannels<T> {
             buffers.push(Vec::new().into_iter());
         }
         // interleave over samples
+        #[allow(clippy::needless_range_loop)]
         'outer: loop {
             if (output_buffer.len() + num_channels) * mem::size_of::<T>() > PACKET_SIZE {
                 // flush because it would overflow if we don't
@@ -63,6 +64,7 @@ impl<T: Copy + Send> ComputeNode for InterleaveChannels<T> {
                         break 'outer;
                     }
                     Err(ChannelError::Poisoned) => panic!("Poisoned channel"),
+                    Err(e) => panic!("Unable to receive: {:?}", e),
                 }
             }
         }
@@ -78,6 +80,7 @@ impl<T: Copy> InterleaveChannels<T> {
         Self { channels, output }
     }
 
+    #[allow(dead_code)]
     pub fn add_input_channel(&mut self, rx: Receiver<Vec<T>>) {
         self.channels.push(rx)
     }
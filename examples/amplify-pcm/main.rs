@@ -1,5 +1,5 @@
 use std::str::FromStr;
-use std::{env, thread};
+use std::{env, mem, thread};
 
 use crate::interleave_channels::InterleaveChannels;
 use crate::read_pcm_directory::ReadPcmDirectory;
@@ -11,8 +11,8 @@ use cgraph::nodes::{ComputeNode, GenericComputeNode_1_1};
 const LITTLE_ENDIAN: bool = true;
 /// Size of vecs passed along the buffer (in bytes).
 const PACKET_SIZE: usize = 4 * 1024;
-/// Number of pending vecs that can be waiting.
-const BUFFER_SIZE: usize = 128;
+/// Number of bytes of pending vecs that can be waiting in each buffer.
+const BUFFER_SIZE: usize = 128 * PACKET_SIZE;
 
 mod interleave_channels;
 mod read_pcm_directory;
@@ -36,6 +36,13 @@ impl FromStr for EncodingType {
     }
 }
 
+/// Create a channel of samples which is bounded by the number of bytes in flight.
+fn packet_channel() -> (Sender<Vec<f32>>, Receiver<Vec<f32>>) {
+    weighted_sync_channel(BUFFER_SIZE, |packet: &Vec<f32>| {
+        packet.len() * mem::size_of::<f32>()
+    })
+}
+
 /// f(x) = x*10^(dB/10)
 fn amplify_linear_signal(data: Vec<f32>, db: f32) -> Vec<f32> {
     let factor = f32::powf(10.0, db / 10.0);
@@ -77,7 +84,7 @@ pub fn main() {
     let amplified_channels: Vec<_> = channels
         .into_iter()
         .map(|channel| {
-            let (amp_tx, amp_rx) = sync_channel(BUFFER_SIZE);
+            let (amp_tx, amp_rx) = packet_channel();
             let amplifier =
                 GenericComputeNode_1_1::new("Amplifier".into(), channel, amp_tx, move |v| {
                     Some(amplify_linear_signal(v.unwrap(), amplification))
@@ -86,7 +93,7 @@ pub fn main() {
             amp_rx
         })
         .collect();
-    let (interleaved_tx, interleaved_rx) = sync_channel(BUFFER_SIZE);
+    let (interleaved_tx, interleaved_rx) = packet_channel();
     nodes.push(Box::new(InterleaveChannels::new(
         amplified_channels,
         interleaved_tx,
@@ -4,10 +4,10 @@ use std::path::PathBuf;
 use std::thread;
 use std::thread::JoinHandle;
 
-use cgraph::mpmc::{sync_channel, ChannelSender, Receiver, Sender};
+use cgraph::mpmc::{ChannelSender, Receiver, Sender};
 use cgraph::nodes::ComputeNode;
 
-use crate::{EncodingType, BUFFER_SIZE, LITTLE_ENDIAN, PACKET_SIZE};
+use crate::{packet_channel, EncodingType, LITTLE_ENDIAN, PACKET_SIZE};
 
 /// Read files in a directory in order (e.g. 0.pcm, 1.pcm, ...) and create new streams for each
 /// file so we can interleave the results.
@@ -40,7 +40,7 @@ impl ReadPcmDirectory {
         channels: usize,
         read_type: EncodingType,
     ) -> (Self, Vec<Receiver<Vec<f32>>>) {
-        let (senders, receivers) = (0..channels).map(|_| sync_channel(BUFFER_SIZE)).unzip();
+        let (senders, receivers) = (0..channels).map(|_| packet_channel()).unzip();
         (
             Self {
                 channels: senders,
@@ -55,7 +55,7 @@ impl ReadPcmDirectory {
 impl ReadPcmDirectory {
     fn read_channel_i16(&self, i: usize) -> JoinHandle<()> {
         let channel = self.channels[i].clone();
-        let file_path = self.path.join(format!("{}.pcm", i.to_string()));
+        let file_path = self.path.join(format!("{}.pcm", i));
         thread::spawn(move || {
             let mut file = File::open(&file_path).expect("Unable to open file");
             // can only read half packet size because they end up doubling in size when we convert
@@ -87,7 +87,7 @@ impl ReadPcmDirectory {
 
     fn read_channel_f32(&self, i: usize) -> JoinHandle<()> {
         let channel = self.channels[i].clone();
-        let file_path = self.path.join(format!("{}.pcm", i.to_string()));
+        let file_path = self.path.join(format!("{}.pcm", i));
         thread::spawn(move || {
             let mut file = File::open(&file_path).expect("Unable to open file");
             let mut buf = [0u8; PACKET_SIZE];
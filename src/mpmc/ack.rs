@@ -0,0 +1,377 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Deref;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{ChannelError, ChannelReceiver};
+
+/// Longest a receiver waits on the underlying channel before checking for items to redeliver.
+const REDELIVERY_POLL: Duration = Duration::from_millis(10);
+
+struct InFlight<T> {
+    value: T,
+    deadline: Instant,
+}
+
+struct AckInner<T> {
+    next_tag: u64,
+    /// Items handed out which have not been acknowledged yet, by delivery tag.
+    in_flight: HashMap<u64, InFlight<T>>,
+    /// Items which were not acknowledged in time or were given back, oldest first.
+    redeliver: VecDeque<T>,
+    /// Items taken from the underlying receiver while waiting which have not been handed out yet.
+    received: VecDeque<T>,
+    redelivered: u64,
+}
+
+impl<T> AckInner<T> {
+    /// Queue everything which has been in flight for too long to be delivered again.
+    fn expire(&mut self, now: Instant) {
+        let expired: Vec<u64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, f)| f.deadline <= now)
+            .map(|(tag, _)| *tag)
+            .collect();
+        for tag in expired {
+            self.give_back(tag);
+        }
+    }
+
+    fn give_back(&mut self, tag: u64) {
+        if let Some(f) = self.in_flight.remove(&tag) {
+            self.redeliver.push_back(f.value);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.in_flight.values().map(|f| f.deadline).min()
+    }
+}
+
+struct AckState<R: ChannelReceiver> {
+    rx: R,
+    timeout: Duration,
+    inner: Mutex<AckInner<R::Item>>,
+    /// Signalled whenever an item is acknowledged or given back.
+    changed: Condvar,
+}
+
+/// Receiver for competing consumers with at-least-once delivery. Every item received comes as a
+/// `Delivery` which has to be acknowledged once it has been dealt with. Items which are not
+/// acknowledged within the timeout, or whose delivery is dropped without being acknowledged (such
+/// as when the worker holding it panics), are delivered again to whichever clone receives next.
+///
+/// Clones share the underlying receiver, so they split the items between them as `SharedReceiver`
+/// does. Since items can be redelivered, they may be seen more than once and out of order, so
+/// processing them should be idempotent.
+///
+/// Any `ChannelReceiver` can be wrapped, including a `PersistentReceiver`. Note that unacknowledged
+/// items are only held in memory, so they are not redelivered if the whole process goes away.
+pub struct AckReceiver<R: ChannelReceiver> {
+    state: Arc<AckState<R>>,
+}
+
+impl<R: ChannelReceiver> Clone for AckReceiver<R> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<R: ChannelReceiver> AckReceiver<R> {
+    /// Wrap a receiver so that items not acknowledged within `timeout` are redelivered.
+    pub fn new(rx: R, timeout: Duration) -> Self {
+        Self {
+            state: Arc::new(AckState {
+                rx,
+                timeout,
+                inner: Mutex::new(AckInner {
+                    next_tag: 0,
+                    in_flight: HashMap::new(),
+                    redeliver: VecDeque::new(),
+                    received: VecDeque::new(),
+                    redelivered: 0,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Get the (buffer id, cursor id) of the wrapped receiver.
+    pub fn id(&self) -> (usize, usize) {
+        self.state.rx.id()
+    }
+
+    fn deliver(
+        &self,
+        inner: &mut AckInner<R::Item>,
+        value: R::Item,
+        redelivered: bool,
+    ) -> Delivery<R> {
+        let tag = inner.next_tag;
+        inner.next_tag += 1;
+        if redelivered {
+            inner.redelivered += 1;
+        }
+        inner.in_flight.insert(
+            tag,
+            InFlight {
+                value: value.clone(),
+                deadline: Instant::now() + self.state.timeout,
+            },
+        );
+        Delivery {
+            state: self.state.clone(),
+            tag,
+            value,
+            redelivered,
+            settled: false,
+        }
+    }
+
+    /// Take an item which needs to be redelivered or, failing that, the next one from the
+    /// underlying receiver without waiting for it.
+    fn take(&self) -> Result<Option<Delivery<R>>, ChannelError> {
+        let mut inner = self.state.inner.lock()?;
+        inner.expire(Instant::now());
+        if let Some(v) = inner.redeliver.pop_front() {
+            return Ok(Some(self.deliver(&mut inner, v, true)));
+        }
+        if let Some(v) = inner.received.pop_front() {
+            return Ok(Some(self.deliver(&mut inner, v, false)));
+        }
+        match self.state.rx.try_recv() {
+            Ok(Some(v)) => Ok(Some(self.deliver(&mut inner, v, false))),
+            Ok(None) => Ok(None),
+            // items still in flight may come back, so this isn't the end yet
+            Err(ChannelError::IsCorked) if !inner.in_flight.is_empty() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receive the next item, waiting until there is one. Items which need to be redelivered come
+    /// first. Once the underlying receiver is corked, this keeps waiting until every item in
+    /// flight has been acknowledged, since any of them could still need to be redelivered.
+    pub fn recv(&self) -> Result<Delivery<R>, ChannelError> {
+        loop {
+            if let Some(d) = self.take()? {
+                return Ok(d);
+            }
+            self.wait()?;
+        }
+    }
+
+    /// Receive the next item if there is one ready.
+    pub fn try_recv(&self) -> Result<Option<Delivery<R>>, ChannelError> {
+        self.take()
+    }
+
+    /// Receive the next item, waiting up to `timeout` for one. Returns None if the time ran out.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Delivery<R>>, ChannelError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(d) = self.take()? {
+                return Ok(Some(d));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            self.wait()?;
+        }
+    }
+
+    /// Wait a little for something to change, either new data or an item to be given back.
+    fn wait(&self) -> Result<(), ChannelError> {
+        let inner = self.state.inner.lock()?;
+        let mut wait = REDELIVERY_POLL;
+        if let Some(deadline) = inner.next_deadline() {
+            wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        if self.state.rx.is_corked() {
+            // nothing new will come in, so only wait for what is in flight
+            let _ = self.state.changed.wait_timeout(inner, wait)?;
+            return Ok(());
+        }
+        drop(inner);
+        match self.state.rx.recv_timeout(wait) {
+            Ok(Some(v)) => self.state.inner.lock()?.received.push_back(v),
+            // corked while waiting, `take` decides if that is the end
+            Ok(None) | Err(ChannelError::IsCorked) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// The number of items delivered which have not been acknowledged yet.
+    pub fn in_flight(&self) -> Result<usize, ChannelError> {
+        Ok(self.state.inner.lock()?.in_flight.len())
+    }
+
+    /// The total number of times items have been delivered again.
+    pub fn redelivered(&self) -> Result<u64, ChannelError> {
+        Ok(self.state.inner.lock()?.redelivered)
+    }
+}
+
+/// An item received from an `AckReceiver` which should be acknowledged with `ack` once it has been
+/// processed. Dropping it without acknowledging it hands it back to be redelivered.
+pub struct Delivery<R: ChannelReceiver> {
+    state: Arc<AckState<R>>,
+    tag: u64,
+    value: R::Item,
+    redelivered: bool,
+    settled: bool,
+}
+
+impl<R: ChannelReceiver> Delivery<R> {
+    /// Whether this item was delivered before without being acknowledged.
+    pub fn is_redelivery(&self) -> bool {
+        self.redelivered
+    }
+
+    /// Mark the item as processed so it will not be delivered again, returning it. If it was
+    /// already redelivered because the timeout passed, this has no effect on the redelivery.
+    pub fn ack(mut self) -> R::Item {
+        self.settle(false);
+        self.value.clone()
+    }
+
+    /// Hand the item back to be redelivered right away, such as when it could not be processed
+    /// right now.
+    pub fn nack(mut self) {
+        self.settle(true);
+    }
+
+    fn settle(&mut self, give_back: bool) {
+        if self.settled {
+            return;
+        }
+        self.settled = true;
+        if let Ok(mut inner) = self.state.inner.lock() {
+            if give_back {
+                inner.give_back(self.tag);
+            } else {
+                inner.in_flight.remove(&self.tag);
+            }
+        }
+        self.state.changed.notify_all();
+    }
+}
+
+impl<R: ChannelReceiver> Deref for Delivery<R> {
+    type Target = R::Item;
+
+    fn deref(&self) -> &R::Item {
+        &self.value
+    }
+}
+
+impl<R: ChannelReceiver> Debug for Delivery<R>
+where
+    R::Item: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// Anything not acknowledged is handed back, which includes unwinding from a panic.
+impl<R: ChannelReceiver> Drop for Delivery<R> {
+    fn drop(&mut self) {
+        self.settle(true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, ChannelSender};
+
+    #[test]
+    fn ack_and_redeliver() {
+        let (tx, rx) = sync_channel(8);
+        let rx = AckReceiver::new(rx, Duration::from_secs(60));
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+
+        let first = rx.recv().unwrap();
+        assert_eq!(*first, 0);
+        assert!(!first.is_redelivery());
+        let second = rx.recv().unwrap();
+        assert_eq!(rx.in_flight(), Ok(2));
+        assert_eq!(first.ack(), 0);
+
+        // dropping it is the same as giving it back, and it jumps the queue
+        drop(second);
+        let again = rx.recv().unwrap();
+        assert_eq!(*again, 1);
+        assert!(again.is_redelivery());
+        again.nack();
+        assert_eq!(rx.recv().unwrap().ack(), 1);
+        assert_eq!(rx.recv().unwrap().ack(), 2);
+        assert_eq!(rx.redelivered(), Ok(2));
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn redeliver_after_timeout() {
+        let (tx, rx) = sync_channel(8);
+        let rx = AckReceiver::new(rx, Duration::from_millis(20));
+        tx.send(7).unwrap();
+        drop(tx);
+
+        let stuck = rx.recv().unwrap();
+        // the channel is corked but we have to wait for the item in flight
+        let retry = rx.recv().unwrap();
+        assert_eq!(*retry, 7);
+        assert!(retry.is_redelivery());
+        retry.ack();
+        // acknowledging late does nothing
+        stuck.ack();
+        assert_eq!(rx.in_flight(), Ok(0));
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn panicking_worker_loses_nothing() {
+        let (tx, rx) = sync_channel(16);
+        let rx = AckReceiver::new(rx, Duration::from_secs(60));
+
+        let crashing = {
+            let rx = rx.clone();
+            thread::spawn(move || {
+                let d = rx.recv().unwrap();
+                if *d == 0 {
+                    panic!("worker died while holding an item");
+                }
+            })
+        };
+        tx.send(0).unwrap();
+        assert!(crashing.join().is_err());
+
+        for i in 1..10 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let worker = {
+            let rx = rx.clone();
+            thread::spawn(move || {
+                let mut seen = Vec::new();
+                while let Ok(d) = rx.recv() {
+                    seen.push(d.ack());
+                }
+                seen
+            })
+        };
+        let mut seen = worker.join().unwrap();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -1,23 +1,224 @@
 use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
-use crate::mpmc::ChannelError;
 use crate::mpmc::ChannelError::IsCorked;
+use crate::mpmc::{ChannelError, ChannelStats, FullPolicy, Position, Retention};
 
 static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// Get a new id which is unique among every channel in the process.
+pub(super) fn next_channel_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Function which determines how much an item counts towards the bound of a buffer.
+pub(super) type Weigher<T> = Box<dyn Fn(&T) -> usize + Send + Sync>;
+
+/// A value held in the buffer along with its bookkeeping.
+struct Entry<T> {
+    value: T,
+    /// Weight of the value towards the buffer's bound.
+    weight: usize,
+    /// When every cursor had read the value, if they have.
+    consumed_at: Option<Instant>,
+    /// When the value stops being worth delivering, if ever.
+    expires_at: Option<Instant>,
+    /// Whether the value has already been counted as expired.
+    expired: bool,
+}
+
 /// Lockable inner working components of the buffer
 struct BufferInner<T> {
-    data: VecDeque<T>,
+    data: VecDeque<Entry<T>>,
+    /// Total weight of the pending items in `data`, that is everything from `head` on.
+    weight: usize,
+    /// Maximum total weight of the pending items in the buffer. This may be changed at any time.
+    bound: usize,
     /// The "true" first index of data since we will use it as a sliding window and don't want the
     /// cursor positions to become invalid when we move it.
     offset: u64,
+    /// Index of the first item which has not been read by every cursor. Anything before this is
+    /// history which is only being kept around because of the retention policy.
+    head: u64,
+    retention: Retention,
     /// Ideally we would use a Priority queue which allows updating "priority" based on current
     /// position so we can quickly find the lowest cursor, but since we probably won't have many
     /// cases where there are more than a couple receivers, it would probably be overkill anyway.
     cursors: HashMap<usize, u64>,
     next_cursor_id: usize,
+    /// Number of items which were discarded by the full-buffer policy.
+    dropped: u64,
+    /// How long items may wait to be read before they expire, if they ever do.
+    ttl: Option<Duration>,
+    /// Number of pending items which expired before every cursor had read them.
+    expired: u64,
+    /// Whether any item has been given a deadline, so channels without any can skip looking for
+    /// expired items.
+    expiring: bool,
+    /// Total time senders have spent waiting for room.
+    send_wait: Duration,
+    /// Total time receivers have spent waiting for data.
+    recv_wait: Duration,
+    /// The send and receive wait totals when the bound was last tuned.
+    tuned_waits: (Duration, Duration),
+}
+
+impl<T> BufferInner<T> {
+    /// Index one past the last item in the buffer.
+    fn end(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+
+    /// Number of items which have not been read by every cursor.
+    fn pending(&self) -> usize {
+        (self.end() - self.head) as usize
+    }
+
+    /// Check if an item of the given weight can be added without going over the bound. An item
+    /// heavier than the whole bound is still let into an empty buffer so it can't block forever.
+    fn fits(&self, weight: usize) -> bool {
+        self.weight + weight <= self.bound || self.pending() == 0
+    }
+
+    fn push_back(&mut self, value: T, weight: usize, expires_at: Option<Instant>) {
+        self.weight += weight;
+        self.data.push_back(Entry {
+            value,
+            weight,
+            consumed_at: None,
+            expires_at,
+            expired: false,
+        });
+    }
+
+    /// Check if the item at an index has passed its deadline. Pending items are counted as expired
+    /// the first time this is noticed. History only counts as expired if it expired while it was
+    /// pending, since anything else was read in time.
+    fn is_expired(&mut self, index: u64, now: Instant) -> bool {
+        let entry = &mut self.data[(index - self.offset) as usize];
+        if index < self.head {
+            return entry.expired;
+        }
+        if entry.expires_at.map_or(true, |t| t > now) {
+            return false;
+        }
+        if !entry.expired {
+            entry.expired = true;
+            self.expired += 1;
+        }
+        true
+    }
+
+    /// Move the head past expired items at the front of the pending items, whether or not every
+    /// cursor has read them, so they no longer count against the bound. Returns the number of
+    /// items moved past. They become history like anything else every cursor has read, which is
+    /// left for `trim_history` to remove, and expired items further back are left for the cursors
+    /// to skip over until they reach the head.
+    fn reclaim_expired(&mut self, now: Instant) -> usize {
+        let mut reclaimed = 0;
+        while self.head < self.end() && self.is_expired(self.head, now) {
+            let head = self.head;
+            let entry = &mut self.data[(head - self.offset) as usize];
+            entry.consumed_at = Some(now);
+            self.weight -= entry.weight;
+            self.head += 1;
+            // cursors replaying history will skip it when they get there
+            for cursor in self.cursors.values_mut() {
+                if *cursor == head {
+                    *cursor = head + 1;
+                }
+            }
+            reclaimed += 1;
+        }
+        reclaimed
+    }
+
+    /// Move a cursor past any expired items in front of it.
+    fn skip_expired(&mut self, cursor_id: usize, now: Instant) {
+        let mut cursor = *self.cursors.get(&cursor_id).expect("Cursor id is invalid");
+        while cursor < self.end() && self.is_expired(cursor, now) {
+            cursor += 1;
+        }
+        self.cursors.insert(cursor_id, cursor);
+    }
+
+    /// When the first pending item will expire, which will make room in the buffer. Items further
+    /// back are only reclaimed once everything in front of them has gone, which a receiver will
+    /// say when it happens, so their deadlines don't matter here even if they have passed.
+    fn next_expiry(&self) -> Option<Instant> {
+        let head = (self.head - self.offset) as usize;
+        self.data.get(head).and_then(|e| e.expires_at)
+    }
+
+    /// Remove the first item in the window, moving any cursors which had not read it yet forward
+    /// so they remain valid. Returns true if the item had not been read by every cursor.
+    fn pop_front(&mut self) -> Option<bool> {
+        let entry = self.data.pop_front()?;
+        let pending = self.offset >= self.head;
+        if pending {
+            self.weight -= entry.weight;
+        }
+        self.offset += 1;
+        let offset = self.offset;
+        self.head = self.head.max(offset);
+        for cursor in self.cursors.values_mut() {
+            if *cursor < offset {
+                *cursor = offset;
+            }
+        }
+        Some(pending)
+    }
+
+    /// Remove the last item in the window. This must only be done if no cursors have read it.
+    fn pop_back(&mut self) -> Option<T> {
+        if self.pending() == 0 {
+            return None;
+        }
+        let entry = self.data.pop_back()?;
+        self.weight -= entry.weight;
+        Some(entry.value)
+    }
+
+    /// Move the head past every item which has been read by all of the cursors and return how
+    /// many items it moved past. The head does not move if there are no cursors since a receiver
+    /// may still be created to read them.
+    fn advance_head(&mut self) -> usize {
+        let mut advanced = 0;
+        let now = Instant::now();
+        while !self.cursors.is_empty()
+            && self.head < self.end()
+            && self.cursors.values().all(|&c| c > self.head)
+        {
+            let entry = &mut self.data[(self.head - self.offset) as usize];
+            entry.consumed_at = Some(now);
+            let weight = entry.weight;
+            self.weight -= weight;
+            self.head += 1;
+            advanced += 1;
+        }
+        self.trim_history();
+        advanced
+    }
+
+    /// Remove history the retention policy no longer wants to keep. History a cursor is still
+    /// replaying is kept until that cursor moves past it.
+    fn trim_history(&mut self) {
+        let keep_from = self.cursors.values().copied().fold(self.head, u64::min);
+        while self.offset < keep_from {
+            let expired = match self.retention {
+                Retention::Items(n) => (self.head - self.offset) as usize > n,
+                Retention::Duration(d) => {
+                    self.data[0].consumed_at.map_or(true, |t| t.elapsed() >= d)
+                }
+            };
+            if !expired {
+                break;
+            }
+            self.pop_front();
+        }
+    }
 }
 
 /// A buffer of data for multiple consumers and producers to work with.
@@ -30,25 +231,46 @@ pub(super) struct Buffer<T> {
     on_data_consumed: Condvar,
     corked: AtomicBool,
     sender_count: AtomicUsize,
-    bound: usize,
+    policy: FullPolicy,
+    /// Determines how much an item counts towards the bound; every item weighs 1 if not set.
+    weigher: Option<Weigher<T>>,
     id: usize,
 }
 
 impl<T: Clone> Buffer<T> {
-    pub fn new(bound: usize) -> Self {
+    pub fn new(
+        bound: usize,
+        policy: FullPolicy,
+        weigher: Option<Weigher<T>>,
+        retention: Retention,
+        ttl: Option<Duration>,
+    ) -> Self {
         Buffer {
             inner: Mutex::new(BufferInner {
-                data: VecDeque::with_capacity(bound),
+                // a weighted bound says nothing about how many items we will hold
+                data: VecDeque::with_capacity(if weigher.is_some() { 0 } else { bound }),
+                weight: 0,
+                bound,
                 offset: 0,
+                head: 0,
+                retention,
                 cursors: HashMap::new(),
                 next_cursor_id: 0,
+                dropped: 0,
+                ttl,
+                expired: 0,
+                expiring: ttl.is_some(),
+                send_wait: Duration::default(),
+                recv_wait: Duration::default(),
+                tuned_waits: Default::default(),
             }),
-            bound,
+            policy,
+            weigher,
             on_new_data: Condvar::new(),
             on_data_consumed: Condvar::new(),
             corked: AtomicBool::new(false),
             sender_count: AtomicUsize::new(0),
-            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            id: next_channel_id(),
         }
     }
 
@@ -58,23 +280,32 @@ impl<T: Clone> Buffer<T> {
     }
 
     /// Write data to the internal buffer for the Receivers to read. This will sleep the current
-    /// thread if the internal buffer is full and wait until there is room to write.
-    pub fn send(&self, v: T) -> Result<(), ChannelError> {
+    /// thread if the internal buffer is full and wait until there is room to write, unless the
+    /// full-buffer policy says to drop data instead. The item expires at `deadline` if given,
+    /// otherwise after the buffer's time-to-live if it has one.
+    pub fn send(&self, mut v: T, deadline: Option<Instant>) -> Result<(), ChannelError> {
         if self.is_corked() {
             return Err(ChannelError::IsCorked);
         }
         {
             // lock scope
             let mut inner = self.inner.lock()?;
-            if inner.data.len() < self.bound {
-                inner.data.push_back(v);
-            } else {
+            while let Err(back) = self.push(&mut inner, v, deadline) {
                 // we need to unlock this mutex and wait for consumed data before pushing
-                let mut zelf = self.on_data_consumed.wait(inner)?;
+                v = back;
+                let start = Instant::now();
+                // an item expiring makes room as well, but nobody will tell us when it does
+                inner = match inner.next_expiry() {
+                    None => self.on_data_consumed.wait(inner)?,
+                    Some(t) => {
+                        let wait = t.saturating_duration_since(start);
+                        self.on_data_consumed.wait_timeout(inner, wait)?.0
+                    }
+                };
+                inner.send_wait += start.elapsed();
                 if self.is_corked() {
                     return Err(ChannelError::IsCorked);
                 }
-                zelf.data.push_back(v);
             }
         }
 
@@ -85,17 +316,16 @@ impl<T: Clone> Buffer<T> {
 
     /// Attempt to write data to the internal buffer for the Receivers to read. This will return
     /// Ok(Some(Item)) if there were no errors but the buffer was full, otherwise it will return
-    /// Ok(None) if sent successfully.
-    pub fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+    /// Ok(None) if sent successfully (or dropped according to the full-buffer policy). The item
+    /// expires the same way as with `send`.
+    pub fn try_send(&self, v: T, deadline: Option<Instant>) -> Result<Option<T>, ChannelError> {
         if self.is_corked() {
             return Err(ChannelError::IsCorked);
         }
         {
             // Lock Scope
             let mut inner = self.inner.lock()?;
-            if inner.data.len() < self.bound {
-                inner.data.push_back(v);
-            } else {
+            if let Err(v) = self.push(&mut inner, v, deadline) {
                 return Ok(Some(v));
             }
         }
@@ -104,57 +334,103 @@ impl<T: Clone> Buffer<T> {
         Ok(None)
     }
 
+    /// Append a value to the buffer, applying the full-buffer policy if there is no room. The value
+    /// is handed back if the caller needs to wait for room.
+    fn push(&self, inner: &mut BufferInner<T>, v: T, deadline: Option<Instant>) -> Result<(), T> {
+        let weight = self.weigher.as_ref().map_or(1, |f| f(&v));
+        let now = Instant::now();
+        if inner.expiring {
+            inner.reclaim_expired(now);
+        }
+        inner.trim_history();
+        if !inner.fits(weight) {
+            match self.policy {
+                FullPolicy::Block => return Err(v),
+                FullPolicy::DropNewest => {
+                    inner.dropped += 1;
+                    return Ok(());
+                }
+                FullPolicy::DropOldest => {}
+                FullPolicy::Overwrite => {
+                    let end = inner.end();
+                    if inner.cursors.values().all(|&c| c < end) && inner.pop_back().is_some() {
+                        // nobody had seen the newest item yet so we can quietly replace it
+                        inner.dropped += 1;
+                    }
+                }
+            }
+            // make room at the front for whatever is left over, history has to go first since it
+            // sits in front of the oldest pending item. Never free more than the new item weighs,
+            // so a buffer over a bound which was just shrunk drains as it is read rather than
+            // losing the excess all at once.
+            let before = inner.weight;
+            while !inner.fits(weight) && before - inner.weight < weight {
+                match inner.pop_front() {
+                    Some(true) => inner.dropped += 1,
+                    Some(false) => {}
+                    None => break,
+                }
+            }
+        }
+        let expires_at = deadline.or_else(|| inner.ttl.map(|ttl| now + ttl));
+        inner.expiring |= expires_at.is_some();
+        inner.push_back(v, weight, expires_at);
+        Ok(())
+    }
+
     /// Receive the next item from the queue, sleeping this thread until there is data automatically
     /// if no data is present at the time of calling.
     pub fn recv(&self, cursor_id: usize) -> Result<T, ChannelError> {
-        let mut inner = loop {
-            // making this a scope because it will pause during this and vars will change and need
-            // to be re-set after
-            let inner = self.inner.lock()?;
+        self.recv_until(cursor_id, None)
+            .map(|v| v.expect("Received nothing without a deadline"))
+    }
+
+    /// Receive the next item from the queue, sleeping this thread for up to `timeout` waiting for
+    /// data if none is present. Returns None if the time ran out.
+    pub fn recv_timeout(
+        &self,
+        cursor_id: usize,
+        timeout: Duration,
+    ) -> Result<Option<T>, ChannelError> {
+        self.recv_until(cursor_id, Some(Instant::now() + timeout))
+    }
+
+    fn recv_until(
+        &self,
+        cursor_id: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Option<T>, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        loop {
+            self.expire(&mut inner, cursor_id);
             let cursor = *inner.cursors.get(&cursor_id).expect("Cursor id is invalid");
-            let offset = inner.offset;
-            let length = inner.data.len() as u64;
-            if cursor >= length + offset {
-                // no data left to read
-                if self.is_corked() {
-                    return Err(IsCorked);
-                }
-                let inner = self.on_new_data.wait(inner)?;
-                if !inner.data.is_empty() {
-                    break inner;
-                } else if self.is_corked() {
-                    return Err(IsCorked);
-                } else {
-                    // shared cursor situation where we are the looser of the race
-                }
-            } else {
-                break inner;
+            if cursor < inner.end() {
+                break;
             }
-        };
-        // re-set values because they may have changed after waiting
-        let offset = inner.offset;
-        let cursor = *inner.cursors.get(&cursor_id).expect("Cursor id is invalid");
-        let v = inner
-            .data
-            .get((cursor - offset) as usize)
-            .expect("Error in cursor arithmetic")
-            .clone();
-        inner.cursors.insert(cursor_id, cursor + 1);
-        if cursor == offset {
-            // if this cursor was at the head of the list it may be time to move the window
-            self.move_buffer_window(inner);
+            // no data left to read
+            if self.is_corked() {
+                return Err(IsCorked);
+            }
+            // values may have changed after waiting, so check again (we may also be the loser of
+            // the race in a shared cursor situation)
+            let start = Instant::now();
+            inner = match deadline {
+                None => self.on_new_data.wait(inner)?,
+                Some(deadline) if deadline <= start => return Ok(None),
+                Some(deadline) => self.on_new_data.wait_timeout(inner, deadline - start)?.0,
+            };
+            inner.recv_wait += start.elapsed();
         }
-        Ok(v)
+        Ok(Some(self.take(inner, cursor_id)))
     }
 
     /// Attempt to retrieve the next item from the queue, if no data is present, return None instead
     /// of sleeping the thread.
     pub fn try_recv(&self, cursor_id: usize) -> Result<Option<T>, ChannelError> {
         let mut inner = self.inner.lock()?;
+        self.expire(&mut inner, cursor_id);
         let cursor = *inner.cursors.get(&cursor_id).expect("Cursor id is invalid");
-        let offset = inner.offset;
-        let length = inner.data.len() as u64;
-        if cursor >= length + offset {
+        if cursor >= inner.end() {
             // no data left to read
             if self.is_corked() {
                 Err(IsCorked)
@@ -162,30 +438,50 @@ impl<T: Clone> Buffer<T> {
                 Ok(None)
             }
         } else {
-            let v = inner
-                .data
-                .get((cursor - offset) as usize)
-                .expect("Error in cursor arithmetic")
-                .clone();
-            inner.cursors.insert(cursor_id, cursor + 1);
-            if cursor == offset {
-                // if this cursor was at the head of the list it may be time to move the window
-                self.move_buffer_window(inner);
-            }
-            Ok(Some(v))
+            Ok(Some(self.take(inner, cursor_id)))
         }
     }
 
+    /// Drop expired items from the window and move the cursor past any it would read next, waking
+    /// blocked senders if that made room.
+    fn expire(&self, inner: &mut BufferInner<T>, cursor_id: usize) {
+        if !inner.expiring {
+            return;
+        }
+        let now = Instant::now();
+        let mut freed = inner.reclaim_expired(now);
+        inner.skip_expired(cursor_id, now);
+        freed += inner.advance_head();
+        if freed > 0 {
+            self.on_data_consumed.notify_all();
+        }
+    }
+
+    /// Read the item under a cursor and advance it. The cursor must not be at the end.
+    fn take(&self, mut inner: MutexGuard<BufferInner<T>>, cursor_id: usize) -> T {
+        let offset = inner.offset;
+        let cursor = *inner.cursors.get(&cursor_id).expect("Cursor id is invalid");
+        let v = inner
+            .data
+            .get((cursor - offset) as usize)
+            .expect("Error in cursor arithmetic")
+            .value
+            .clone();
+        inner.cursors.insert(cursor_id, cursor + 1);
+        if cursor == inner.head {
+            // if this cursor was at the head of the list it may be time to move the window
+            self.move_buffer_window(inner);
+        }
+        v
+    }
+
     /// Move sliding window if possible
     fn move_buffer_window(&self, mut inner: MutexGuard<BufferInner<T>>) {
-        if inner.cursors.values().any(|&c| c <= inner.offset) {
+        if inner.advance_head() == 0 {
             // there is at least one cursor still at the beginning of the buffer so we can't move
             // forward yet.
             return;
         }
-
-        inner.data.pop_front();
-        inner.offset += 1;
         std::mem::drop(inner);
         // only notify one since otherwise we will will get one new submission from
         // each producer that was waiting
@@ -194,7 +490,7 @@ impl<T: Clone> Buffer<T> {
 
     /// Check if this buffer is no longer accepting new inputs.
     pub fn is_corked(&self) -> bool {
-        return self.corked.load(Ordering::Acquire);
+        self.corked.load(Ordering::Acquire)
     }
 
     /// Indicate no new data will come into this buffer.
@@ -220,26 +516,162 @@ impl<T: Clone> Buffer<T> {
         self.sender_count.load(Ordering::Acquire)
     }
 
-    /// Create a new receiver id and cursor at the beginning of the buffer.
-    pub fn new_receiver(&self) -> Result<usize, ChannelError> {
+    /// Create a new receiver id and cursor at the given position in the buffer. Positions outside
+    /// of the buffer are moved to the closest end of it.
+    pub fn new_receiver(&self, position: Position) -> Result<usize, ChannelError> {
         let mut inner = self.inner.lock()?;
         let id = inner.next_cursor_id;
         inner.next_cursor_id += 1;
-        let offset = inner.offset;
-        inner.cursors.insert(id, offset);
+        let cursor = Self::resolve(&inner, position);
+        inner.cursors.insert(id, cursor);
         Ok(id)
     }
-    
+
+    /// Move an existing cursor to a new position. Moving backwards replays any history which is
+    /// still retained.
+    pub fn seek(&self, id: usize, position: Position) -> Result<(), ChannelError> {
+        let mut inner = self.inner.lock()?;
+        let cursor = Self::resolve(&inner, position);
+        inner.cursors.insert(id, cursor);
+        // we may have been the one holding back the head
+        self.move_buffer_window(inner);
+        Ok(())
+    }
+
+    fn resolve(inner: &BufferInner<T>, position: Position) -> u64 {
+        match position {
+            Position::Oldest => inner.offset,
+            Position::Latest => inner.end(),
+            Position::Offset(i) => i.max(inner.offset).min(inner.end()),
+        }
+    }
+
+    /// Get the position of the next item a cursor will read.
+    pub fn position(&self, id: usize) -> Result<u64, ChannelError> {
+        Ok(*self
+            .inner
+            .lock()?
+            .cursors
+            .get(&id)
+            .expect("Cursor id is invalid"))
+    }
+
     /// Remove the cursor for a receiver and perform any other necessary cleanup. This buffer will
     /// no longer wait on the provided receiver.
     pub fn drop_receiver(&self, id: usize) -> Result<(), ChannelError> {
-        self.inner.lock()?.cursors.remove(&id);
+        let released = {
+            let mut inner = self.inner.lock()?;
+            inner.cursors.remove(&id);
+            // this may have been the receiver holding everyone else back
+            inner.advance_head()
+        };
+        if released > 0 {
+            self.on_data_consumed.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Get the maximum total weight of the items in the buffer.
+    pub fn bound(&self) -> Result<usize, ChannelError> {
+        Ok(self.inner.lock()?.bound)
+    }
+
+    /// Change the maximum total weight of the items in the buffer. Growing the bound wakes up any
+    /// blocked senders, while shrinking it leaves what is already in the buffer alone and only
+    /// holds back new items until enough has been consumed.
+    pub fn set_bound(&self, bound: usize) -> Result<(), ChannelError> {
+        let mut inner = self.inner.lock()?;
+        self.resize(&mut inner, bound);
         Ok(())
     }
 
+    /// Double the bound if senders spent longer blocked than receivers did since the last call, or
+    /// halve it if senders never blocked at all, keeping it within `min..=max`. Returns the new
+    /// bound.
+    pub fn tune_bound(&self, min: usize, max: usize) -> Result<usize, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        let send_wait = inner.send_wait - inner.tuned_waits.0;
+        let recv_wait = inner.recv_wait - inner.tuned_waits.1;
+        inner.tuned_waits = (inner.send_wait, inner.recv_wait);
+        let bound = if send_wait > recv_wait {
+            inner.bound.saturating_mul(2)
+        } else if send_wait.is_zero() {
+            inner.bound / 2
+        } else {
+            inner.bound
+        };
+        let bound = bound.clamp(min, max);
+        self.resize(&mut inner, bound);
+        Ok(bound)
+    }
+
+    fn resize(&self, inner: &mut BufferInner<T>, bound: usize) {
+        let grew = bound > inner.bound;
+        inner.bound = bound;
+        if grew {
+            self.on_data_consumed.notify_all();
+        }
+    }
+
     /// Current number of pending elements in the buffer.
     pub fn len(&self) -> Result<usize, ChannelError> {
         // TODO: we could store this outside the mutex with an atomic usize
-        Ok(self.inner.lock()?.data.len())
+        Ok(self.inner.lock()?.pending())
+    }
+
+    /// Snapshot of the counters describing this buffer.
+    pub fn stats(&self) -> Result<ChannelStats, ChannelError> {
+        let inner = self.inner.lock()?;
+        Ok(ChannelStats {
+            pending: inner.pending(),
+            pending_weight: inner.weight,
+            retained: (inner.head - inner.offset) as usize,
+            senders: self.senders(),
+            receivers: inner.cursors.len(),
+            dropped: inner.dropped,
+            expired: inner.expired,
+            send_wait: inner.send_wait,
+            recv_wait: inner.recv_wait,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn wait_for_front_deadline() {
+        let buffer = Arc::new(Buffer::new(
+            2,
+            FullPolicy::Block,
+            None,
+            Retention::default(),
+            None,
+        ));
+        let cursor = buffer.new_receiver(Position::Oldest).unwrap();
+        let now = Instant::now();
+        buffer
+            .send(1u8, Some(now + Duration::from_secs(60)))
+            .unwrap();
+        // already expired, but stuck behind an item which isn't
+        buffer.send(2, Some(now)).unwrap();
+        assert_eq!(
+            buffer.inner.lock().unwrap().next_expiry(),
+            Some(now + Duration::from_secs(60))
+        );
+
+        let sender = {
+            let buffer = buffer.clone();
+            thread::spawn(move || buffer.send(3, None))
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(buffer.recv(cursor), Ok(1));
+        sender.join().unwrap().unwrap();
+        assert_eq!(buffer.recv(cursor), Ok(3));
+        assert_eq!(buffer.stats().unwrap().expired, 1);
     }
 }
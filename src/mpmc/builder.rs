@@ -0,0 +1,81 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::buffer::Weigher;
+use super::{Buffer, FullPolicy, Receiver, Retention, Sender};
+
+/// Configure and create a multiple-producer, multiple-consumer channel when the defaults used by
+/// `sync_channel` are not appropriate.
+///
+/// ```
+/// use cgraph::mpmc::{ChannelBuilder, FullPolicy};
+///
+/// let (tx, rx) = ChannelBuilder::<Vec<f32>>::new(16)
+///     .full_policy(FullPolicy::DropOldest)
+///     .build();
+/// ```
+pub struct ChannelBuilder<T> {
+    bound: usize,
+    policy: FullPolicy,
+    weigher: Option<Weigher<T>>,
+    retention: Retention,
+    ttl: Option<Duration>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Clone> ChannelBuilder<T> {
+    /// Start building a channel which will hold at most `bound` items at a time.
+    pub fn new(bound: usize) -> Self {
+        Self {
+            bound,
+            policy: FullPolicy::default(),
+            weigher: None,
+            retention: Retention::default(),
+            ttl: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set what senders should do when the buffer is full.
+    pub fn full_policy(mut self, policy: FullPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Bound the channel by the total weight of the items in it rather than by how many there are.
+    /// The weight function must always return the same weight for the same item.
+    pub fn weigher<F>(mut self, weigher: F) -> Self
+    where
+        F: Fn(&T) -> usize + Send + Sync + 'static,
+    {
+        self.weigher = Some(Box::new(weigher));
+        self
+    }
+
+    /// Keep some history around after every receiver has read it so it can be replayed.
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Expire items which have not been read by every receiver within `ttl` of being sent. Expired
+    /// items are never delivered to receivers which had not read them yet, and free up their room
+    /// in the buffer. Items sent with their own deadline use that instead.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Create the channel.
+    pub fn build(self) -> (Sender<T>, Receiver<T>) {
+        let buffer = Arc::new(Buffer::new(
+            self.bound,
+            self.policy,
+            self.weigher,
+            self.retention,
+            self.ttl,
+        ));
+        (Sender::new(buffer.clone()), Receiver::new(buffer))
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::ErrorKind;
+#[cfg(feature = "serde")]
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use crate::mpmc::ChannelError;
+
+/// Converts items to and from bytes so they can leave the process, such as when they are written
+/// to disk or sent over the network. Framing is the responsibility of whatever is using the codec,
+/// so `decode` is always given exactly the bytes one call to `encode` produced.
+pub trait Codec<T>: Send + Sync {
+    /// Append the encoded form of `v` to `buf`.
+    fn encode(&self, v: &T, buf: &mut Vec<u8>) -> Result<(), ChannelError>;
+
+    /// Rebuild an item from its encoded form.
+    fn decode(&self, bytes: &[u8]) -> Result<T, ChannelError>;
+}
+
+fn invalid_data() -> ChannelError {
+    ChannelError::Io(ErrorKind::InvalidData)
+}
+
+/// Primitive numbers which `RawLeCodec` knows how to write.
+pub trait LePrimitive: Copy + Send + Sync + 'static {
+    /// Append the little-endian bytes of the value.
+    fn write_le(self, buf: &mut Vec<u8>);
+
+    /// Read a value from exactly `size_of::<Self>()` little-endian bytes.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! le_primitive {
+    ($($t:ty),*) => {$(
+        impl LePrimitive for $t {
+            fn write_le(self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                let mut raw = [0u8; size_of::<$t>()];
+                raw.copy_from_slice(bytes);
+                <$t>::from_le_bytes(raw)
+            }
+        }
+    )*};
+}
+
+le_primitive!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// Writes primitive numbers, or `Vec`s of them such as PCM packets, as their raw little-endian
+/// bytes with nothing else added. Decoding a `Vec` takes as many values as fit in the bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct RawLeCodec;
+
+impl<T: LePrimitive> Codec<T> for RawLeCodec {
+    fn encode(&self, v: &T, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        v.write_le(buf);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, ChannelError> {
+        if bytes.len() != size_of::<T>() {
+            return Err(invalid_data());
+        }
+        Ok(T::read_le(bytes))
+    }
+}
+
+impl<T: LePrimitive> Codec<Vec<T>> for RawLeCodec {
+    fn encode(&self, v: &Vec<T>, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        buf.reserve(v.len() * size_of::<T>());
+        for x in v {
+            x.write_le(buf);
+        }
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<T>, ChannelError> {
+        if bytes.len() % size_of::<T>() != 0 {
+            return Err(invalid_data());
+        }
+        Ok(bytes.chunks_exact(size_of::<T>()).map(T::read_le).collect())
+    }
+}
+
+/// Writes byte strings and text as a little-endian `u32` length followed by the bytes, so items
+/// can be told apart even when whatever stores them does no framing of its own.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct BytesCodec;
+
+impl BytesCodec {
+    fn write(bytes: &[u8], buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        if bytes.len() > u32::MAX as usize {
+            return Err(ChannelError::Io(ErrorKind::InvalidInput));
+        }
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read(bytes: &[u8]) -> Result<&[u8], ChannelError> {
+        if bytes.len() < 4 {
+            return Err(invalid_data());
+        }
+        let len = u32::read_le(&bytes[..4]) as usize;
+        if bytes.len() - 4 != len {
+            return Err(invalid_data());
+        }
+        Ok(&bytes[4..])
+    }
+}
+
+impl Codec<Vec<u8>> for BytesCodec {
+    fn encode(&self, v: &Vec<u8>, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        Self::write(v, buf)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<u8>, ChannelError> {
+        Ok(Self::read(bytes)?.to_vec())
+    }
+}
+
+impl Codec<String> for BytesCodec {
+    fn encode(&self, v: &String, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        Self::write(v.as_bytes(), buf)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<String, ChannelError> {
+        String::from_utf8(Self::read(bytes)?.to_vec()).map_err(|_| invalid_data())
+    }
+}
+
+/// Writes anything serde can serialize using bincode.
+#[cfg(feature = "serde")]
+pub struct BincodeCodec<T> {
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> BincodeCodec<T> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Default for BincodeCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Codec<T> for BincodeCodec<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn encode(&self, v: &T, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+        bincode::serialize_into(buf, v).map_err(|_| ChannelError::Io(ErrorKind::InvalidInput))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, ChannelError> {
+        bincode::deserialize(bytes).map_err(|_| invalid_data())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<T, C: Codec<T>>(codec: &C, v: &T) -> (Vec<u8>, T) {
+        let mut buf = Vec::new();
+        codec.encode(v, &mut buf).unwrap();
+        let decoded = codec.decode(&buf).unwrap();
+        (buf, decoded)
+    }
+
+    #[test]
+    fn raw_le() {
+        let (bytes, v) = round_trip(&RawLeCodec, &0x0102_0304u32);
+        assert_eq!(bytes, vec![4, 3, 2, 1]);
+        assert_eq!(v, 0x0102_0304);
+
+        let packet = vec![0.5f32, -1.0, 3.25];
+        let (bytes, v) = round_trip(&RawLeCodec, &packet);
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(v, packet);
+
+        assert!(Codec::<u16>::decode(&RawLeCodec, &[1, 2, 3]).is_err());
+        assert!(Codec::<Vec<f32>>::decode(&RawLeCodec, &[0; 6]).is_err());
+    }
+
+    #[test]
+    fn length_prefixed_bytes() {
+        let (bytes, v) = round_trip(&BytesCodec, &vec![7u8, 8, 9]);
+        assert_eq!(bytes, vec![3, 0, 0, 0, 7, 8, 9]);
+        assert_eq!(v, vec![7, 8, 9]);
+
+        let (_, v) = round_trip(&BytesCodec, &String::from("héllo"));
+        assert_eq!(v, "héllo");
+
+        // truncated or not text
+        assert!(Codec::<Vec<u8>>::decode(&BytesCodec, &[3, 0, 0, 0, 7]).is_err());
+        assert!(Codec::<String>::decode(&BytesCodec, &[1, 0, 0, 0, 0xff]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bincode() {
+        let codec = BincodeCodec::new();
+        let item = (42u32, String::from("packet"), vec![1.5f64]);
+        let (_, v) = round_trip(&codec, &item);
+        assert_eq!(v, item);
+        assert!(codec.decode(&[1, 2]).is_err());
+    }
+}
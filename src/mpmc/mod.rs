@@ -8,20 +8,46 @@
 //! At this time an unbounded channel is not implemented, but could be added as well.
 
 use std::fmt::Debug;
-use std::sync::{Arc, PoisonError};
+use std::io;
+use std::sync::PoisonError;
+use std::time::Duration;
 
+pub use ack::*;
 use buffer::Buffer;
+pub use builder::*;
+pub use codec::*;
+pub use partitioned::*;
+pub use persistent::*;
+pub use priority::*;
 pub use receiver::*;
+pub use remote::*;
 pub use sender::*;
+#[cfg(unix)]
+pub use shm::*;
 
+mod ack;
 mod buffer;
+mod builder;
+mod codec;
+mod partitioned;
+mod persistent;
+mod priority;
 mod receiver;
+mod remote;
 mod sender;
+#[cfg(unix)]
+mod shm;
+#[cfg(unix)]
+mod unix_socket;
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ChannelError {
     IsCorked,
     Poisoned,
+    /// Reading or writing the storage backing the channel failed.
+    Io(io::ErrorKind),
+    /// The other end of a channel which crosses a process boundary went away without corking it.
+    Disconnected,
 }
 
 impl<T> From<PoisonError<T>> for ChannelError {
@@ -30,17 +56,143 @@ impl<T> From<PoisonError<T>> for ChannelError {
     }
 }
 
+impl From<io::Error> for ChannelError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e.kind())
+    }
+}
+
+/// What a sender should do with an item when the buffer it is sending to is full.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum FullPolicy {
+    /// Wait until there is room in the buffer (backpressure). This is the default.
+    #[default]
+    Block,
+    /// Discard the item being sent.
+    DropNewest,
+    /// Discard the oldest item in the buffer to make room, even if some receivers have not read it.
+    DropOldest,
+    /// Replace the newest item in the buffer with the one being sent so receivers only see the
+    /// latest value. If any receiver has already read the newest item, this falls back to
+    /// `DropOldest`.
+    Overwrite,
+}
+
+/// How much history a channel keeps after every receiver has read it, so receivers created later
+/// (or moved back with `seek`) can replay it. History does not count towards the bound.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Retention {
+    /// Keep up to this many of the most recently consumed items.
+    Items(usize),
+    /// Keep items for this long after they were consumed.
+    Duration(Duration),
+}
+
+impl Default for Retention {
+    /// Keep nothing.
+    fn default() -> Self {
+        Self::Items(0)
+    }
+}
+
+/// Where in a channel a new receiver starts reading from.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Position {
+    /// The oldest item still held by the channel, including retained history. This is where cloned
+    /// receivers start.
+    Oldest,
+    /// Only items sent after the receiver is created.
+    Latest,
+    /// A specific index in the stream, counting every item ever sent to the channel from 0. If it
+    /// is no longer (or not yet) in the channel, the closest end is used instead.
+    Offset(u64),
+}
+
+/// Point-in-time counters describing a channel.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ChannelStats {
+    /// Number of items in the buffer which have not been read by every receiver.
+    pub pending: usize,
+    /// Total weight of the pending items. This is the same as `pending` unless the channel was
+    /// created with a weight function.
+    pub pending_weight: usize,
+    /// Number of items every receiver has read which are being kept by the retention policy.
+    pub retained: usize,
+    /// Number of senders attached to the buffer.
+    pub senders: usize,
+    /// Number of independent cursors in the buffer. Shared receivers count once.
+    pub receivers: usize,
+    /// Number of items discarded because of the full-buffer policy.
+    pub dropped: u64,
+    /// Number of items which expired before every receiver had read them. Expired items are
+    /// skipped by receivers which have not read them yet.
+    pub expired: u64,
+    /// Total time senders have spent blocked waiting for room in the buffer. If this keeps
+    /// growing, the bound may be too small or the receivers too slow.
+    pub send_wait: Duration,
+    /// Total time receivers have spent blocked waiting for new data.
+    pub recv_wait: Duration,
+}
+
 /// Create a new multiple-producer, multiple-consumer channel. It highly recommended that `T` is a
 /// suitably large data packet for efficiency.
 pub fn sync_channel<T: Clone>(bound: usize) -> (Sender<T>, Receiver<T>) {
-    let buffer = Arc::new(Buffer::new(bound));
-    (Sender::new(buffer.clone()), Receiver::new(buffer))
+    ChannelBuilder::new(bound).build()
+}
+
+/// Create a new multiple-producer, multiple-consumer channel which is bounded by the total weight
+/// of the items in flight instead of the number of items, e.g. bytes with
+/// `|v: &Vec<f32>| v.len() * size_of::<f32>()`. This is useful when the packets sent vary in
+/// size.
+pub fn weighted_sync_channel<T, F>(budget: usize, weigher: F) -> (Sender<T>, Receiver<T>)
+where
+    T: Clone,
+    F: Fn(&T) -> usize + Send + Sync + 'static,
+{
+    ChannelBuilder::new(budget).weigher(weigher).build()
+}
+
+/// Helpers shared by the tests of the channel implementations.
+#[cfg(test)]
+mod test_util {
+    use std::fmt::Debug;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{ChannelError, ChannelReceiver, ChannelSender};
+
+    /// Check that `recv_timeout` gives up once the time runs out, wakes up for an item sent while
+    /// it waits instead of sleeping through the timeout, and notices the channel being corked.
+    /// The channel must be empty and `tx` its only sender.
+    pub fn check_recv_timeout<S, R>(tx: S, rx: &R, v: S::Item)
+    where
+        S: ChannelSender + Send + 'static,
+        S::Item: Send + PartialEq + Debug,
+        R: ChannelReceiver<Item = S::Item>,
+    {
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Ok(None));
+
+        let start = Instant::now();
+        let expected = v.clone();
+        let tx_thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(v).unwrap();
+            tx.cork();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(30)), Ok(Some(expected)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        tx_thread.join().unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(30)),
+            Err(ChannelError::IsCorked)
+        );
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::thread;
-    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
     use super::*;
 
@@ -143,6 +295,7 @@ mod test {
     }
 
     #[test]
+    #[allow(unused_variables)]
     fn blocking_many_tx() {
         let (tx1, rx) = sync_channel::<u8>(2);
         let tx2 = tx1.clone();
@@ -259,6 +412,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn blocking_shared_rx() {
         let (tx, rx1) = sync_channel::<u8>(2);
         let rx1 = SharedReceiver::from(rx1);
@@ -293,4 +447,442 @@ mod test {
         let c2 = rx2_thread.join().unwrap();
         assert_eq!(c1 + c2, 200);
     }
+
+    #[test]
+    fn full_policy_drop_newest() {
+        let (tx, rx) = ChannelBuilder::new(2)
+            .full_policy(FullPolicy::DropNewest)
+            .build();
+        tx.send(1u8).unwrap();
+        tx.send(2).unwrap();
+        // would block with the default policy
+        tx.send(3).unwrap();
+        assert_eq!(tx.try_send(4).unwrap(), None);
+        assert_eq!(tx.stats().unwrap().dropped, 2);
+
+        assert_eq!(rx.try_recv().unwrap(), Some(1));
+        assert_eq!(rx.try_recv().unwrap(), Some(2));
+        assert_eq!(rx.try_recv().unwrap(), None);
+    }
+
+    #[test]
+    fn full_policy_drop_oldest() {
+        let (tx, rx1) = ChannelBuilder::new(2)
+            .full_policy(FullPolicy::DropOldest)
+            .build();
+        let rx2 = rx1.clone();
+        tx.send(1u8).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(1));
+        tx.send(3).unwrap();
+        tx.send(4).unwrap();
+
+        // the slow receiver skipped ahead and the fast receiver only lost what it had not read
+        assert_eq!(rx2.try_recv().unwrap(), Some(3));
+        assert_eq!(rx1.try_recv().unwrap(), Some(3));
+        assert_eq!(rx1.try_recv().unwrap(), Some(4));
+        assert_eq!(rx2.try_recv().unwrap(), Some(4));
+        assert_eq!(rx1.try_recv().unwrap(), None);
+
+        let stats = rx1.stats().unwrap();
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.receivers, 2);
+        assert_eq!(stats.senders, 1);
+    }
+
+    #[test]
+    fn full_policy_overwrite() {
+        let (tx, rx1) = ChannelBuilder::new(2)
+            .full_policy(FullPolicy::Overwrite)
+            .build();
+        let rx2 = rx1.clone();
+        tx.send(1u8).unwrap();
+        tx.send(2).unwrap();
+        tx.send(3).unwrap();
+        tx.send(4).unwrap();
+        assert_eq!(tx.stats().unwrap().dropped, 2);
+        assert_eq!(rx1.try_recv().unwrap(), Some(1));
+        assert_eq!(rx1.try_recv().unwrap(), Some(4));
+
+        // rx1 has seen the newest item so it can't be replaced anymore
+        tx.send(5).unwrap();
+        assert_eq!(rx2.try_recv().unwrap(), Some(4));
+        assert_eq!(rx2.try_recv().unwrap(), Some(5));
+        assert_eq!(rx1.try_recv().unwrap(), Some(5));
+    }
+
+    #[test]
+    fn full_policy_never_blocks() {
+        let (tx, rx) = ChannelBuilder::new(4)
+            .full_policy(FullPolicy::DropOldest)
+            .build();
+        let tx_thread = thread::spawn(move || {
+            for i in 0..=200u8 {
+                tx.send(i).unwrap();
+            }
+        });
+        // nobody is reading, but the producer still finishes
+        tx_thread.join().unwrap();
+        let mut last = 0;
+        while let Ok(v) = rx.recv() {
+            assert!(v > last || last == 0);
+            last = v;
+        }
+        assert_eq!(last, 200);
+    }
+
+    #[test]
+    fn weighted_bound() {
+        let (tx, rx) = weighted_sync_channel(8, |v: &Vec<u8>| v.len());
+        tx.try_send(vec![1, 2, 3]).unwrap();
+        tx.try_send(vec![4, 5, 6, 7, 8]).unwrap();
+        // full by weight even though there are only two items
+        assert_eq!(tx.try_send(vec![9]).unwrap(), Some(vec![9]));
+        let stats = tx.stats().unwrap();
+        assert_eq!(stats.pending, 2);
+        assert_eq!(stats.pending_weight, 8);
+
+        assert_eq!(rx.try_recv().unwrap(), Some(vec![1, 2, 3]));
+        tx.try_send(vec![9]).unwrap();
+        tx.try_send(vec![10, 11]).unwrap();
+        assert_eq!(tx.try_send(vec![12]).unwrap(), Some(vec![12]));
+
+        // drain and make sure an oversized item still gets through an empty buffer
+        while rx.try_recv().unwrap().is_some() {}
+        assert_eq!(tx.stats().unwrap().pending_weight, 0);
+        tx.try_send((0..20).collect()).unwrap();
+        assert_eq!(tx.try_send(vec![0]).unwrap(), Some(vec![0]));
+        assert_eq!(rx.try_recv().unwrap().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn weighted_drop_oldest() {
+        let (tx, rx) = ChannelBuilder::new(4)
+            .weigher(|v: &Vec<u8>| v.len())
+            .full_policy(FullPolicy::DropOldest)
+            .build();
+        tx.send(vec![1]).unwrap();
+        tx.send(vec![2]).unwrap();
+        tx.send(vec![3, 3]).unwrap();
+        // needs to make room for 3 which means dropping all three items before it
+        tx.send(vec![4, 4, 4]).unwrap();
+        assert_eq!(tx.stats().unwrap().dropped, 3);
+        assert_eq!(rx.try_recv().unwrap(), Some(vec![4, 4, 4]));
+        assert_eq!(rx.try_recv().unwrap(), None);
+    }
+
+    #[test]
+    fn blocking_weighted() {
+        let (tx, rx) = weighted_sync_channel(16, |v: &Vec<u8>| v.len());
+        let tx_thread = thread::spawn(move || {
+            for i in 1..=100u8 {
+                tx.send(vec![i; (i % 10) as usize + 1]).unwrap();
+                assert!(tx.stats().unwrap().pending_weight <= 16);
+            }
+        });
+        let rx_thread = thread::spawn(move || {
+            for i in 1..=100u8 {
+                thread::sleep(pseudo_random_duration());
+                assert_eq!(rx.recv().unwrap()[0], i);
+            }
+            assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+        });
+        tx_thread.join().unwrap();
+        rx_thread.join().unwrap();
+    }
+
+    #[test]
+    fn resize_bound() {
+        let (tx, rx) = sync_channel::<u8>(2);
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(tx.try_send(3).unwrap(), Some(3));
+
+        rx.set_bound(3).unwrap();
+        assert_eq!(tx.bound().unwrap(), 3);
+        tx.try_send(3).unwrap();
+        assert_eq!(tx.try_send(4).unwrap(), Some(4));
+
+        // shrinking keeps what is already there but holds back new items until drained
+        tx.set_bound(1).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), Some(1));
+        assert_eq!(tx.try_send(4).unwrap(), Some(4));
+        assert_eq!(rx.try_recv().unwrap(), Some(2));
+        assert_eq!(tx.try_send(4).unwrap(), Some(4));
+        assert_eq!(rx.try_recv().unwrap(), Some(3));
+        tx.try_send(4).unwrap();
+        assert_eq!(tx.try_send(5).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn shrink_drop_oldest_gradually() {
+        let (tx, rx) = ChannelBuilder::new(4)
+            .full_policy(FullPolicy::DropOldest)
+            .build();
+        for i in 1..=4u8 {
+            tx.send(i).unwrap();
+        }
+        tx.set_bound(2).unwrap();
+        // each new item only pushes out one old one rather than cutting back to the bound
+        tx.send(5).unwrap();
+        assert_eq!(tx.stats().unwrap().dropped, 1);
+        assert_eq!(rx.pending().unwrap(), 4);
+        assert_eq!(rx.try_recv().unwrap(), Some(2));
+        assert_eq!(rx.try_recv().unwrap(), Some(3));
+        assert_eq!(rx.try_recv().unwrap(), Some(4));
+        tx.send(6).unwrap();
+        assert_eq!(rx.pending().unwrap(), 2);
+        assert_eq!(tx.stats().unwrap().dropped, 1);
+    }
+
+    #[test]
+    fn tune_bound() {
+        let (tx, rx) = sync_channel::<u8>(4);
+        // nobody has been blocked so the bound shrinks, but not below the minimum
+        assert_eq!(tx.tune_bound(3, 16).unwrap(), 3);
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        let tx_thread = thread::spawn(move || {
+            tx.send(3).unwrap();
+            tx
+        });
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(rx.try_recv().unwrap(), Some(0));
+        let tx = tx_thread.join().unwrap();
+        // the sender had to wait for room so it grows, but not above the maximum
+        assert_eq!(tx.tune_bound(3, 5).unwrap(), 5);
+        assert_eq!(rx.bound().unwrap(), 5);
+    }
+
+    #[test]
+    fn resize_wakes_blocked_senders() {
+        let (tx, rx) = sync_channel::<u8>(1);
+        tx.send(1).unwrap();
+        let tx_thread = thread::spawn(move || {
+            // blocks until the bound grows since nobody is reading
+            tx.send(2).unwrap();
+            tx.stats().unwrap()
+        });
+        thread::sleep(Duration::from_millis(10));
+        rx.set_bound(2).unwrap();
+        let stats = tx_thread.join().unwrap();
+        assert_eq!(stats.pending, 2);
+        assert!(stats.send_wait >= Duration::from_millis(5));
+        assert_eq!(rx.try_recv().unwrap(), Some(1));
+        assert_eq!(rx.try_recv().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn subscribe_positions() {
+        let (tx, rx1) = sync_channel::<u8>(4);
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        tx.try_send(3).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(1));
+
+        let latest = rx1.subscribe_from(Position::Latest);
+        let oldest = tx.subscribe_from(Position::Oldest);
+        let middle = rx1.subscribe_from(Position::Offset(2));
+        let past_end = rx1.subscribe_from(Position::Offset(100));
+        assert_eq!(latest.position().unwrap(), 3);
+        assert_eq!(oldest.position().unwrap(), 1);
+        assert_eq!(past_end.position().unwrap(), 3);
+        assert_eq!(latest.try_recv().unwrap(), None);
+
+        tx.try_send(4).unwrap();
+        assert_eq!(latest.try_recv().unwrap(), Some(4));
+        assert_eq!(past_end.try_recv().unwrap(), Some(4));
+        assert_eq!(middle.try_recv().unwrap(), Some(3));
+        assert_eq!(oldest.try_recv().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn dropping_slow_receiver_moves_window() {
+        let (tx, rx1) = sync_channel::<u8>(2);
+        let monitor = rx1.subscribe_from(Position::Latest);
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(1));
+        assert_eq!(rx1.try_recv().unwrap(), Some(2));
+        // the monitor has not read anything so it is holding the window
+        assert_eq!(tx.try_send(3).unwrap(), Some(3));
+        drop(monitor);
+        tx.try_send(3).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(3));
+    }
+
+    #[test]
+    fn retain_items() {
+        let (tx, rx1) = ChannelBuilder::new(2)
+            .retention(Retention::Items(3))
+            .build();
+        for i in 1..=5u8 {
+            tx.send(i).unwrap();
+            assert_eq!(rx1.recv().unwrap(), i);
+        }
+        // history does not count against the bound
+        let stats = tx.stats().unwrap();
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.retained, 3);
+        tx.try_send(6).unwrap();
+        tx.try_send(7).unwrap();
+
+        // a late subscriber can replay what was kept
+        let rx2 = tx.subscribe_from(Position::Oldest);
+        let replayed: Vec<u8> = (0..5).map(|_| rx2.try_recv().unwrap().unwrap()).collect();
+        assert_eq!(replayed, vec![3, 4, 5, 6, 7]);
+
+        // and an existing receiver can rewind
+        assert_eq!(rx1.try_recv().unwrap(), Some(6));
+        rx1.seek(Position::Offset(4)).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(5));
+        assert_eq!(rx1.try_recv().unwrap(), Some(6));
+        assert_eq!(rx1.try_recv().unwrap(), Some(7));
+        assert_eq!(rx1.try_recv().unwrap(), None);
+        assert_eq!(rx1.stats().unwrap().retained, 3);
+    }
+
+    #[test]
+    fn retain_duration() {
+        let (tx, rx) = ChannelBuilder::new(4)
+            .retention(Retention::Duration(Duration::from_millis(20)))
+            .build();
+        tx.send(1u8).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.stats().unwrap().retained, 2);
+
+        thread::sleep(Duration::from_millis(25));
+        tx.send(3).unwrap();
+        assert_eq!(rx.stats().unwrap().retained, 0);
+        rx.seek(Position::Oldest).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), Some(3));
+    }
+
+    #[test]
+    fn retained_history_not_kept_by_default() {
+        let (tx, rx) = sync_channel(2);
+        tx.send(1u8).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.stats().unwrap().retained, 0);
+        rx.seek(Position::Oldest).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), None);
+    }
+
+    #[test]
+    fn ttl_expires_unread_items() {
+        let (tx, rx1) = ChannelBuilder::new(4)
+            .ttl(Duration::from_millis(20))
+            .build();
+        let rx2 = rx1.clone();
+        tx.send(1u8).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx1.recv().unwrap(), 1);
+
+        thread::sleep(Duration::from_millis(25));
+        tx.send(3).unwrap();
+        // both items expired before rx2 read them, only rx1 got one of them first
+        assert_eq!(rx2.try_recv().unwrap(), Some(3));
+        assert_eq!(rx1.try_recv().unwrap(), Some(3));
+        let stats = rx1.stats().unwrap();
+        assert_eq!(stats.expired, 2);
+        assert_eq!(stats.pending, 0);
+    }
+
+    #[test]
+    fn expiry_keeps_history() {
+        let (tx, rx1) = ChannelBuilder::new(4)
+            .retention(Retention::Items(2))
+            .build();
+        let rx2 = rx1.clone();
+        let now = Instant::now();
+        tx.send_with_deadline(1u8, now + Duration::from_millis(20))
+            .unwrap();
+        tx.send_with_deadline(2, now + Duration::from_millis(20))
+            .unwrap();
+        tx.send_with_deadline(3, now).unwrap();
+        assert_eq!(rx1.recv().unwrap(), 1);
+        assert_eq!(rx2.recv().unwrap(), 1);
+        assert_eq!(rx1.recv().unwrap(), 2);
+        assert_eq!(rx2.recv().unwrap(), 2);
+
+        // the expired item is reclaimed without taking the history in front of it along
+        thread::sleep(Duration::from_millis(25));
+        tx.send(4).unwrap();
+        let stats = tx.stats().unwrap();
+        assert_eq!(stats.expired, 1);
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.retained, 2);
+
+        // items read in time are replayed even though their deadline has since passed, but the
+        // one which expired unread is still skipped
+        rx1.seek(Position::Oldest).unwrap();
+        let replayed: Vec<u8> = (0..2).map(|_| rx1.try_recv().unwrap().unwrap()).collect();
+        assert_eq!(replayed, vec![2, 4]);
+    }
+
+    #[test]
+    fn per_item_deadline() {
+        let (tx, rx) = sync_channel(4);
+        let now = Instant::now();
+        tx.send(1u8).unwrap();
+        tx.send_with_deadline(2, now).unwrap();
+        tx.send_with_deadline(3, now + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 3);
+        assert_eq!(rx.try_recv().unwrap(), None);
+        assert_eq!(rx.stats().unwrap().expired, 1);
+    }
+
+    #[test]
+    fn expiry_makes_room_for_blocked_sender() {
+        let (tx, rx) = sync_channel(1);
+        tx.send_with_deadline(1u8, Instant::now() + Duration::from_millis(20))
+            .unwrap();
+        // nobody reads, but the first item expiring frees its room
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), Some(2));
+        let stats = rx.stats().unwrap();
+        assert_eq!(stats.expired, 1);
+        assert!(stats.send_wait > Duration::default());
+    }
+
+    /// A receiver which leaves `recv_timeout` to the trait's default.
+    #[derive(Clone)]
+    struct Polling(Receiver<u8>);
+
+    impl ChannelReceiver for Polling {
+        type Item = u8;
+
+        fn id(&self) -> (usize, usize) {
+            self.0.id()
+        }
+
+        fn recv(&self) -> Result<u8, ChannelError> {
+            self.0.recv()
+        }
+
+        fn try_recv(&self) -> Result<Option<u8>, ChannelError> {
+            self.0.try_recv()
+        }
+
+        fn is_corked(&self) -> bool {
+            self.0.is_corked()
+        }
+
+        fn pending(&self) -> Result<usize, ChannelError> {
+            self.0.pending()
+        }
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = sync_channel(2);
+        test_util::check_recv_timeout(tx, &rx, 1u8);
+        let (tx, rx) = sync_channel(2);
+        test_util::check_recv_timeout(tx, &Polling(rx), 1);
+    }
 }
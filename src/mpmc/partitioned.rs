@@ -0,0 +1,167 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use super::buffer::next_channel_id;
+use super::{sync_channel, ChannelError, ChannelSender, Receiver, Sender};
+
+/// Function which picks the key an item is routed by.
+type KeyFn<T, K> = Arc<dyn Fn(&T) -> K + Send + Sync>;
+
+/// Which of `n` partitions a key belongs to, shared by everything which splits items by key so
+/// they all agree.
+pub(crate) fn partition_of<K: Hash>(key: &K, n: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % n as u64) as usize
+}
+
+/// `ChannelSender` which routes every item to one of several channels by hashing a key taken from
+/// the item, so items with the same key always go to the same channel. This lets stateful work be
+/// split between parallel workers (one per channel) while every key is still seen by only one of
+/// them and in order, which competing consumers on a `SharedReceiver` can't guarantee.
+///
+/// The partition of a key only depends on the key and the number of partitions, so it is stable
+/// between runs of the same build.
+pub struct PartitionedSender<T: Clone, K> {
+    partitions: Vec<Sender<T>>,
+    key: KeyFn<T, K>,
+    id: usize,
+}
+
+impl<T: Clone, K> Clone for PartitionedSender<T, K> {
+    fn clone(&self) -> Self {
+        Self {
+            partitions: self.partitions.clone(),
+            key: self.key.clone(),
+            id: self.id,
+        }
+    }
+}
+
+impl<T: Clone, K: Hash> PartitionedSender<T, K> {
+    /// Route items between `partitions` by the key `key` extracts from them.
+    ///
+    /// Panics if there are no partitions.
+    pub fn new<F>(partitions: Vec<Sender<T>>, key: F) -> Self
+    where
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        assert!(!partitions.is_empty(), "At least one partition is required");
+        Self {
+            partitions,
+            key: Arc::new(key),
+            id: next_channel_id(),
+        }
+    }
+
+    /// The number of channels items are split between.
+    pub fn partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// Which partition an item will be sent to.
+    pub fn partition_of(&self, v: &T) -> usize {
+        partition_of(&(self.key)(v), self.partitions.len())
+    }
+
+    /// The sender for one of the partitions.
+    pub fn partition(&self, i: usize) -> &Sender<T> {
+        &self.partitions[i]
+    }
+}
+
+impl<T: Clone, K: Hash> ChannelSender for PartitionedSender<T, K> {
+    type Item = T;
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        self.partitions[self.partition_of(&v)].send(v)
+    }
+
+    fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+        self.partitions[self.partition_of(&v)].try_send(v)
+    }
+
+    /// Cork every partition.
+    fn cork(&self) {
+        for tx in &self.partitions {
+            tx.cork();
+        }
+    }
+
+    /// Only true once every partition has been corked, since items for the others can still be
+    /// sent.
+    fn is_corked(&self) -> bool {
+        self.partitions.iter().all(|tx| tx.is_corked())
+    }
+
+    /// The total number of items pending over all partitions.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        self.partitions.iter().map(|tx| tx.pending()).sum()
+    }
+}
+
+/// Create `partitions` channels, each holding up to `bound` items, along with a sender which routes
+/// items between them by key and a receiver for each of them.
+pub fn partitioned_channel<T, K, F>(
+    partitions: usize,
+    bound: usize,
+    key: F,
+) -> (PartitionedSender<T, K>, Vec<Receiver<T>>)
+where
+    T: Clone,
+    K: Hash,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    let (senders, receivers) = (0..partitions).map(|_| sync_channel(bound)).unzip();
+    (PartitionedSender::new(senders, key), receivers)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::mpmc::ChannelReceiver;
+
+    #[test]
+    fn same_key_same_partition() {
+        let (tx, rxs) = partitioned_channel(3, 100, |v: &(u32, u32)| v.0);
+        assert_eq!(tx.partitions(), 3);
+        for i in 0..60 {
+            tx.send((i % 6, i)).unwrap();
+        }
+        drop(tx);
+
+        let mut owner = HashMap::new();
+        let mut total = 0;
+        for (p, rx) in rxs.iter().enumerate() {
+            let mut last = HashMap::new();
+            while let Ok((key, i)) = rx.recv() {
+                // every key lives in exactly one partition and stays in order there
+                assert_eq!(*owner.entry(key).or_insert(p), p);
+                assert!(last.insert(key, i).map_or(true, |prev| prev < i));
+                total += 1;
+            }
+        }
+        assert_eq!(total, 60);
+        assert_eq!(owner.len(), 6);
+    }
+
+    #[test]
+    fn cork_all_partitions() {
+        let (tx, rxs) = partitioned_channel(2, 4, |v: &u8| *v);
+        tx.send(1).unwrap();
+        assert_eq!(tx.pending(), Ok(1));
+        tx.cork();
+        assert!(tx.is_corked());
+        assert_eq!(tx.send(1), Err(ChannelError::IsCorked));
+        let p = tx.partition_of(&1);
+        assert_eq!(rxs[p].recv(), Ok(1));
+        assert_eq!(rxs[1 - p].recv(), Err(ChannelError::IsCorked));
+    }
+}
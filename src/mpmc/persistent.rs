@@ -0,0 +1,875 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::mpmc::buffer::next_channel_id;
+use crate::mpmc::{ChannelError, ChannelReceiver, ChannelSender, Codec};
+
+/// Segments are rolled over once they reach this many bytes unless configured otherwise.
+const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+const SEGMENT_EXTENSION: &str = "log";
+const CURSOR_EXTENSION: &str = "cursor";
+/// Marker file which exists once the log has been corked.
+const CORKED_FILE: &str = "corked";
+
+/// Position of a named cursor and, if it has been reading, the segment file it is reading from.
+struct Cursor {
+    id: usize,
+    position: u64,
+    reader: Option<SegmentReader>,
+    /// Shared with every receiver using the cursor, so it isn't removed from under them.
+    users: Arc<()>,
+}
+
+/// Sequential reader of records within a single segment.
+struct SegmentReader {
+    /// First index stored in the segment.
+    segment: u64,
+    /// Index of the record the file is positioned at.
+    next: u64,
+    file: BufReader<File>,
+}
+
+/// Lockable inner working components of the log
+struct LogInner {
+    dir: PathBuf,
+    segment_bytes: u64,
+    /// The first index stored in each segment on disk, oldest first. The last segment is the one
+    /// being appended to.
+    segments: Vec<u64>,
+    writer: File,
+    /// Number of bytes written to the last segment.
+    written: u64,
+    /// Index the next record will be written at.
+    end: u64,
+    /// Every durable cursor, including those which were loaded from disk but do not currently
+    /// have a receiver.
+    cursors: HashMap<String, Cursor>,
+    next_cursor_id: usize,
+}
+
+struct Shared<T, C> {
+    inner: Mutex<LogInner>,
+    on_new_data: Condvar,
+    corked: AtomicBool,
+    sender_count: AtomicUsize,
+    codec: C,
+    id: usize,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+/// A channel backed by a segmented log on disk with named, durable cursors, so neither the items
+/// nor how far each consumer has read through them are lost when the process restarts.
+///
+/// Every item is written as a length-prefixed record using the log's `Codec`, and the position of
+/// each named cursor is persisted whenever it reads an item. Both are synced to disk before the
+/// call returns, so they survive a crash as well. Opening the same directory again picks up where
+/// the last process left off. Segments are deleted once every durable cursor has read past them;
+/// remove cursors which are no longer needed with `remove_receiver` or they will hold on to data
+/// forever.
+///
+/// Corking a sender, or dropping the last one, only corks the log for this process, so a producer
+/// which exits cleanly does not stop the next one from appending after a restart. Use
+/// `cork_durably` to mark the log as finished for good, and `uncork` to undo that.
+///
+/// Senders never block since the log is only bounded by the disk. Only one process may have a
+/// given log directory open at a time.
+pub struct PersistentLog<T, C> {
+    shared: Arc<Shared<T, C>>,
+}
+
+impl<T, C> Clone for PersistentLog<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Clone, C: Codec<T>> PersistentLog<T, C> {
+    /// Open or create a log in the given directory.
+    pub fn open<P: AsRef<Path>>(dir: P, codec: C) -> Result<Self, ChannelError> {
+        Self::with_segment_size(dir, codec, DEFAULT_SEGMENT_BYTES)
+    }
+
+    /// Open or create a log in the given directory which starts a new segment file whenever the
+    /// current one grows past `segment_bytes`.
+    pub fn with_segment_size<P: AsRef<Path>>(
+        dir: P,
+        codec: C,
+        segment_bytes: u64,
+    ) -> Result<Self, ChannelError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut segments = Vec::new();
+        let mut cursors = HashMap::new();
+        let mut next_cursor_id = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let stem = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) => stem.to_owned(),
+                None => continue,
+            };
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(SEGMENT_EXTENSION) => {
+                    if let Ok(first) = stem.parse::<u64>() {
+                        segments.push(first);
+                    }
+                }
+                Some(CURSOR_EXTENSION) => {
+                    let position = read_cursor(&path)?;
+                    cursors.insert(
+                        stem,
+                        Cursor {
+                            id: next_cursor_id,
+                            position,
+                            reader: None,
+                            users: Arc::new(()),
+                        },
+                    );
+                    next_cursor_id += 1;
+                }
+                _ => {}
+            }
+        }
+        segments.sort_unstable();
+        if segments.is_empty() {
+            segments.push(0);
+        }
+
+        // recover the end of the log from the last segment, dropping any record which was only
+        // partially written before the last process stopped
+        let last = *segments.last().unwrap();
+        let mut writer = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(segment_path(&dir, last))?;
+        let (count, written) = scan_segment(&mut writer)?;
+        writer.set_len(written)?;
+        writer.sync_all()?;
+        sync_dir(&dir)?;
+        let end = last + count;
+
+        // cursors may point at segments which were removed or records which were lost
+        for cursor in cursors.values_mut() {
+            cursor.position = cursor.position.max(segments[0]).min(end);
+        }
+
+        let corked = dir.join(CORKED_FILE).exists();
+        Ok(Self {
+            shared: Arc::new(Shared {
+                inner: Mutex::new(LogInner {
+                    dir,
+                    segment_bytes,
+                    segments,
+                    writer,
+                    written,
+                    end,
+                    cursors,
+                    next_cursor_id,
+                }),
+                on_new_data: Condvar::new(),
+                corked: AtomicBool::new(corked),
+                sender_count: AtomicUsize::new(0),
+                codec,
+                id: next_channel_id(),
+                _phantom: PhantomData,
+            }),
+        })
+    }
+
+    /// Create a new sender which appends to this log.
+    pub fn sender(&self) -> PersistentSender<T, C> {
+        self.shared.sender_count.fetch_add(1, Ordering::AcqRel);
+        PersistentSender {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Get a receiver for the durable cursor with the given name, creating the cursor at the
+    /// oldest item in the log if it does not exist yet. Receivers with the same name share the
+    /// same cursor. Names may only contain ASCII letters, digits, `-` and `_`.
+    pub fn receiver(&self, name: &str) -> Result<PersistentReceiver<T, C>, ChannelError> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ChannelError::Io(ErrorKind::InvalidInput));
+        }
+        let mut inner = self.shared.inner.lock()?;
+        if !inner.cursors.contains_key(name) {
+            let id = inner.next_cursor_id;
+            inner.next_cursor_id += 1;
+            let position = inner.segments[0];
+            write_cursor(&inner.dir, name, position)?;
+            inner.cursors.insert(
+                name.to_owned(),
+                Cursor {
+                    id,
+                    position,
+                    reader: None,
+                    users: Arc::new(()),
+                },
+            );
+        }
+        let cursor = inner.cursor(name);
+        Ok(PersistentReceiver {
+            shared: self.shared.clone(),
+            name: name.to_owned(),
+            cursor_id: cursor.id,
+            _user: cursor.users.clone(),
+        })
+    }
+
+    /// Forget a durable cursor so it no longer holds on to old segments. This fails with `Other`
+    /// while any receiver using the cursor is still around.
+    pub fn remove_receiver(&self, name: &str) -> Result<(), ChannelError> {
+        let mut inner = self.shared.inner.lock()?;
+        if inner
+            .cursors
+            .get(name)
+            .is_some_and(|c| Arc::strong_count(&c.users) > 1)
+        {
+            return Err(ChannelError::Io(ErrorKind::Other));
+        }
+        if inner.cursors.remove(name).is_some() {
+            fs::remove_file(cursor_path(&inner.dir, name))?;
+            inner.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// Check if the log has been corked and will not accept any new items.
+    pub fn is_corked(&self) -> bool {
+        self.shared.is_corked()
+    }
+
+    /// Cork the log and record that on disk, so it stays corked when it is opened again.
+    pub fn cork_durably(&self) -> Result<(), ChannelError> {
+        let inner = self.shared.inner.lock()?;
+        File::create(inner.dir.join(CORKED_FILE))?.sync_all()?;
+        sync_dir(&inner.dir)?;
+        std::mem::drop(inner);
+        self.shared.cork();
+        Ok(())
+    }
+
+    /// Let new items be appended to a log which has been corked, whether in this process or
+    /// durably by an earlier one.
+    pub fn uncork(&self) -> Result<(), ChannelError> {
+        let inner = self.shared.inner.lock()?;
+        match fs::remove_file(inner.dir.join(CORKED_FILE)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => sync_dir(&inner.dir)?,
+        }
+        self.shared.corked.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T, C: Codec<T>> Shared<T, C> {
+    fn is_corked(&self) -> bool {
+        self.corked.load(Ordering::Acquire)
+    }
+
+    fn cork(&self) {
+        // take the lock so a receiver can't miss the notification between checking and waiting
+        let inner = self.inner.lock();
+        self.corked.store(true, Ordering::Release);
+        std::mem::drop(inner);
+        self.on_new_data.notify_all();
+    }
+
+    fn send(&self, v: &T) -> Result<(), ChannelError> {
+        if self.is_corked() {
+            return Err(ChannelError::IsCorked);
+        }
+        let mut record = vec![0u8; 4];
+        self.codec.encode(v, &mut record)?;
+        let len = record.len() - 4;
+        if len > u32::MAX as usize {
+            return Err(ChannelError::Io(ErrorKind::InvalidInput));
+        }
+        record[..4].copy_from_slice(&(len as u32).to_le_bytes());
+
+        self.inner.lock()?.append(&record)?;
+        self.on_new_data.notify_all();
+        Ok(())
+    }
+
+    fn recv(&self, name: &str) -> Result<T, ChannelError> {
+        self.recv_until(name, None)
+            .map(|v| v.expect("Received nothing without a deadline"))
+    }
+
+    fn recv_until(&self, name: &str, deadline: Option<Instant>) -> Result<Option<T>, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        while inner.cursor(name).position >= inner.end {
+            if self.is_corked() {
+                return Err(ChannelError::IsCorked);
+            }
+            inner = match deadline {
+                None => self.on_new_data.wait(inner)?,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(None);
+                    }
+                    self.on_new_data.wait_timeout(inner, deadline - now)?.0
+                }
+            };
+        }
+        self.take(inner, name).map(Some)
+    }
+
+    fn try_recv(&self, name: &str) -> Result<Option<T>, ChannelError> {
+        let inner = self.inner.lock()?;
+        if inner.cursor(name).position < inner.end {
+            self.take(inner, name).map(Some)
+        } else if self.is_corked() {
+            Err(ChannelError::IsCorked)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Read the record under a cursor and advance it. The cursor must not be at the end. It is only
+    /// advanced once the record has been decoded, so an item which can't be decoded is not lost.
+    /// The lock is held throughout so receivers sharing the cursor can't read the same record.
+    fn take(&self, mut inner: MutexGuard<LogInner>, name: &str) -> Result<T, ChannelError> {
+        let record = inner.read(name)?;
+        let v = self.codec.decode(&record)?;
+        inner.advance(name)?;
+        Ok(v)
+    }
+}
+
+impl LogInner {
+    fn cursor(&self, name: &str) -> &Cursor {
+        self.cursors.get(name).expect("Cursor name is invalid")
+    }
+
+    /// Write a complete record to the end of the log.
+    fn append(&mut self, record: &[u8]) -> Result<(), ChannelError> {
+        if self.written > 0 && self.written + record.len() as u64 > self.segment_bytes {
+            // roll over to a new segment
+            self.writer = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .open(segment_path(&self.dir, self.end))?;
+            sync_dir(&self.dir)?;
+            self.segments.push(self.end);
+            self.written = 0;
+        }
+        self.writer.write_all(record)?;
+        self.writer.sync_data()?;
+        self.written += record.len() as u64;
+        self.end += 1;
+        Ok(())
+    }
+
+    /// Read the record under a cursor without moving the cursor.
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, ChannelError> {
+        let position = self.cursor(name).position;
+        // the segment holding the record is the last one to start at or before it
+        let segment = *self
+            .segments
+            .iter()
+            .rev()
+            .find(|&&first| first <= position)
+            .expect("Cursor is before the start of the log");
+        let dir = self.dir.clone();
+        let cursor = self.cursors.get_mut(name).unwrap();
+
+        let reusable = match &cursor.reader {
+            Some(reader) => reader.segment == segment && reader.next == position,
+            None => false,
+        };
+        if !reusable {
+            let mut file = BufReader::new(File::open(segment_path(&dir, segment))?);
+            for _ in segment..position {
+                read_record(&mut file)?;
+            }
+            cursor.reader = Some(SegmentReader {
+                segment,
+                next: position,
+                file,
+            });
+        }
+        let reader = cursor.reader.as_mut().unwrap();
+        let record = read_record(&mut reader.file)?;
+        reader.next += 1;
+        Ok(record)
+    }
+
+    /// Durably move a cursor past the record it just read.
+    fn advance(&mut self, name: &str) -> Result<(), ChannelError> {
+        let cursor = self.cursors.get_mut(name).expect("Cursor name is invalid");
+        let position = cursor.position;
+        write_cursor(&self.dir, name, position + 1)?;
+        cursor.position += 1;
+        if self.segments.contains(&position) {
+            // we just moved into this segment, so the previous one may no longer be needed
+            self.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// Delete every segment which all of the durable cursors have read past.
+    fn truncate(&mut self) -> Result<(), ChannelError> {
+        let oldest = match self.cursors.values().map(|c| c.position).min() {
+            Some(oldest) => oldest,
+            // keep everything for a receiver which may be created later
+            None => return Ok(()),
+        };
+        while self.segments.len() > 1 && self.segments[1] <= oldest {
+            let first = self.segments.remove(0);
+            fs::remove_file(segment_path(&self.dir, first))?;
+        }
+        Ok(())
+    }
+}
+
+/// `ChannelSender` implementation which appends to a `PersistentLog`.
+pub struct PersistentSender<T, C: Codec<T>> {
+    shared: Arc<Shared<T, C>>,
+}
+
+impl<T, C: Codec<T>> Clone for PersistentSender<T, C> {
+    fn clone(&self) -> Self {
+        self.shared.sender_count.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T, C: Codec<T>> Drop for PersistentSender<T, C> {
+    fn drop(&mut self) {
+        if self.shared.sender_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // this was the last sender so it is time to cork it off, as with the in-memory
+            // channels. This is not persisted, so the log can be appended to after a restart.
+            self.shared.cork();
+        }
+    }
+}
+
+impl<T: Clone, C: Codec<T>> ChannelSender for PersistentSender<T, C> {
+    type Item = T;
+
+    fn id(&self) -> usize {
+        self.shared.id
+    }
+
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        self.shared.send(&v)
+    }
+
+    /// The log is only bounded by the disk, so this never hands the item back.
+    fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+        self.shared.send(&v).map(|_| None)
+    }
+
+    /// Cork the log for this process only. Use `PersistentLog::cork_durably` to keep it corked
+    /// after a restart.
+    fn cork(&self) {
+        self.shared.cork();
+    }
+
+    fn is_corked(&self) -> bool {
+        self.shared.is_corked()
+    }
+
+    /// The number of items the furthest behind durable cursor has not read yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        let inner = self.shared.inner.lock()?;
+        let oldest = inner.cursors.values().map(|c| c.position).min();
+        Ok(oldest.map_or(0, |oldest| (inner.end - oldest) as usize))
+    }
+}
+
+/// `ChannelReceiver` implementation which reads a `PersistentLog` using a named durable cursor.
+/// Clones share the same cursor.
+pub struct PersistentReceiver<T, C: Codec<T>> {
+    shared: Arc<Shared<T, C>>,
+    name: String,
+    cursor_id: usize,
+    /// Keeps the cursor from being removed while this receiver exists.
+    _user: Arc<()>,
+}
+
+impl<T, C: Codec<T>> Clone for PersistentReceiver<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            name: self.name.clone(),
+            cursor_id: self.cursor_id,
+            _user: self._user.clone(),
+        }
+    }
+}
+
+impl<T: Clone, C: Codec<T>> ChannelReceiver for PersistentReceiver<T, C> {
+    type Item = T;
+
+    fn id(&self) -> (usize, usize) {
+        (self.shared.id, self.cursor_id)
+    }
+
+    fn recv(&self) -> Result<T, ChannelError> {
+        self.shared.recv(&self.name)
+    }
+
+    fn try_recv(&self) -> Result<Option<T>, ChannelError> {
+        self.shared.try_recv(&self.name)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        self.shared
+            .recv_until(&self.name, Some(Instant::now() + timeout))
+    }
+
+    fn is_corked(&self) -> bool {
+        self.shared.is_corked()
+    }
+
+    /// The number of items this cursor has not read yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        let inner = self.shared.inner.lock()?;
+        Ok((inner.end - inner.cursor(&self.name).position) as usize)
+    }
+}
+
+impl<T, C: Codec<T>> PersistentReceiver<T, C> {
+    /// Name of the durable cursor this receiver reads with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index in the log of the next item this receiver will read.
+    pub fn position(&self) -> Result<u64, ChannelError> {
+        Ok(self.shared.inner.lock()?.cursor(&self.name).position)
+    }
+}
+
+fn segment_path(dir: &Path, first: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", first, SEGMENT_EXTENSION))
+}
+
+fn cursor_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, CURSOR_EXTENSION))
+}
+
+fn read_cursor(path: &Path) -> Result<u64, ChannelError> {
+    let mut buf = [0u8; 8];
+    File::open(path)?.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Replace the persisted position of a cursor. This writes a new file and renames it over the old
+/// one so a crash can't leave a half written position behind, syncing both the file and the
+/// directory so the new position survives a crash too.
+fn write_cursor(dir: &Path, name: &str, position: u64) -> Result<(), ChannelError> {
+    let path = cursor_path(dir, name);
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(&position.to_le_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp, path)?;
+    sync_dir(dir)?;
+    Ok(())
+}
+
+/// Make sure files created, renamed or removed in a directory are on disk.
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Syncing a directory means opening it, which is only possible on Unix.
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Read one length-prefixed record.
+fn read_record<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    r.read_exact(&mut len)?;
+    let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+    r.read_exact(&mut record)?;
+    Ok(record)
+}
+
+/// Count the complete records in a segment and find where the last one ends.
+fn scan_segment(file: &mut File) -> io::Result<(u64, u64)> {
+    let size = file.metadata()?.len();
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let (mut count, mut end) = (0, 0);
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).is_err() {
+            break;
+        }
+        let next = end + 4 + u32::from_le_bytes(len) as u64;
+        if next > size {
+            break;
+        }
+        reader.seek(SeekFrom::Start(next))?;
+        end = next;
+        count += 1;
+    }
+    Ok((count, end))
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::thread;
+
+    use super::*;
+    use crate::mpmc::test_util::check_recv_timeout;
+    use crate::mpmc::RawLeCodec;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cgraph-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn segment_count(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    == Some(SEGMENT_EXTENSION)
+            })
+            .count()
+    }
+
+    #[test]
+    fn resume_after_restart() {
+        let dir = temp_dir("resume");
+        {
+            let log = PersistentLog::open(&dir, RawLeCodec).unwrap();
+            let rx = log.receiver("reader").unwrap();
+            let tx = log.sender();
+            for i in 0..10 {
+                tx.send(i).unwrap();
+            }
+            for i in 0..4 {
+                assert_eq!(rx.recv().unwrap(), i);
+            }
+            // dropping the last sender corks the log, but only until it is opened again
+            drop(tx);
+            assert!(log.is_corked());
+        }
+        let log = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        assert!(!log.is_corked());
+        let rx = log.receiver("reader").unwrap();
+        assert_eq!(rx.position().unwrap(), 4);
+        assert_eq!(rx.pending().unwrap(), 6);
+        let tx = log.sender();
+        tx.send(10).unwrap();
+        drop(tx);
+        for i in 4..=10 {
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn durable_cork() {
+        let dir = temp_dir("cork");
+        let log: PersistentLog<u32, _> = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        log.sender().send(1).unwrap();
+        log.cork_durably().unwrap();
+        drop(log);
+
+        let log: PersistentLog<u32, _> = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        assert!(log.is_corked());
+        let rx = log.receiver("reader").unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.try_recv(), Err(ChannelError::IsCorked));
+        assert_eq!(log.sender().send(2), Err(ChannelError::IsCorked));
+
+        log.uncork().unwrap();
+        log.sender().send(2).unwrap();
+        drop(log);
+        let log: PersistentLog<u32, _> = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        assert!(!log.is_corked());
+        let rx = log.receiver("reader").unwrap();
+        assert_eq!(rx.recv().unwrap(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Fails to decode the first record it is given.
+    struct FailOnce(AtomicBool);
+
+    impl Codec<u32> for FailOnce {
+        fn encode(&self, v: &u32, buf: &mut Vec<u8>) -> Result<(), ChannelError> {
+            RawLeCodec.encode(v, buf)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<u32, ChannelError> {
+            if self.0.swap(false, Ordering::AcqRel) {
+                return Err(ChannelError::Io(ErrorKind::InvalidData));
+            }
+            RawLeCodec.decode(bytes)
+        }
+    }
+
+    #[test]
+    fn decode_errors_keep_the_item() {
+        let dir = temp_dir("decode");
+        let log = PersistentLog::open(&dir, FailOnce(AtomicBool::new(true))).unwrap();
+        let rx = log.receiver("reader").unwrap();
+        let tx = log.sender();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Err(ChannelError::Io(ErrorKind::InvalidData)));
+        assert_eq!(rx.position().unwrap(), 0);
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        drop(rx);
+        drop(log);
+        let log: PersistentLog<u32, _> = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        assert_eq!(log.receiver("reader").unwrap().position().unwrap(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncate_read_segments() {
+        let dir = temp_dir("truncate");
+        // each record is 8 bytes, so 4 records per segment
+        let log = PersistentLog::with_segment_size(&dir, RawLeCodec, 32).unwrap();
+        let fast = log.receiver("fast").unwrap();
+        let slow = log.receiver("slow").unwrap();
+        let tx = log.sender();
+        for i in 0..12 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(segment_count(&dir), 3);
+        for i in 0..12 {
+            assert_eq!(fast.recv().unwrap(), i);
+        }
+        // the slow reader still needs everything
+        assert_eq!(segment_count(&dir), 3);
+        for i in 0..9 {
+            assert_eq!(slow.recv().unwrap(), i);
+        }
+        assert_eq!(segment_count(&dir), 1);
+
+        // a cursor which is no longer needed stops holding on to data, but only once nothing is
+        // using it
+        let stale = log.receiver("stale").unwrap();
+        let stale_clone = stale.clone();
+        drop(stale);
+        assert_eq!(
+            log.remove_receiver("stale"),
+            Err(ChannelError::Io(ErrorKind::Other))
+        );
+        assert!(stale_clone.pending().is_ok());
+        drop(stale_clone);
+        for i in 12..20 {
+            tx.send(i).unwrap();
+        }
+        for _ in 9..20 {
+            slow.recv().unwrap();
+            fast.try_recv().unwrap();
+        }
+        assert_eq!(segment_count(&dir), 3);
+        log.remove_receiver("stale").unwrap();
+        assert_eq!(segment_count(&dir), 1);
+
+        // a new reader starts at the oldest item still around
+        assert_eq!(log.receiver("late").unwrap().recv().unwrap(), 16);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recover_partial_record() {
+        let dir = temp_dir("partial");
+        {
+            let log = PersistentLog::open(&dir, RawLeCodec).unwrap();
+            let tx = log.sender();
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+        }
+        // simulate a crash part way through writing a record
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(segment_path(&dir, 0))
+            .unwrap();
+        file.write_all(&[4, 0, 0, 0, 3]).unwrap();
+        drop(file);
+
+        let log = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        let rx = log.receiver("reader").unwrap();
+        let tx = log.sender();
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.recv().unwrap(), 3);
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn blocking_shared_cursor() {
+        let dir = temp_dir("blocking");
+        let log = PersistentLog::with_segment_size(&dir, RawLeCodec, 64).unwrap();
+        let rx1 = log.receiver("workers").unwrap();
+        let rx2 = rx1.clone();
+        assert_eq!(rx1.id(), rx2.id());
+        let tx = log.sender();
+
+        let tx_thread = thread::spawn(move || {
+            for i in 0..200 {
+                tx.send(i).unwrap();
+            }
+        });
+        let rx_threads: Vec<_> = vec![rx1, rx2]
+            .into_iter()
+            .map(|rx| {
+                thread::spawn(move || {
+                    let mut sum = 0;
+                    while let Ok(v) = rx.recv() {
+                        sum += v;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        tx_thread.join().unwrap();
+        let sum: u32 = rx_threads.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, (0..200).sum());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let dir = temp_dir("recv-timeout");
+        let log = PersistentLog::open(&dir, RawLeCodec).unwrap();
+        let rx = log.receiver("reader").unwrap();
+        check_recv_timeout(log.sender(), &rx, 7u32);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::buffer::next_channel_id;
+use super::{ChannelError, ChannelReceiver, ChannelSender};
+
+/// Function which picks the priority of an item sent without one.
+type PriorityFn<T> = Box<dyn Fn(&T) -> u8 + Send + Sync>;
+
+struct Stored<T> {
+    value: T,
+    priority: u8,
+    /// Number of cursors which have not read this item yet.
+    readers: usize,
+}
+
+struct PriorityInner<T> {
+    /// Items by the order they were sent in.
+    items: HashMap<u64, Stored<T>>,
+    /// Items each cursor has yet to read, highest priority first and in the order they were sent
+    /// within a priority.
+    cursors: HashMap<usize, BinaryHeap<(u8, Reverse<u64>)>>,
+    next_seq: u64,
+    next_cursor_id: usize,
+    bound: usize,
+}
+
+impl<T> PriorityInner<T> {
+    /// Mark an item as read by one more cursor, removing it once every cursor has read it.
+    fn release(&mut self, seq: u64) -> bool {
+        let stored = self.items.get_mut(&seq).expect("Item read twice");
+        stored.readers -= 1;
+        if stored.readers == 0 {
+            self.items.remove(&seq);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Like `Buffer`, except every receiver gets the highest priority item it has not read yet instead
+/// of the oldest. Since each receiver can be at a different point, they each keep a heap of what
+/// they have left to read and items are kept until every receiver has read them.
+struct PriorityBuffer<T> {
+    inner: Mutex<PriorityInner<T>>,
+    on_new_data: Condvar,
+    on_data_consumed: Condvar,
+    corked: AtomicBool,
+    sender_count: AtomicUsize,
+    priority: PriorityFn<T>,
+    id: usize,
+}
+
+impl<T: Clone> PriorityBuffer<T> {
+    fn send(&self, v: T, priority: u8) -> Result<(), ChannelError> {
+        let mut inner = self.inner.lock()?;
+        while inner.items.len() >= inner.bound && !self.is_corked() {
+            inner = self.on_data_consumed.wait(inner)?;
+        }
+        self.push(inner, v, priority)
+    }
+
+    fn try_send(&self, v: T, priority: u8) -> Result<Option<T>, ChannelError> {
+        let inner = self.inner.lock()?;
+        if inner.items.len() >= inner.bound && !self.is_corked() {
+            return Ok(Some(v));
+        }
+        self.push(inner, v, priority).map(|_| None)
+    }
+
+    fn push(
+        &self,
+        mut inner: MutexGuard<PriorityInner<T>>,
+        value: T,
+        priority: u8,
+    ) -> Result<(), ChannelError> {
+        if self.is_corked() {
+            return Err(ChannelError::IsCorked);
+        }
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        for heap in inner.cursors.values_mut() {
+            heap.push((priority, Reverse(seq)));
+        }
+        let readers = inner.cursors.len();
+        // with nobody to read it yet, the item waits for the first receiver
+        inner.items.insert(
+            seq,
+            Stored {
+                value,
+                priority,
+                readers,
+            },
+        );
+        drop(inner);
+        self.on_new_data.notify_all();
+        Ok(())
+    }
+
+    fn recv(&self, cursor_id: usize) -> Result<T, ChannelError> {
+        self.recv_until(cursor_id, None)
+            .map(|v| v.expect("Received nothing without a deadline"))
+    }
+
+    fn recv_until(
+        &self,
+        cursor_id: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Option<T>, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        loop {
+            if let Some(v) = self.take(&mut inner, cursor_id) {
+                return Ok(Some(v));
+            }
+            if self.is_corked() {
+                return Err(ChannelError::IsCorked);
+            }
+            inner = match deadline {
+                None => self.on_new_data.wait(inner)?,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(None);
+                    }
+                    self.on_new_data.wait_timeout(inner, deadline - now)?.0
+                }
+            };
+        }
+    }
+
+    fn try_recv(&self, cursor_id: usize) -> Result<Option<T>, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        match self.take(&mut inner, cursor_id) {
+            Some(v) => Ok(Some(v)),
+            None if self.is_corked() => Err(ChannelError::IsCorked),
+            None => Ok(None),
+        }
+    }
+
+    fn take(&self, inner: &mut PriorityInner<T>, cursor_id: usize) -> Option<T> {
+        let (_, Reverse(seq)) = inner
+            .cursors
+            .get_mut(&cursor_id)
+            .expect("Cursor id is invalid")
+            .pop()?;
+        let v = inner.items[&seq].value.clone();
+        if inner.release(seq) {
+            self.on_data_consumed.notify_one();
+        }
+        Some(v)
+    }
+
+    /// Create a cursor which has every item currently held left to read.
+    fn new_receiver(&self) -> Result<usize, ChannelError> {
+        let mut inner = self.inner.lock()?;
+        let id = inner.next_cursor_id;
+        inner.next_cursor_id += 1;
+        let mut heap = BinaryHeap::with_capacity(inner.items.len());
+        for (seq, stored) in inner.items.iter_mut() {
+            stored.readers += 1;
+            heap.push((stored.priority, Reverse(*seq)));
+        }
+        inner.cursors.insert(id, heap);
+        Ok(id)
+    }
+
+    fn drop_receiver(&self, cursor_id: usize) -> Result<(), ChannelError> {
+        let mut inner = self.inner.lock()?;
+        let heap = inner.cursors.remove(&cursor_id).unwrap_or_default();
+        let mut freed = false;
+        for (_, Reverse(seq)) in heap {
+            freed |= inner.release(seq);
+        }
+        drop(inner);
+        if freed {
+            self.on_data_consumed.notify_all();
+        }
+        Ok(())
+    }
+
+    fn is_corked(&self) -> bool {
+        self.corked.load(Ordering::Acquire)
+    }
+
+    fn cork(&self) {
+        self.corked.store(true, Ordering::Release);
+        self.on_data_consumed.notify_all();
+        self.on_new_data.notify_all();
+    }
+}
+
+/// `ChannelSender` for a channel created with `priority_channel`.
+pub struct PrioritySender<T: Clone> {
+    buffer: Arc<PriorityBuffer<T>>,
+}
+
+impl<T: Clone> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        self.buffer.sender_count.fetch_add(1, Ordering::AcqRel);
+        Self {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Drop for PrioritySender<T> {
+    fn drop(&mut self) {
+        if self.buffer.sender_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.buffer.cork();
+        }
+    }
+}
+
+impl<T: Clone> PrioritySender<T> {
+    /// Send an item with the given priority instead of the one the channel would pick for it.
+    pub fn send_with(&self, v: T, priority: u8) -> Result<(), ChannelError> {
+        self.buffer.send(v, priority)
+    }
+
+    /// Try to send an item with the given priority, handing it back if the channel is full.
+    pub fn try_send_with(&self, v: T, priority: u8) -> Result<Option<T>, ChannelError> {
+        self.buffer.try_send(v, priority)
+    }
+}
+
+impl<T: Clone> ChannelSender for PrioritySender<T> {
+    type Item = T;
+
+    fn id(&self) -> usize {
+        self.buffer.id
+    }
+
+    /// Send an item with the priority the channel picks for it.
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        let priority = (self.buffer.priority)(&v);
+        self.buffer.send(v, priority)
+    }
+
+    fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+        let priority = (self.buffer.priority)(&v);
+        self.buffer.try_send(v, priority)
+    }
+
+    fn cork(&self) {
+        self.buffer.cork()
+    }
+
+    fn is_corked(&self) -> bool {
+        self.buffer.is_corked()
+    }
+
+    /// The number of items held which some receiver has not read yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        Ok(self.buffer.inner.lock()?.items.len())
+    }
+}
+
+/// `ChannelReceiver` for a channel created with `priority_channel`. Clones read every item
+/// independently, starting with whatever the channel still holds.
+pub struct PriorityReceiver<T: Clone> {
+    buffer: Arc<PriorityBuffer<T>>,
+    id: usize,
+}
+
+impl<T: Clone> Clone for PriorityReceiver<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.buffer.clone())
+    }
+}
+
+impl<T: Clone> Drop for PriorityReceiver<T> {
+    fn drop(&mut self) {
+        let _ = self.buffer.drop_receiver(self.id);
+    }
+}
+
+impl<T: Clone> PriorityReceiver<T> {
+    fn new(buffer: Arc<PriorityBuffer<T>>) -> Self {
+        let id = buffer.new_receiver().unwrap();
+        Self { buffer, id }
+    }
+}
+
+impl<T: Clone> ChannelReceiver for PriorityReceiver<T> {
+    type Item = T;
+
+    fn id(&self) -> (usize, usize) {
+        (self.buffer.id, self.id)
+    }
+
+    /// Receive the highest priority item this receiver has not read yet, or the oldest of them if
+    /// several share the highest priority.
+    fn recv(&self) -> Result<T, ChannelError> {
+        self.buffer.recv(self.id)
+    }
+
+    fn try_recv(&self) -> Result<Option<T>, ChannelError> {
+        self.buffer.try_recv(self.id)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        self.buffer
+            .recv_until(self.id, Some(Instant::now() + timeout))
+    }
+
+    fn is_corked(&self) -> bool {
+        self.buffer.is_corked()
+    }
+
+    /// The number of items this receiver has not read yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        Ok(self.buffer.inner.lock()?.cursors[&self.id].len())
+    }
+}
+
+/// Create a channel holding up to `bound` items where receivers get the highest priority item
+/// first rather than the oldest. Items sent with `send` get the priority `priority` picks for them,
+/// or any priority can be given with `PrioritySender::send_with`. Higher numbers go first, and
+/// items of the same priority arrive in the order they were sent.
+///
+/// As with `sync_channel`, every receiver sees every item and an item only leaves the channel
+/// once all of them have read it.
+pub fn priority_channel<T, F>(bound: usize, priority: F) -> (PrioritySender<T>, PriorityReceiver<T>)
+where
+    T: Clone,
+    F: Fn(&T) -> u8 + Send + Sync + 'static,
+{
+    let buffer = Arc::new(PriorityBuffer {
+        inner: Mutex::new(PriorityInner {
+            items: HashMap::with_capacity(bound),
+            cursors: HashMap::new(),
+            next_seq: 0,
+            next_cursor_id: 0,
+            bound,
+        }),
+        on_new_data: Condvar::new(),
+        on_data_consumed: Condvar::new(),
+        corked: AtomicBool::new(false),
+        sender_count: AtomicUsize::new(1),
+        priority: Box::new(priority),
+        id: next_channel_id(),
+    });
+    let rx = PriorityReceiver::new(buffer.clone());
+    (PrioritySender { buffer }, rx)
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::mpmc::test_util::check_recv_timeout;
+
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    enum Msg {
+        Data(u32),
+        Flush,
+    }
+
+    fn priority(m: &Msg) -> u8 {
+        match m {
+            Msg::Data(_) => 0,
+            Msg::Flush => 10,
+        }
+    }
+
+    #[test]
+    fn control_jumps_ahead() {
+        let (tx, rx) = priority_channel(8, priority);
+        tx.send(Msg::Data(1)).unwrap();
+        tx.send(Msg::Data(2)).unwrap();
+        tx.send(Msg::Flush).unwrap();
+        tx.send_with(Msg::Data(3), 5).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(Msg::Flush));
+        assert_eq!(rx.recv(), Ok(Msg::Data(3)));
+        assert_eq!(rx.recv(), Ok(Msg::Data(1)));
+        assert_eq!(rx.recv(), Ok(Msg::Data(2)));
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn fan_out_per_cursor() {
+        let (tx, rx1) = priority_channel(4, priority);
+        tx.send(Msg::Data(1)).unwrap();
+        let rx2 = rx1.clone();
+        assert_eq!(rx1.recv(), Ok(Msg::Data(1)));
+        tx.send(Msg::Flush).unwrap();
+        assert_eq!(tx.pending(), Ok(2));
+
+        // rx2 still has both, the flush goes first
+        assert_eq!(rx2.pending(), Ok(2));
+        assert_eq!(rx2.recv(), Ok(Msg::Flush));
+        assert_eq!(rx2.recv(), Ok(Msg::Data(1)));
+        assert_eq!(rx1.recv(), Ok(Msg::Flush));
+        assert_eq!(tx.pending(), Ok(0));
+    }
+
+    #[test]
+    fn backpressure_until_all_read() {
+        let (tx, rx1) = priority_channel(2, |_: &u32| 0);
+        let rx2 = rx1.clone();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(tx.try_send(3), Ok(Some(3)));
+        assert_eq!(rx1.recv(), Ok(1));
+        // rx2 hasn't read it so there is still no room
+        assert_eq!(tx.try_send(3), Ok(Some(3)));
+
+        let sender = thread::spawn(move || tx.send(3));
+        assert_eq!(rx2.recv(), Ok(1));
+        sender.join().unwrap().unwrap();
+        // dropping a lagging receiver doesn't hold up the others
+        drop(rx2);
+        assert_eq!(rx1.recv(), Ok(2));
+        assert_eq!(rx1.recv(), Ok(3));
+        assert_eq!(rx1.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = priority_channel(4, priority);
+        check_recv_timeout(tx, &rx, Msg::Flush);
+    }
+}
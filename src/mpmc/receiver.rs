@@ -1,6 +1,8 @@
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use super::{Buffer, ChannelError};
+use super::{Buffer, ChannelError, ChannelStats, Position};
 
 /// A generic receiver of packets/data for a `mpmc` channel. This is a consumer with a cursor in the
 /// buffer.
@@ -27,6 +29,25 @@ pub trait ChannelReceiver: Clone {
     /// of sleeping the thread.
     fn try_recv(&self) -> Result<Option<Self::Item>, ChannelError>;
 
+    /// Receive the next item, sleeping this thread for up to `timeout` waiting for data if none is
+    /// present. Returns None if the time ran out.
+    ///
+    /// The default implementation polls `try_recv`, so implementations which can wait for new data
+    /// directly should override it.
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Self::Item>, ChannelError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(v) = self.try_recv()? {
+                return Ok(Some(v));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(1)));
+        }
+    }
+
     /// Check if the channel is corked and no new data will come in. Even if it is corked,
     /// there may still be more data left to retrieve.
     fn is_corked(&self) -> bool;
@@ -41,7 +62,7 @@ pub struct Receiver<T: Clone> {
     id: usize,
 }
 
-/// Make another reader of the same underlying data starting where this reader currently is but
+/// Make another reader of the same underlying data starting at the oldest item in the buffer but
 /// allowing both readers to independently read the same data.
 impl<T: Clone> Clone for Receiver<T> {
     fn clone(&self) -> Self {
@@ -55,7 +76,7 @@ impl<T: Clone> Drop for Receiver<T> {
         // this should panic only if there there was another panic which is leading to this cleanup,
         // so avoid unwrapping here to prevent seeing the cryptic error
         // `SIGILL: illegal instruction` which results from a panic during a panic.
-        self.buffer.drop_receiver(self.id);
+        let _ = self.buffer.drop_receiver(self.id);
     }
 }
 
@@ -74,6 +95,10 @@ impl<T: Clone> ChannelReceiver for Receiver<T> {
         self.buffer.try_recv(self.id)
     }
 
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        self.buffer.recv_timeout(self.id, timeout)
+    }
+
     fn is_corked(&self) -> bool {
         self.buffer.is_corked()
     }
@@ -85,9 +110,47 @@ impl<T: Clone> ChannelReceiver for Receiver<T> {
 
 impl<T: Clone> Receiver<T> {
     pub(super) fn new(buffer: Arc<Buffer<T>>) -> Self {
-        let id = buffer.new_receiver().unwrap();
+        Self::at(buffer, Position::Oldest)
+    }
+
+    pub(super) fn at(buffer: Arc<Buffer<T>>, position: Position) -> Self {
+        let id = buffer.new_receiver(position).unwrap();
         Self { buffer, id }
     }
+
+    /// Make another independent reader of the same underlying data which starts reading at the
+    /// given position. Use `Position::Latest` for receivers which join a running stream and do
+    /// not care about what was sent before, such as monitors.
+    pub fn subscribe_from(&self, position: Position) -> Self {
+        Self::at(self.buffer.clone(), position)
+    }
+
+    /// Move this receiver to a different position in the stream. Moving back replays whatever
+    /// history the channel has retained.
+    pub fn seek(&self, position: Position) -> Result<(), ChannelError> {
+        self.buffer.seek(self.id, position)
+    }
+
+    /// Index in the stream of the next item this receiver will read.
+    pub fn position(&self) -> Result<u64, ChannelError> {
+        self.buffer.position(self.id)
+    }
+
+    /// Counters describing the channel this receiver reads from.
+    pub fn stats(&self) -> Result<ChannelStats, ChannelError> {
+        self.buffer.stats()
+    }
+
+    /// Get the current bound of the channel this receiver reads from.
+    pub fn bound(&self) -> Result<usize, ChannelError> {
+        self.buffer.bound()
+    }
+
+    /// Change the bound of the channel this receiver reads from while it is in use, as with
+    /// `Sender::set_bound`.
+    pub fn set_bound(&self, bound: usize) -> Result<(), ChannelError> {
+        self.buffer.set_bound(bound)
+    }
 }
 
 /// SharedReceivers use the same underlying cursor allowing them to take a single Receiver instance
@@ -119,6 +182,10 @@ impl<T: Clone> ChannelReceiver for SharedReceiver<T> {
         self.rx.try_recv()
     }
 
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        self.rx.recv_timeout(timeout)
+    }
+
     fn is_corked(&self) -> bool {
         self.rx.is_corked()
     }
@@ -129,10 +196,26 @@ impl<T: Clone> ChannelReceiver for SharedReceiver<T> {
 }
 
 impl<T: Clone> SharedReceiver<T> {
-    pub fn try_unwrap(self: Self) -> Result<Receiver<T>, Self> {
+    pub fn try_unwrap(self) -> Result<Receiver<T>, Self> {
         match Arc::try_unwrap(self.rx) {
             Ok(rx) => Ok(rx),
             Err(rx) => Err(Self { rx }),
         }
     }
+
+    /// Counters describing the channel this receiver reads from.
+    pub fn stats(&self) -> Result<ChannelStats, ChannelError> {
+        self.rx.stats()
+    }
+
+    /// Get the current bound of the channel this receiver reads from.
+    pub fn bound(&self) -> Result<usize, ChannelError> {
+        self.rx.bound()
+    }
+
+    /// Change the bound of the channel this receiver reads from while it is in use, as with
+    /// `Sender::set_bound`.
+    pub fn set_bound(&self, bound: usize) -> Result<(), ChannelError> {
+        self.rx.set_bound(bound)
+    }
 }
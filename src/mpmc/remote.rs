@@ -0,0 +1,1099 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::buffer::Buffer;
+use crate::mpmc::{
+    ChannelError, ChannelReceiver, ChannelSender, Codec, Position, Receiver, Sender, SharedReceiver,
+};
+
+/// Number of items a remote sender may have on the way to the server before it has to wait for
+/// the server to make room for them in the buffer.
+const SENDER_WINDOW: u32 = 16;
+/// Number of items a remote receiver asks for ahead of time unless told otherwise.
+pub(super) const DEFAULT_PREFETCH: u32 = 16;
+/// How often the server checks on things which can't wake it up, such as the buffer being corked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Largest frame payload either side will read, which also limits how large an encoded item can
+/// be. Anything larger is treated as garbage rather than allocating whatever a peer asks for.
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+/// Number of connections a server handles at once unless told otherwise.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Types of the frames making up the wire protocol. Every frame is a type byte, a little-endian
+/// `u32` payload length and then the payload.
+mod frame {
+    /// Client wants to send items. No payload.
+    pub const HELLO_SENDER: u8 = 1;
+    /// Client wants to receive items. Payload is the cursor to use, see `RemoteCursor::encode`.
+    pub const HELLO_RECEIVER: u8 = 2;
+    /// Server accepted the client. Payload is the buffer id and cursor id as `u64`s followed by
+    /// the initial credits as a `u32`.
+    pub const WELCOME: u8 = 3;
+    /// An item encoded by the codec.
+    pub const DATA: u8 = 4;
+    /// The receiving side has room for this many more items, as a `u32`.
+    pub const CREDIT: u8 = 5;
+    /// Client asks for the buffer to be corked. No payload.
+    pub const CORK: u8 = 6;
+    /// Server tells the client the buffer was corked. No payload.
+    pub const CORKED: u8 = 7;
+}
+
+/// A stream which items can be sent over.
+pub(super) trait Connection: Read + Write + Send + 'static {
+    /// Get another handle to the same stream so it can be read and written from different
+    /// threads.
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>>;
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Close the stream in both directions for every handle to it.
+    fn close(&self);
+
+    /// Tell the peer nothing more will be written while still reading whatever it sends back, so
+    /// everything written before is delivered.
+    fn finish(&self);
+}
+
+impl Connection for TcpStream {
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+
+    fn close(&self) {
+        let _ = self.shutdown(Shutdown::Both);
+    }
+
+    fn finish(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+/// Something which accepts new connections for a `ChannelServer`.
+pub(super) trait Listener: Send + Sync + 'static {
+    fn accept_connection(&self) -> io::Result<Box<dyn Connection>>;
+
+    /// Stop accepting connections, unblocking a thread waiting in `accept_connection`.
+    fn shutdown(&self);
+}
+
+impl Listener for TcpListener {
+    fn accept_connection(&self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = self.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(stream))
+    }
+
+    fn shutdown(&self) {
+        if let Ok(addr) = self.local_addr() {
+            let _ = TcpStream::connect(addr);
+        }
+    }
+}
+
+fn write_frame<W: Write + ?Sized>(w: &mut W, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    w.write_all(&frame)
+}
+
+/// Build a complete data frame for an item.
+fn data_frame<T, C: Codec<T>>(codec: &C, v: &T) -> Result<Vec<u8>, ChannelError> {
+    let mut frame = vec![frame::DATA, 0, 0, 0, 0];
+    codec.encode(v, &mut frame)?;
+    let len = frame.len() - 5;
+    if len > MAX_FRAME_BYTES {
+        return Err(ChannelError::Io(ErrorKind::InvalidInput));
+    }
+    frame[1..5].copy_from_slice(&(len as u32).to_le_bytes());
+    Ok(frame)
+}
+
+fn read_frame<R: Read + ?Sized>(r: &mut R) -> io::Result<(u8, Vec<u8>)> {
+    let mut kind = [0u8];
+    r.read_exact(&mut kind)?;
+    Ok((kind[0], read_payload(r)?))
+}
+
+/// Read the rest of a frame once its type has been read. Payloads larger than `MAX_FRAME_BYTES`
+/// are rejected with `InvalidData`.
+fn read_payload<R: Read + ?Sized>(r: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    r.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(ErrorKind::InvalidData, "frame is too large"));
+    }
+    let mut payload = vec![0u8; len];
+    r.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> Option<u32> {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes.get(i..i + 4)?);
+    Some(u32::from_le_bytes(buf))
+}
+
+fn u64_at(bytes: &[u8], i: usize) -> Option<u64> {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes.get(i..i + 8)?);
+    Some(u64::from_le_bytes(buf))
+}
+
+fn credit_frame(n: u32) -> [u8; 9] {
+    let mut frame = [frame::CREDIT, 4, 0, 0, 0, 0, 0, 0, 0];
+    frame[5..].copy_from_slice(&n.to_le_bytes());
+    frame
+}
+
+/// Which cursor a remote receiver reads with.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RemoteCursor {
+    /// A cursor of its own which starts at the given position, so it sees every item.
+    Own(Position),
+    /// A cursor shared with every other remote receiver using the same name, so items are split
+    /// between them. The cursor is created at the oldest item by the first receiver to use it
+    /// and removed once none of them are connected.
+    Shared(String),
+}
+
+impl RemoteCursor {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            RemoteCursor::Own(position) => {
+                buf.push(0);
+                let (kind, offset) = match *position {
+                    Position::Oldest => (0, 0),
+                    Position::Latest => (1, 0),
+                    Position::Offset(i) => (2, i),
+                };
+                buf.push(kind);
+                buf.extend_from_slice(&offset.to_le_bytes());
+            }
+            RemoteCursor::Shared(name) => {
+                buf.push(1);
+                buf.extend_from_slice(name.as_bytes());
+            }
+        }
+        buf
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes.first()? {
+            0 => Some(RemoteCursor::Own(match bytes.get(1)? {
+                0 => Position::Oldest,
+                1 => Position::Latest,
+                2 => Position::Offset(u64_at(bytes, 2)?),
+                _ => return None,
+            })),
+            1 => Some(RemoteCursor::Shared(
+                String::from_utf8(bytes[1..].to_vec()).ok()?,
+            )),
+            _ => None,
+        }
+    }
+}
+
+struct ServerState<T: Clone, C> {
+    buffer: Arc<Buffer<T>>,
+    codec: C,
+    /// Shared cursors by name along with how many connections are using them.
+    shared: Mutex<HashMap<String, (SharedReceiver<T>, usize)>>,
+    stopped: AtomicBool,
+    /// Number of connections being handled.
+    connections: AtomicUsize,
+    max_connections: AtomicUsize,
+}
+
+/// Makes a channel available to other processes. Remote senders and receivers connect to the
+/// server and behave as though they were in-process handles to the channel: remote senders count
+/// towards the senders of the channel, so it is corked once they have all disconnected and no
+/// in-process senders are left, and remote receivers have their own or shared cursors.
+///
+/// Backpressure crosses the connection: a remote sender can only have a small number of items in
+/// flight, and more are only allowed once the server was able to put them in the buffer.
+///
+/// Every connection is handled on its own thread, so the server only handles up to
+/// `DEFAULT_MAX_CONNECTIONS` at once and closes any more straight away. Change this with
+/// `set_max_connections`.
+pub struct ChannelServer<T: Clone, C> {
+    state: Arc<ServerState<T, C>>,
+    listener: Arc<dyn Listener>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl<T, C> ChannelServer<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    /// Serve the channel `tx` sends to over TCP.
+    pub fn bind<A: ToSocketAddrs>(addr: A, tx: &Sender<T>, codec: C) -> Result<Self, ChannelError> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = Some(listener.local_addr()?);
+        Ok(Self::serve(Arc::new(listener), local_addr, tx, codec))
+    }
+
+    pub(super) fn serve(
+        listener: Arc<dyn Listener>,
+        local_addr: Option<SocketAddr>,
+        tx: &Sender<T>,
+        codec: C,
+    ) -> Self {
+        let state = Arc::new(ServerState {
+            buffer: tx.buffer().clone(),
+            codec,
+            shared: Mutex::new(HashMap::new()),
+            stopped: AtomicBool::new(false),
+            connections: AtomicUsize::new(0),
+            max_connections: AtomicUsize::new(DEFAULT_MAX_CONNECTIONS),
+        });
+        {
+            let state = state.clone();
+            let listener = listener.clone();
+            thread::spawn(move || loop {
+                let conn = listener.accept_connection();
+                if state.stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                let conn = match conn {
+                    Ok(conn) => conn,
+                    Err(_) => continue,
+                };
+                let max = state.max_connections.load(Ordering::Acquire);
+                if state.connections.fetch_add(1, Ordering::AcqRel) >= max {
+                    state.connections.fetch_sub(1, Ordering::AcqRel);
+                    conn.close();
+                    continue;
+                }
+                let state = state.clone();
+                thread::spawn(move || {
+                    state.handle(conn);
+                    state.connections.fetch_sub(1, Ordering::AcqRel);
+                });
+            });
+        }
+        Self {
+            state,
+            listener,
+            local_addr,
+        }
+    }
+
+    /// The address the server is listening on if it is using TCP.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Change how many connections the server handles at once. Connections beyond this are closed
+    /// as soon as they are accepted, while those already being handled are left alone.
+    pub fn set_max_connections(&self, max: usize) {
+        self.state.max_connections.store(max, Ordering::Release);
+    }
+}
+
+/// Stop accepting new connections. Connections which were already accepted are left alone.
+impl<T: Clone, C> Drop for ChannelServer<T, C> {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::Release);
+        self.listener.shutdown();
+    }
+}
+
+impl<T, C> ServerState<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    fn handle(&self, mut conn: Box<dyn Connection>) {
+        match read_frame(&mut conn) {
+            Ok((frame::HELLO_SENDER, _)) => self.serve_sender(&mut conn),
+            Ok((frame::HELLO_RECEIVER, payload)) => {
+                if let Some(cursor) = RemoteCursor::decode(&payload) {
+                    self.serve_receiver(conn, cursor);
+                    return;
+                }
+            }
+            _ => {}
+        }
+        conn.close();
+    }
+
+    fn welcome(conn: &mut Box<dyn Connection>, ids: (usize, usize), credits: u32) -> bool {
+        let mut payload = Vec::with_capacity(20);
+        payload.extend_from_slice(&(ids.0 as u64).to_le_bytes());
+        payload.extend_from_slice(&(ids.1 as u64).to_le_bytes());
+        payload.extend_from_slice(&credits.to_le_bytes());
+        write_frame(conn, frame::WELCOME, &payload).is_ok()
+    }
+
+    /// Put items from a remote sender in the buffer, returning a credit for each one so the
+    /// sender knows there is room for another.
+    fn serve_sender(&self, conn: &mut Box<dyn Connection>) {
+        let tx = Sender::new(self.buffer.clone());
+        if !Self::welcome(conn, (tx.id(), 0), SENDER_WINDOW) {
+            return;
+        }
+        let mut told_corked = false;
+        loop {
+            // only wait a little for the next frame so we notice the buffer being corked
+            let mut kind = [0u8];
+            if conn.set_timeout(Some(POLL_INTERVAL)).is_err() {
+                break;
+            }
+            match conn.read(&mut kind) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    if tx.is_corked() && !told_corked {
+                        told_corked = write_frame(conn, frame::CORKED, &[]).is_ok();
+                    }
+                    continue;
+                }
+                Err(_) => break,
+            }
+            let payload = match conn.set_timeout(None).and_then(|_| read_payload(conn)) {
+                Ok(payload) => payload,
+                Err(_) => break,
+            };
+            match kind[0] {
+                frame::DATA => match self.codec.decode(&payload).and_then(|v| tx.send(v)) {
+                    Ok(()) => {
+                        if conn.write_all(&credit_frame(1)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(ChannelError::IsCorked) => {
+                        if !told_corked {
+                            told_corked = write_frame(conn, frame::CORKED, &[]).is_ok();
+                        }
+                    }
+                    // the client is sending us garbage
+                    Err(_) => break,
+                },
+                frame::CORK => tx.cork(),
+                _ => break,
+            }
+        }
+        // dropping tx here will cork the buffer if this was the last sender
+    }
+
+    /// Send items to a remote receiver as long as it has given us credits for them.
+    fn serve_receiver(&self, mut conn: Box<dyn Connection>, cursor: RemoteCursor) {
+        let rx = match self.receiver(&cursor) {
+            Ok(rx) => rx,
+            Err(_) => return conn.close(),
+        };
+        let credits = Arc::new((Mutex::new((0u32, false)), Condvar::new()));
+        let reader = match conn.try_clone_connection() {
+            Ok(reader) if Self::welcome(&mut conn, rx.id(), 0) => reader,
+            _ => {
+                self.release(&cursor);
+                return conn.close();
+            }
+        };
+        let reader_thread = {
+            let credits = credits.clone();
+            let mut reader = reader;
+            thread::spawn(move || {
+                while let Ok((frame::CREDIT, payload)) = read_frame(&mut reader) {
+                    let n = u32_at(&payload, 0).unwrap_or(0);
+                    match credits.0.lock() {
+                        Ok(mut state) => state.0 += n,
+                        Err(_) => break,
+                    }
+                    credits.1.notify_all();
+                }
+                if let Ok(mut state) = credits.0.lock() {
+                    state.1 = true;
+                }
+                credits.1.notify_all();
+            })
+        };
+        if let Err(ChannelError::IsCorked) = self.stream(&mut conn, &rx, &credits) {
+            let _ = write_frame(&mut conn, frame::CORKED, &[]);
+        }
+        conn.close();
+        let _ = reader_thread.join();
+        drop(rx);
+        self.release(&cursor);
+    }
+
+    /// Find or create the receiver for a remote cursor.
+    fn receiver(&self, cursor: &RemoteCursor) -> Result<SharedReceiver<T>, ChannelError> {
+        Ok(match cursor {
+            RemoteCursor::Own(position) => {
+                SharedReceiver::from(Receiver::at(self.buffer.clone(), *position))
+            }
+            RemoteCursor::Shared(name) => {
+                let mut shared = self.shared.lock()?;
+                let entry = shared.entry(name.clone()).or_insert_with(|| {
+                    (
+                        SharedReceiver::from(Receiver::at(self.buffer.clone(), Position::Oldest)),
+                        0,
+                    )
+                });
+                entry.1 += 1;
+                entry.0.clone()
+            }
+        })
+    }
+
+    /// Write items to the connection while there are credits for them, until the client goes away
+    /// or the buffer is corked.
+    fn stream(
+        &self,
+        conn: &mut Box<dyn Connection>,
+        rx: &SharedReceiver<T>,
+        credits: &(Mutex<(u32, bool)>, Condvar),
+    ) -> Result<(), ChannelError> {
+        loop {
+            {
+                let mut state = credits.0.lock()?;
+                while state.0 == 0 && !state.1 {
+                    state = credits.1.wait(state)?;
+                }
+                if state.1 {
+                    return Ok(());
+                }
+            }
+            if let Some(v) = rx.recv_timeout(POLL_INTERVAL)? {
+                let frame = data_frame(&self.codec, &v)?;
+                conn.write_all(&frame)?;
+                credits.0.lock()?.0 -= 1;
+            }
+        }
+    }
+
+    /// Forget a shared cursor once nobody is using it.
+    fn release(&self, cursor: &RemoteCursor) {
+        if let RemoteCursor::Shared(name) = cursor {
+            // a poisoned map only means the cursor is never forgotten
+            if let Ok(mut shared) = self.shared.lock() {
+                if let Some(entry) = shared.get_mut(name) {
+                    entry.1 -= 1;
+                    if entry.1 == 0 {
+                        shared.remove(name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Read the server's response to a hello, returning the ids and initial credits.
+fn read_welcome(conn: &mut Box<dyn Connection>) -> Result<(usize, usize, u32), ChannelError> {
+    match read_frame(conn) {
+        Ok((frame::WELCOME, payload)) => match (
+            u64_at(&payload, 0),
+            u64_at(&payload, 8),
+            u32_at(&payload, 16),
+        ) {
+            (Some(buffer), Some(cursor), Some(credits)) => {
+                Ok((buffer as usize, cursor as usize, credits))
+            }
+            _ => Err(ChannelError::Io(ErrorKind::InvalidData)),
+        },
+        Ok(_) => Err(ChannelError::Io(ErrorKind::InvalidData)),
+        Err(_) => Err(ChannelError::Disconnected),
+    }
+}
+
+struct SenderState {
+    credits: u32,
+    /// Items sent which the server has not put in the buffer yet.
+    in_flight: usize,
+    corked: bool,
+    disconnected: bool,
+}
+
+struct SenderLink<T, C> {
+    writer: Mutex<Box<dyn Connection>>,
+    state: Arc<(Mutex<SenderState>, Condvar)>,
+    codec: C,
+    buffer_id: usize,
+    _phantom: PhantomData<fn(T)>,
+}
+
+/// Closing the connection outright while credits are still arriving would reset it and lose the
+/// items in flight, so only close our side and let the reader drain the rest.
+impl<T, C> Drop for SenderLink<T, C> {
+    fn drop(&mut self) {
+        if let Ok(writer) = self.writer.lock() {
+            writer.finish();
+        }
+    }
+}
+
+/// `ChannelSender` which sends items to a channel hosted by a `ChannelServer` in another process.
+/// Clones share the same connection, which is closed once they have all been dropped.
+pub struct RemoteSender<T, C> {
+    link: Arc<SenderLink<T, C>>,
+}
+
+impl<T, C> Clone for RemoteSender<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            link: self.link.clone(),
+        }
+    }
+}
+
+impl<T, C> RemoteSender<T, C>
+where
+    T: Clone,
+    C: Codec<T>,
+{
+    /// Connect to a `ChannelServer` over TCP.
+    pub fn connect<A: ToSocketAddrs>(addr: A, codec: C) -> Result<Self, ChannelError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Self::handshake(Box::new(stream), codec)
+    }
+
+    pub(super) fn handshake(mut conn: Box<dyn Connection>, codec: C) -> Result<Self, ChannelError> {
+        write_frame(&mut conn, frame::HELLO_SENDER, &[])?;
+        let (buffer_id, _, credits) = read_welcome(&mut conn)?;
+        let state = Arc::new((
+            Mutex::new(SenderState {
+                credits,
+                in_flight: 0,
+                corked: false,
+                disconnected: false,
+            }),
+            Condvar::new(),
+        ));
+        let mut reader = conn.try_clone_connection()?;
+        {
+            let state = state.clone();
+            thread::spawn(move || loop {
+                let frame = read_frame(&mut reader);
+                let mut s = match state.0.lock() {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                match frame {
+                    Ok((frame::CREDIT, payload)) => {
+                        let n = u32_at(&payload, 0).unwrap_or(0);
+                        s.credits += n;
+                        s.in_flight = s.in_flight.saturating_sub(n as usize);
+                    }
+                    Ok((frame::CORKED, _)) => s.corked = true,
+                    _ => {
+                        s.disconnected = true;
+                        state.1.notify_all();
+                        break;
+                    }
+                }
+                state.1.notify_all();
+            });
+        }
+        Ok(Self {
+            link: Arc::new(SenderLink {
+                writer: Mutex::new(conn),
+                state,
+                codec,
+                buffer_id,
+                _phantom: PhantomData,
+            }),
+        })
+    }
+
+    /// Use up a credit and write the item.
+    fn write(&self, v: &T) -> Result<(), ChannelError> {
+        let frame = data_frame(&self.link.codec, v)?;
+        self.link
+            .writer
+            .lock()?
+            .write_all(&frame)
+            .map_err(|_| ChannelError::Disconnected)
+    }
+
+    /// Check if sending is possible at all, which is an error if it isn't.
+    fn check(state: &SenderState) -> Result<(), ChannelError> {
+        if state.corked {
+            Err(ChannelError::IsCorked)
+        } else if state.disconnected {
+            Err(ChannelError::Disconnected)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T, C> ChannelSender for RemoteSender<T, C>
+where
+    T: Clone,
+    C: Codec<T>,
+{
+    type Item = T;
+
+    fn id(&self) -> usize {
+        self.link.buffer_id
+    }
+
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        {
+            let (lock, changed) = &*self.link.state;
+            let mut state = lock.lock()?;
+            while state.credits == 0 {
+                Self::check(&state)?;
+                state = changed.wait(state)?;
+            }
+            Self::check(&state)?;
+            state.credits -= 1;
+            state.in_flight += 1;
+        }
+        self.write(&v)
+    }
+
+    fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+        {
+            let mut state = self.link.state.0.lock()?;
+            Self::check(&state)?;
+            if state.credits == 0 {
+                return Ok(Some(v));
+            }
+            state.credits -= 1;
+            state.in_flight += 1;
+        }
+        self.write(&v).map(|_| None)
+    }
+
+    fn cork(&self) {
+        if let Ok(mut writer) = self.link.writer.lock() {
+            let _ = write_frame(&mut *writer, frame::CORK, &[]);
+        }
+        if let Ok(mut state) = self.link.state.0.lock() {
+            state.corked = true;
+        }
+        self.link.state.1.notify_all();
+    }
+
+    fn is_corked(&self) -> bool {
+        self.link.state.0.lock().map(|s| s.corked).unwrap_or(true)
+    }
+
+    /// The number of items sent which the server has not been able to put in the buffer yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        Ok(self.link.state.0.lock()?.in_flight)
+    }
+}
+
+struct ReceiverState<T> {
+    queue: VecDeque<T>,
+    /// Items taken from the queue which we have not given the server credits for yet.
+    consumed: u32,
+    corked: bool,
+    error: Option<ChannelError>,
+}
+
+struct ReceiverLink<T> {
+    writer: Mutex<Box<dyn Connection>>,
+    state: Arc<(Mutex<ReceiverState<T>>, Condvar)>,
+    ids: (usize, usize),
+    prefetch: u32,
+}
+
+impl<T> Drop for ReceiverLink<T> {
+    fn drop(&mut self) {
+        if let Ok(writer) = self.writer.lock() {
+            writer.close();
+        }
+    }
+}
+
+/// `ChannelReceiver` which receives items from a channel hosted by a `ChannelServer` in another
+/// process. Up to `prefetch` items are sent ahead of time so they are ready when `recv` is called;
+/// any of those which have not been received when the receiver is dropped are lost.
+///
+/// Clones share the same connection and split the items between them, as with `SharedReceiver`.
+pub struct RemoteReceiver<T, C> {
+    link: Arc<ReceiverLink<T>>,
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<T, C> Clone for RemoteReceiver<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            link: self.link.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, C> RemoteReceiver<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    /// Connect to a `ChannelServer` over TCP with a cursor of our own starting at the oldest item
+    /// in the channel.
+    pub fn connect<A: ToSocketAddrs>(addr: A, codec: C) -> Result<Self, ChannelError> {
+        Self::connect_with(
+            addr,
+            codec,
+            RemoteCursor::Own(Position::Oldest),
+            DEFAULT_PREFETCH,
+        )
+    }
+
+    /// Connect to a `ChannelServer` over TCP with the given cursor and number of items to fetch
+    /// ahead of time. Competing consumers on a shared cursor should use a small `prefetch` so one
+    /// of them doesn't take more than its share.
+    pub fn connect_with<A: ToSocketAddrs>(
+        addr: A,
+        codec: C,
+        cursor: RemoteCursor,
+        prefetch: u32,
+    ) -> Result<Self, ChannelError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Self::handshake(Box::new(stream), codec, cursor, prefetch)
+    }
+
+    pub(super) fn handshake(
+        mut conn: Box<dyn Connection>,
+        codec: C,
+        cursor: RemoteCursor,
+        prefetch: u32,
+    ) -> Result<Self, ChannelError> {
+        let prefetch = prefetch.max(1);
+        write_frame(&mut conn, frame::HELLO_RECEIVER, &cursor.encode())?;
+        let (buffer_id, cursor_id, _) = read_welcome(&mut conn)?;
+        conn.write_all(&credit_frame(prefetch))?;
+
+        let state = Arc::new((
+            Mutex::new(ReceiverState {
+                queue: VecDeque::with_capacity(prefetch as usize),
+                consumed: 0,
+                corked: false,
+                error: None,
+            }),
+            Condvar::new(),
+        ));
+        let mut reader = conn.try_clone_connection()?;
+        {
+            let state = state.clone();
+            thread::spawn(move || loop {
+                let frame = read_frame(&mut reader);
+                let mut s = match state.0.lock() {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                let done = match frame {
+                    Ok((frame::DATA, payload)) => match codec.decode(&payload) {
+                        Ok(v) => {
+                            s.queue.push_back(v);
+                            false
+                        }
+                        Err(e) => {
+                            s.error = Some(e);
+                            true
+                        }
+                    },
+                    Ok((frame::CORKED, _)) => {
+                        s.corked = true;
+                        true
+                    }
+                    _ => {
+                        s.error = Some(ChannelError::Disconnected);
+                        true
+                    }
+                };
+                state.1.notify_all();
+                if done {
+                    break;
+                }
+            });
+        }
+        Ok(Self {
+            link: Arc::new(ReceiverLink {
+                writer: Mutex::new(conn),
+                state,
+                ids: (buffer_id, cursor_id),
+                prefetch,
+            }),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Take the next item off the queue and give the server credit for it once enough have been
+    /// taken.
+    fn pop(&self, state: &mut ReceiverState<T>) -> Result<Option<T>, ChannelError> {
+        let v = match state.queue.pop_front() {
+            Some(v) => v,
+            None if state.corked => return Err(ChannelError::IsCorked),
+            None => match state.error {
+                Some(e) => return Err(e),
+                None => return Ok(None),
+            },
+        };
+        state.consumed += 1;
+        if state.consumed >= (self.link.prefetch / 2).max(1) {
+            // if this fails the reader will find out the connection is gone
+            let _ = self
+                .link
+                .writer
+                .lock()?
+                .write_all(&credit_frame(state.consumed));
+            state.consumed = 0;
+        }
+        Ok(Some(v))
+    }
+}
+
+impl<T, C> ChannelReceiver for RemoteReceiver<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    type Item = T;
+
+    fn id(&self) -> (usize, usize) {
+        self.link.ids
+    }
+
+    fn recv(&self) -> Result<T, ChannelError> {
+        let (lock, changed) = &*self.link.state;
+        let mut state = lock.lock()?;
+        loop {
+            if let Some(v) = self.pop(&mut state)? {
+                return Ok(v);
+            }
+            state = changed.wait(state)?;
+        }
+    }
+
+    fn try_recv(&self) -> Result<Option<T>, ChannelError> {
+        let mut state = self.link.state.0.lock()?;
+        self.pop(&mut state)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        let deadline = Instant::now() + timeout;
+        let (lock, changed) = &*self.link.state;
+        let mut state = lock.lock()?;
+        loop {
+            if let Some(v) = self.pop(&mut state)? {
+                return Ok(Some(v));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            state = changed.wait_timeout(state, deadline - now)?.0;
+        }
+    }
+
+    fn is_corked(&self) -> bool {
+        self.link.state.0.lock().map(|s| s.corked).unwrap_or(true)
+    }
+
+    /// The number of items which have arrived but have not been received yet.
+    fn pending(&self) -> Result<usize, ChannelError> {
+        Ok(self.link.state.0.lock()?.queue.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::{sync_channel, RawLeCodec};
+
+    fn server(bound: usize) -> (ChannelServer<u32, RawLeCodec>, Sender<u32>, Receiver<u32>) {
+        let (tx, rx) = sync_channel(bound);
+        let server = ChannelServer::bind("127.0.0.1:0", &tx, RawLeCodec).unwrap();
+        (server, tx, rx)
+    }
+
+    #[test]
+    fn remote_sender_to_local_receiver() {
+        let (server, tx, rx) = server(4);
+        let remote =
+            RemoteSender::<u32, _>::connect(server.local_addr().unwrap(), RawLeCodec).unwrap();
+        // only the remote sender should keep the channel open
+        drop(tx);
+        assert_eq!(remote.id(), rx.id().0);
+        let tx_thread = thread::spawn(move || {
+            for i in 0..200 {
+                remote.send(i).unwrap();
+            }
+            // disconnecting corks the channel since it was the last sender
+        });
+        for i in 0..200 {
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+        tx_thread.join().unwrap();
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn local_sender_to_remote_receivers() {
+        let (server, tx, rx) = server(4);
+        drop(rx);
+        let addr = server.local_addr().unwrap();
+        let rx1 = RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        let rx2 = RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        assert_eq!(rx1.id().0, tx.id());
+        assert_ne!(rx1.id().1, rx2.id().1);
+
+        let tx_thread = thread::spawn(move || {
+            for i in 0..200 {
+                tx.send(i).unwrap();
+            }
+        });
+        // each has their own cursor so they both see everything
+        for i in 0..200 {
+            assert_eq!(rx1.recv().unwrap(), i);
+            assert_eq!(rx2.recv().unwrap(), i);
+        }
+        tx_thread.join().unwrap();
+        assert_eq!(rx1.recv(), Err(ChannelError::IsCorked));
+        assert_eq!(rx2.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn shared_remote_receivers() {
+        let (server, tx, rx) = server(4);
+        drop(rx);
+        let addr = server.local_addr().unwrap();
+        let cursor = RemoteCursor::Shared("workers".into());
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                RemoteReceiver::<u32, _>::connect_with(addr, RawLeCodec, cursor.clone(), 1).unwrap()
+            })
+            .collect();
+        assert_eq!(workers[0].id(), workers[1].id());
+
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|rx| {
+                thread::spawn(move || {
+                    let mut sum = 0;
+                    while let Ok(v) = rx.recv() {
+                        sum += v;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        for i in 0..300 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let sum: u32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, (0..300).sum());
+    }
+
+    #[test]
+    fn backpressure_crosses_network() {
+        let (server, _tx, rx) = server(2);
+        let remote =
+            RemoteSender::<u32, _>::connect(server.local_addr().unwrap(), RawLeCodec).unwrap();
+        let mut sent = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // the buffer holds 2 and a full window is in flight, one of which the server is stuck on
+        while sent < 2 + SENDER_WINDOW && Instant::now() < deadline {
+            if remote.try_send(sent).unwrap().is_none() {
+                sent += 1;
+            } else {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(remote.try_send(sent).unwrap(), Some(sent));
+        assert_eq!(remote.pending().unwrap(), SENDER_WINDOW as usize);
+
+        // reading makes room again
+        assert_eq!(rx.recv().unwrap(), 0);
+        assert_eq!(remote.send(sent), Ok(()));
+    }
+
+    #[test]
+    fn cork_propagates() {
+        let (server, tx, rx) = server(2);
+        let addr = server.local_addr().unwrap();
+        let remote_tx = RemoteSender::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        let remote_rx = RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        remote_tx.send(1).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(remote_rx.recv().unwrap(), 1);
+
+        remote_tx.cork();
+        assert_eq!(remote_rx.recv(), Err(ChannelError::IsCorked));
+        assert!(tx.is_corked());
+
+        // and the other way around
+        let (server, tx, _rx) = super::test::server(2);
+        let remote_tx =
+            RemoteSender::<u32, _>::connect(server.local_addr().unwrap(), RawLeCodec).unwrap();
+        tx.cork();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !remote_tx.is_corked() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(remote_tx.send(2), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn disconnect_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let fake = thread::spawn(move || {
+            // say hello and then die
+            let (mut conn, _) = listener.accept().unwrap();
+            read_frame(&mut conn).unwrap();
+            write_frame(&mut conn, frame::WELCOME, &[0; 20]).unwrap();
+            read_frame(&mut conn).unwrap();
+        });
+        let rx = RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        fake.join().unwrap();
+        assert_eq!(rx.recv(), Err(ChannelError::Disconnected));
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut frame: &[u8] = &[frame::DATA, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(
+            read_frame(&mut frame).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn connection_limit() {
+        let (server, tx, _rx) = server(4);
+        server.set_max_connections(1);
+        let addr = server.local_addr().unwrap();
+        let first = RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).unwrap();
+        assert!(RemoteReceiver::<u32, _>::connect(addr, RawLeCodec).is_err());
+
+        // room is made once the first connection is done with
+        drop(first);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let second = loop {
+            match RemoteReceiver::<u32, _>::connect(addr, RawLeCodec) {
+                Ok(rx) => break rx,
+                Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                Err(e) => panic!("{:?}", e),
+            }
+        };
+        tx.send(1).unwrap();
+        assert_eq!(second.recv().unwrap(), 1);
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::mpmc::buffer::Buffer;
-use crate::mpmc::ChannelError;
+use crate::mpmc::{ChannelError, ChannelStats, Position, Receiver};
 
 /// A generic sender of packets/data for a `mpmc` channel. This is a producer.
 pub trait ChannelSender: Clone {
@@ -63,11 +64,11 @@ impl<T: Clone> ChannelSender for Sender<T> {
     }
 
     fn send(&self, v: T) -> Result<(), ChannelError> {
-        self.buffer.send(v)
+        self.buffer.send(v, None)
     }
 
     fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
-        self.buffer.try_send(v)
+        self.buffer.try_send(v, None)
     }
 
     fn cork(&self) {
@@ -88,4 +89,61 @@ impl<T: Clone> Sender<T> {
         buffer.add_sender();
         Self { buffer }
     }
+
+    pub(super) fn buffer(&self) -> &Arc<Buffer<T>> {
+        &self.buffer
+    }
+
+    /// Send an item which expires at `deadline`, overriding the time-to-live of the channel. If it
+    /// has not been read by every receiver by then, it is skipped by those which have not.
+    pub fn send_with_deadline(&self, v: T, deadline: Instant) -> Result<(), ChannelError> {
+        self.buffer.send(v, Some(deadline))
+    }
+
+    /// Attempt to send an item which expires at `deadline` without waiting for room, handing it
+    /// back if the channel is full.
+    pub fn try_send_with_deadline(
+        &self,
+        v: T,
+        deadline: Instant,
+    ) -> Result<Option<T>, ChannelError> {
+        self.buffer.try_send(v, Some(deadline))
+    }
+
+    /// Create a new receiver for the channel this sender writes to, starting at the given position.
+    pub fn subscribe_from(&self, position: Position) -> Receiver<T> {
+        Receiver::at(self.buffer.clone(), position)
+    }
+
+    /// Counters describing the channel this sender writes to.
+    pub fn stats(&self) -> Result<ChannelStats, ChannelError> {
+        self.buffer.stats()
+    }
+
+    /// Get the current bound of the channel this sender writes to.
+    pub fn bound(&self) -> Result<usize, ChannelError> {
+        self.buffer.bound()
+    }
+
+    /// Change the bound of the channel this sender writes to while it is in use. Senders blocked
+    /// on a full buffer are woken if it grows. If it shrinks, nothing already in the buffer is
+    /// removed and the excess drains as receivers catch up:
+    /// - with `FullPolicy::Block`, new items are held back until the buffer is under the new bound;
+    /// - with `FullPolicy::DropNewest`, new items are dropped until then;
+    /// - with `FullPolicy::DropOldest` and `FullPolicy::Overwrite`, each new item only displaces
+    ///   as much as it weighs, so the buffer stops growing but does not discard the excess at once.
+    pub fn set_bound(&self, bound: usize) -> Result<(), ChannelError> {
+        self.buffer.set_bound(bound)
+    }
+
+    /// Adjust the bound of the channel based on how long senders and receivers have been blocked
+    /// since the last call. The bound is doubled if senders spent longer waiting for room than
+    /// receivers spent waiting for data, and halved if senders never had to wait, but always kept
+    /// within `min..=max`. Call this periodically, such as from a timer thread, to let the bound
+    /// settle on what the graph needs. Returns the new bound.
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn tune_bound(&self, min: usize, max: usize) -> Result<usize, ChannelError> {
+        self.buffer.tune_bound(min, max)
+    }
 }
@@ -0,0 +1,883 @@
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::{c_int, c_long, c_void};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::buffer::next_channel_id;
+use crate::mpmc::{ChannelError, ChannelReceiver, ChannelSender};
+
+/// Identifies a file as a shared-memory channel so we don't go reading garbage.
+const MAGIC: u64 = 0x6367_7261_7068_0001;
+/// Maximum number of independent cursors a shared-memory channel can have at once.
+pub const SHM_MAX_CURSORS: usize = 32;
+/// Start of the item slots, which keeps them aligned for anything reasonable.
+const DATA_ALIGN: usize = 64;
+/// Waiting on the futex is always given a timeout so a wake-up lost to a process which died
+/// mid-update can't hang us forever.
+const WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long to wait for a lock held by a process which is still alive before giving up. Critical
+/// sections are tiny, so only a stuck or stopped process holds the lock this long.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Types which are nothing but their bytes, so they can be copied in and out of memory shared with
+/// another process.
+///
+/// # Safety
+/// Implementors must be `Copy`, contain no pointers or references (including through heap
+/// allocations), and be valid for whatever bit patterns another process may write.
+pub unsafe trait Pod: Copy + Send + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+#[repr(C)]
+struct CursorSlot {
+    /// Number of receivers reading with this cursor, so it is free when this is 0.
+    in_use: AtomicU32,
+    _padding: u32,
+    position: AtomicU64,
+}
+
+/// Layout of the start of the shared memory. Everything except `lock`, `futex`, `waiters` and
+/// `senders` must only be touched while holding `lock`.
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    item_size: AtomicU64,
+    capacity: AtomicU64,
+    /// Process id of the lock holder, or 0 if it is not held.
+    lock: AtomicU32,
+    /// Bumped every time something another process may be waiting on changes.
+    futex: AtomicU32,
+    /// Number of threads waiting on `futex`, so we can skip waking them when there are none.
+    waiters: AtomicU32,
+    corked: AtomicU32,
+    senders: AtomicU32,
+    _padding: u32,
+    /// Index of the oldest item still in the ring.
+    offset: AtomicU64,
+    /// Index the next item will be written at.
+    end: AtomicU64,
+    cursors: [CursorSlot; SHM_MAX_CURSORS],
+}
+
+/// A shared mapping of the channel file.
+struct Region<T> {
+    ptr: *mut u8,
+    len: usize,
+    capacity: u64,
+    slot_size: usize,
+    id: usize,
+    _file: File,
+    _phantom: PhantomData<T>,
+}
+
+// the region is only ever accessed through atomics or while holding the lock in the header
+unsafe impl<T: Pod> Send for Region<T> {}
+unsafe impl<T: Pod> Sync for Region<T> {}
+
+impl<T> Drop for Region<T> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::munmap(self.ptr as *mut c_void, self.len);
+        }
+    }
+}
+
+impl<T: Pod> Region<T> {
+    fn slot_size() -> usize {
+        let align = mem::align_of::<T>();
+        mem::size_of::<T>().div_ceil(align) * align
+    }
+
+    fn data_start() -> usize {
+        mem::size_of::<Header>().div_ceil(DATA_ALIGN) * DATA_ALIGN
+    }
+
+    fn map(file: File, capacity: u64) -> Result<Self, ChannelError> {
+        assert!(
+            mem::size_of::<T>() > 0,
+            "Zero sized items are not supported"
+        );
+        assert!(
+            mem::align_of::<T>() <= DATA_ALIGN,
+            "Item alignment is too large"
+        );
+        let slot_size = Self::slot_size();
+        let len = Self::data_start() + slot_size * capacity as usize;
+        if file.metadata()?.len() < len as u64 {
+            return Err(ChannelError::Io(ErrorKind::InvalidData));
+        }
+        let ptr = unsafe {
+            sys::mmap(
+                ptr::null_mut(),
+                len,
+                sys::PROT_READ | sys::PROT_WRITE,
+                sys::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+            capacity,
+            slot_size,
+            id: next_channel_id(),
+            _file: file,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.ptr as *const Header) }
+    }
+
+    fn slot(&self, index: u64) -> *mut T {
+        let i = (index % self.capacity) as usize;
+        unsafe { self.ptr.add(Self::data_start() + i * self.slot_size) as *mut T }
+    }
+
+    /// Take the lock in the header. If the process holding it has died, the lock is taken over
+    /// from it, which is safe as long as it did not die part way through changing the header. If
+    /// it is still alive but does not let go within `LOCK_TIMEOUT`, this fails with `TimedOut`.
+    fn lock(&self) -> Result<Guard<'_, T>, ChannelError> {
+        let lock = &self.header().lock;
+        let pid = std::process::id();
+        let mut spins = 0u32;
+        let mut start = None;
+        let mut owner = 0;
+        while let Err(held) =
+            lock.compare_exchange_weak(owner, pid, Ordering::Acquire, Ordering::Relaxed)
+        {
+            // critical sections are tiny so just spin a little before giving up our time slice
+            spins += 1;
+            owner = 0;
+            if spins < 64 {
+                std::hint::spin_loop();
+                continue;
+            }
+            thread::yield_now();
+            if spins % 64 == 0 && held != 0 && held != pid && !sys::is_alive(held) {
+                // try to take it from the dead process on the next attempt
+                owner = held;
+            } else if start.get_or_insert_with(Instant::now).elapsed() > LOCK_TIMEOUT {
+                return Err(ChannelError::Io(ErrorKind::TimedOut));
+            }
+        }
+        Ok(Guard { region: self })
+    }
+
+    /// Let anyone waiting in any process know that something changed.
+    fn notify(&self) {
+        let header = self.header();
+        header.futex.fetch_add(1, Ordering::Release);
+        if header.waiters.load(Ordering::Acquire) > 0 {
+            sys::wake(&header.futex);
+        }
+    }
+
+    /// Sleep until `notify` is called after `seen` was read from the futex, or until the timeout.
+    fn wait(&self, seen: u32, timeout: Duration) {
+        let header = self.header();
+        header.waiters.fetch_add(1, Ordering::AcqRel);
+        sys::wait(&header.futex, seen, timeout.min(WAIT_TIMEOUT));
+        header.waiters.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn is_corked(&self) -> bool {
+        self.header().corked.load(Ordering::Acquire) != 0
+    }
+
+    fn cork(&self) {
+        {
+            // the flag is atomic, so it is still set if the lock can't be had
+            let _guard = self.lock();
+            self.header().corked.store(1, Ordering::Release);
+        }
+        self.notify();
+    }
+
+    fn try_push(&self, v: T) -> Result<bool, ChannelError> {
+        let guard = self.lock()?;
+        if self.is_corked() {
+            return Err(ChannelError::IsCorked);
+        }
+        if !guard.push(v) {
+            return Ok(false);
+        }
+        drop(guard);
+        self.notify();
+        Ok(true)
+    }
+
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        loop {
+            let guard = self.lock()?;
+            if self.is_corked() {
+                return Err(ChannelError::IsCorked);
+            }
+            if guard.push(v) {
+                drop(guard);
+                self.notify();
+                return Ok(());
+            }
+            // anything which makes room will change the futex after we release the lock
+            let seen = self.header().futex.load(Ordering::Acquire);
+            drop(guard);
+            self.wait(seen, WAIT_TIMEOUT);
+        }
+    }
+
+    fn try_recv(&self, cursor: usize) -> Result<Option<T>, ChannelError> {
+        let guard = self.lock()?;
+        match guard.take(cursor) {
+            Some((v, moved)) => {
+                drop(guard);
+                if moved {
+                    self.notify();
+                }
+                Ok(Some(v))
+            }
+            None if self.is_corked() => Err(ChannelError::IsCorked),
+            None => Ok(None),
+        }
+    }
+
+    fn recv(&self, cursor: usize) -> Result<T, ChannelError> {
+        self.recv_until(cursor, None)
+            .map(|v| v.expect("Received nothing without a deadline"))
+    }
+
+    fn recv_until(
+        &self,
+        cursor: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Option<T>, ChannelError> {
+        loop {
+            let guard = self.lock()?;
+            if let Some((v, moved)) = guard.take(cursor) {
+                drop(guard);
+                if moved {
+                    self.notify();
+                }
+                return Ok(Some(v));
+            }
+            if self.is_corked() {
+                return Err(ChannelError::IsCorked);
+            }
+            let seen = self.header().futex.load(Ordering::Acquire);
+            drop(guard);
+            let timeout = match deadline {
+                None => WAIT_TIMEOUT,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(None);
+                    }
+                    deadline - now
+                }
+            };
+            self.wait(seen, timeout);
+        }
+    }
+
+    fn new_cursor(&self) -> Result<usize, ChannelError> {
+        let guard = self.lock()?;
+        let header = self.header();
+        let (i, slot) = header
+            .cursors
+            .iter()
+            .enumerate()
+            .find(|(_, slot)| slot.in_use.load(Ordering::Relaxed) == 0)
+            .ok_or(ChannelError::Io(ErrorKind::Other))?;
+        slot.position
+            .store(header.offset.load(Ordering::Relaxed), Ordering::Relaxed);
+        slot.in_use.store(1, Ordering::Relaxed);
+        drop(guard);
+        Ok(i)
+    }
+
+    /// Add another receiver to a cursor which is already in use.
+    fn share_cursor(&self, cursor: usize) -> Result<(), ChannelError> {
+        let _guard = self.lock()?;
+        match self.header().cursors.get(cursor) {
+            Some(slot) if slot.in_use.load(Ordering::Relaxed) != 0 => {
+                slot.in_use.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err(ChannelError::Io(ErrorKind::NotFound)),
+        }
+    }
+
+    fn drop_cursor(&self, cursor: usize) {
+        let guard = self.lock();
+        let users = self.header().cursors[cursor]
+            .in_use
+            .fetch_sub(1, Ordering::AcqRel);
+        if users > 1 {
+            return;
+        }
+        // this may have been the cursor holding everyone else back, but if we couldn't get the
+        // lock the window will move the next time anyone reads
+        let moved = guard.is_ok_and(|guard| guard.move_window());
+        if moved {
+            self.notify();
+        }
+    }
+
+    fn pending(&self) -> Result<usize, ChannelError> {
+        let _guard = self.lock()?;
+        let header = self.header();
+        Ok((header.end.load(Ordering::Relaxed) - header.offset.load(Ordering::Relaxed)) as usize)
+    }
+}
+
+/// Proof that the lock in the header is held, which is released when dropped.
+struct Guard<'a, T: Pod> {
+    region: &'a Region<T>,
+}
+
+impl<'a, T: Pod> Drop for Guard<'a, T> {
+    fn drop(&mut self) {
+        self.region.header().lock.store(0, Ordering::Release);
+    }
+}
+
+impl<'a, T: Pod> Guard<'a, T> {
+    /// Write a value to the end of the ring if there is room.
+    fn push(&self, v: T) -> bool {
+        let header = self.region.header();
+        let end = header.end.load(Ordering::Relaxed);
+        if end - header.offset.load(Ordering::Relaxed) >= self.region.capacity {
+            return false;
+        }
+        unsafe { ptr::write_volatile(self.region.slot(end), v) };
+        header.end.store(end + 1, Ordering::Relaxed);
+        true
+    }
+
+    /// Read the next value for a cursor and advance it, also returning whether the window moved.
+    fn take(&self, cursor: usize) -> Option<(T, bool)> {
+        let header = self.region.header();
+        let slot = &header.cursors[cursor];
+        let position = slot.position.load(Ordering::Relaxed);
+        if position >= header.end.load(Ordering::Relaxed) {
+            return None;
+        }
+        let v = unsafe { ptr::read_volatile(self.region.slot(position)) };
+        slot.position.store(position + 1, Ordering::Relaxed);
+        let moved = position == header.offset.load(Ordering::Relaxed) && self.move_window();
+        Some((v, moved))
+    }
+
+    /// Move the oldest item index past everything which all cursors have read.
+    fn move_window(&self) -> bool {
+        let header = self.region.header();
+        let oldest = header
+            .cursors
+            .iter()
+            .filter(|slot| slot.in_use.load(Ordering::Relaxed) != 0)
+            .map(|slot| slot.position.load(Ordering::Relaxed))
+            .min();
+        match oldest {
+            // keep everything for a receiver which may be created later
+            None => false,
+            Some(oldest) if oldest > header.offset.load(Ordering::Relaxed) => {
+                header.offset.store(oldest, Ordering::Relaxed);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+}
+
+/// A channel of plain-old-data items living in a memory mapped file, usually in `/dev/shm`, so
+/// processes on the same host can be connected to each other. It behaves like `sync_channel`:
+/// every receiver has its own cursor and sees every item, senders wait while the ring is full, and
+/// the channel is corked once the last sender in any process is dropped.
+///
+/// Receivers can also share a cursor, splitting the items between them as `SharedReceiver` does,
+/// including receivers in different processes: pass the cursor from `ChannelReceiver::id` to
+/// `shared_receiver` in the other process.
+///
+/// One process creates the channel and the others open the same path. A process which dies while
+/// holding a receiver leaves its cursor behind, which will eventually hold up the senders. In the
+/// same way a process which dies while holding a sender leaves the count of senders raised, so the
+/// channel is never corked and receivers in other processes wait for items forever. The
+/// lock protecting the ring records which process holds it, so one which dies while holding it is
+/// noticed and the lock taken over, and waiting on a live process which never lets go fails with
+/// `TimedOut` rather than hanging.
+pub struct ShmChannel<T: Pod> {
+    region: Arc<Region<T>>,
+}
+
+impl<T: Pod> ShmChannel<T> {
+    /// Create a new channel at the given path which can hold `capacity` items. This fails with
+    /// `AlreadyExists` if there is already a file there, since another process may still have it
+    /// mapped, so remove a stale channel before creating a new one in its place.
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self, ChannelError> {
+        assert!(capacity > 0, "Capacity must be at least 1");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let len = Region::<T>::data_start() + Region::<T>::slot_size() * capacity;
+        file.set_len(len as u64)?;
+        let region = Region::map(file, capacity as u64)?;
+        let header = region.header();
+        header
+            .item_size
+            .store(mem::size_of::<T>() as u64, Ordering::Relaxed);
+        header.capacity.store(capacity as u64, Ordering::Relaxed);
+        header.magic.store(MAGIC, Ordering::Release);
+        Ok(Self {
+            region: Arc::new(region),
+        })
+    }
+
+    /// Open a channel another process created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ChannelError> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = [0u8; 24];
+        std::io::Read::read_exact(&mut file, &mut header)?;
+        let field = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&header[i * 8..(i + 1) * 8]);
+            u64::from_ne_bytes(bytes)
+        };
+        if field(0) != MAGIC || field(1) != mem::size_of::<T>() as u64 || field(2) == 0 {
+            return Err(ChannelError::Io(ErrorKind::InvalidData));
+        }
+        Ok(Self {
+            region: Arc::new(Region::map(file, field(2))?),
+        })
+    }
+
+    /// Create a new sender for this channel.
+    pub fn sender(&self) -> ShmSender<T> {
+        self.region.header().senders.fetch_add(1, Ordering::AcqRel);
+        ShmSender {
+            region: self.region.clone(),
+        }
+    }
+
+    /// Create a new receiver with its own cursor at the oldest item in the channel. This fails if
+    /// there are already `SHM_MAX_CURSORS` receivers.
+    pub fn receiver(&self) -> Result<ShmReceiver<T>, ChannelError> {
+        Ok(ShmReceiver {
+            cursor: self.region.new_cursor()?,
+            region: self.region.clone(),
+        })
+    }
+
+    /// Create a receiver which shares the cursor of an existing receiver, which may be in another
+    /// process, so that each item goes to only one of them. `cursor` is the second half of the
+    /// existing receiver's `ChannelReceiver::id`, and this fails with `NotFound` if no receiver is
+    /// using it.
+    pub fn shared_receiver(&self, cursor: usize) -> Result<ShmReceiver<T>, ChannelError> {
+        self.region.share_cursor(cursor)?;
+        Ok(ShmReceiver {
+            cursor,
+            region: self.region.clone(),
+        })
+    }
+}
+
+/// `ChannelSender` which writes to a `ShmChannel`.
+pub struct ShmSender<T: Pod> {
+    region: Arc<Region<T>>,
+}
+
+impl<T: Pod> Clone for ShmSender<T> {
+    fn clone(&self) -> Self {
+        self.region.header().senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            region: self.region.clone(),
+        }
+    }
+}
+
+impl<T: Pod> Drop for ShmSender<T> {
+    fn drop(&mut self) {
+        if self.region.header().senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // that was the last sender in any process
+            self.region.cork()
+        }
+    }
+}
+
+impl<T: Pod> ChannelSender for ShmSender<T> {
+    type Item = T;
+
+    fn id(&self) -> usize {
+        self.region.id
+    }
+
+    fn send(&self, v: T) -> Result<(), ChannelError> {
+        self.region.send(v)
+    }
+
+    fn try_send(&self, v: T) -> Result<Option<T>, ChannelError> {
+        Ok(if self.region.try_push(v)? {
+            None
+        } else {
+            Some(v)
+        })
+    }
+
+    fn cork(&self) {
+        self.region.cork()
+    }
+
+    fn is_corked(&self) -> bool {
+        self.region.is_corked()
+    }
+
+    fn pending(&self) -> Result<usize, ChannelError> {
+        self.region.pending()
+    }
+}
+
+/// `ChannelReceiver` which reads from a `ShmChannel` with its own cursor, or one shared with other
+/// receivers created by `ShmChannel::shared_receiver`.
+pub struct ShmReceiver<T: Pod> {
+    region: Arc<Region<T>>,
+    cursor: usize,
+}
+
+/// Make another reader of the same underlying data starting at the oldest item in the ring.
+///
+/// # Panics
+/// If there are no cursors left in the channel.
+impl<T: Pod> Clone for ShmReceiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cursor: self
+                .region
+                .new_cursor()
+                .expect("No cursors left in the shared-memory channel"),
+            region: self.region.clone(),
+        }
+    }
+}
+
+impl<T: Pod> Drop for ShmReceiver<T> {
+    fn drop(&mut self) {
+        self.region.drop_cursor(self.cursor)
+    }
+}
+
+impl<T: Pod> ChannelReceiver for ShmReceiver<T> {
+    type Item = T;
+
+    fn id(&self) -> (usize, usize) {
+        (self.region.id, self.cursor)
+    }
+
+    fn recv(&self) -> Result<T, ChannelError> {
+        self.region.recv(self.cursor)
+    }
+
+    fn try_recv(&self) -> Result<Option<T>, ChannelError> {
+        self.region.try_recv(self.cursor)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<T>, ChannelError> {
+        self.region
+            .recv_until(self.cursor, Some(Instant::now() + timeout))
+    }
+
+    fn is_corked(&self) -> bool {
+        self.region.is_corked()
+    }
+
+    fn pending(&self) -> Result<usize, ChannelError> {
+        self.region.pending()
+    }
+}
+
+/// The few system calls we need, declared by hand to keep this crate free of dependencies.
+mod sys {
+    use super::*;
+
+    pub const PROT_READ: c_int = 1;
+    pub const PROT_WRITE: c_int = 2;
+    pub const MAP_SHARED: c_int = 1;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    /// `off_t` is a `long` on Linux, so only 32 bits on 32-bit targets, but always 64 bits on the
+    /// BSDs and macOS.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[allow(non_camel_case_types)]
+    pub type off_t = c_long;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    #[allow(non_camel_case_types)]
+    pub type off_t = i64;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: off_t,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn kill(pid: c_int, sig: c_int) -> c_int;
+    }
+
+    /// No such process.
+    const ESRCH: i32 = 3;
+
+    /// Check if a process exists by sending it the null signal, which only checks whether it
+    /// could be sent. A process we aren't allowed to signal still exists.
+    pub fn is_alive(pid: u32) -> bool {
+        if unsafe { kill(pid as c_int, 0) } == 0 {
+            return true;
+        }
+        std::io::Error::last_os_error().raw_os_error() != Some(ESRCH)
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "riscv64"
+        )
+    ))]
+    mod futex {
+        use super::*;
+
+        #[cfg(target_arch = "x86_64")]
+        const SYS_FUTEX: c_long = 202;
+        #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+        const SYS_FUTEX: c_long = 98;
+        // not the private variants since other processes are waiting on the same memory
+        const FUTEX_WAIT: c_int = 0;
+        const FUTEX_WAKE: c_int = 1;
+
+        #[repr(C)]
+        struct Timespec {
+            tv_sec: i64,
+            tv_nsec: i64,
+        }
+
+        extern "C" {
+            fn syscall(num: c_long, ...) -> c_long;
+        }
+
+        pub fn wait(futex: &AtomicU32, seen: u32, timeout: Duration) {
+            let timeout = Timespec {
+                tv_sec: timeout.as_secs() as i64,
+                tv_nsec: timeout.subsec_nanos() as i64,
+            };
+            unsafe {
+                syscall(
+                    SYS_FUTEX,
+                    futex as *const AtomicU32,
+                    FUTEX_WAIT,
+                    seen,
+                    &timeout as *const Timespec,
+                );
+            }
+        }
+
+        pub fn wake(futex: &AtomicU32) {
+            unsafe {
+                syscall(SYS_FUTEX, futex as *const AtomicU32, FUTEX_WAKE, c_int::MAX);
+            }
+        }
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            target_arch = "riscv64"
+        )
+    )))]
+    mod futex {
+        use super::*;
+
+        /// Without futexes we fall back to polling for changes.
+        pub fn wait(futex: &AtomicU32, seen: u32, timeout: Duration) {
+            let step = Duration::from_micros(200);
+            let mut waited = Duration::default();
+            while futex.load(Ordering::Acquire) == seen && waited < timeout {
+                thread::sleep(step);
+                waited += step;
+            }
+        }
+
+        pub fn wake(_futex: &AtomicU32) {}
+    }
+
+    pub use futex::{wait, wake};
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+    use crate::mpmc::test_util::check_recv_timeout;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("cgraph-shm-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn non_blocking_many_rx() {
+        let path = temp_path("non-blocking");
+        let channel = ShmChannel::<u32>::create(&path, 2).unwrap();
+        let tx = channel.sender();
+        let rx1 = channel.receiver().unwrap();
+        let rx2 = rx1.clone();
+        assert_ne!(rx1.id().1, rx2.id().1);
+
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(tx.try_send(3).unwrap(), Some(3));
+        assert_eq!(rx1.try_recv().unwrap(), Some(1));
+        assert_eq!(rx1.try_recv().unwrap(), Some(2));
+        // still waiting on the second receiver
+        assert_eq!(tx.try_send(3).unwrap(), Some(3));
+        assert_eq!(rx2.try_recv().unwrap(), Some(1));
+        tx.try_send(3).unwrap();
+        assert_eq!(tx.pending().unwrap(), 2);
+
+        drop(rx2);
+        // the window moves without the dropped receiver
+        tx.try_send(4).unwrap();
+        assert_eq!(rx1.try_recv().unwrap(), Some(3));
+        assert_eq!(rx1.try_recv().unwrap(), Some(4));
+        drop(tx);
+        assert_eq!(rx1.try_recv(), Err(ChannelError::IsCorked));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_cursor() {
+        let path = temp_path("shared");
+        let producer = ShmChannel::<u32>::create(&path, 4).unwrap();
+        let consumer = ShmChannel::<u32>::open(&path).unwrap();
+        let tx = producer.sender();
+        let rx1 = producer.receiver().unwrap();
+        // another process would be handed the cursor some other way
+        let rx2 = consumer.shared_receiver(rx1.id().1).unwrap();
+        assert_eq!(rx1.id().1, rx2.id().1);
+        assert_eq!(
+            consumer.shared_receiver(rx1.id().1 + 1).err(),
+            Some(ChannelError::Io(ErrorKind::NotFound))
+        );
+
+        for i in 0..4 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx1.try_recv().unwrap(), Some(0));
+        assert_eq!(rx2.try_recv().unwrap(), Some(1));
+        assert_eq!(rx1.try_recv().unwrap(), Some(2));
+        // the cursor stays in use while either receiver has it
+        drop(rx1);
+        assert_eq!(rx2.try_recv().unwrap(), Some(3));
+        tx.send(4).unwrap();
+        assert_eq!(rx2.try_recv().unwrap(), Some(4));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn separate_mappings() {
+        // two mappings of the same file behave the same as two processes would
+        let path = temp_path("mappings");
+        let producer = ShmChannel::<[f32; 4]>::create(&path, 8).unwrap();
+        let consumer = ShmChannel::<[f32; 4]>::open(&path).unwrap();
+        assert!(ShmChannel::<u8>::open(&path).is_err());
+
+        let rx = consumer.receiver().unwrap();
+        let tx = producer.sender();
+        let tx_thread = thread::spawn(move || {
+            for i in 0..500 {
+                let v = i as f32;
+                tx.send([v, v + 1.0, v + 2.0, v + 3.0]).unwrap();
+            }
+        });
+        let rx_thread = thread::spawn(move || {
+            for i in 0..500 {
+                let v = i as f32;
+                assert_eq!(rx.recv().unwrap(), [v, v + 1.0, v + 2.0, v + 3.0]);
+            }
+            // the sender being dropped in the other mapping corks this one
+            assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+        });
+        tx_thread.join().unwrap();
+        rx_thread.join().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuse_to_replace_existing() {
+        let path = temp_path("existing");
+        let _channel = ShmChannel::<u32>::create(&path, 2).unwrap();
+        assert_eq!(
+            ShmChannel::<u32>::create(&path, 2).err(),
+            Some(ChannelError::Io(ErrorKind::AlreadyExists))
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn lock_held_by_another_process() {
+        let path = temp_path("lock");
+        let channel = ShmChannel::<u32>::create(&path, 2).unwrap();
+        let tx = channel.sender();
+        let rx = channel.receiver().unwrap();
+        let lock = &channel.region.header().lock;
+
+        // a process id which can't exist, as if the holder died
+        lock.store(i32::MAX as u32, Ordering::Release);
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        // init is always alive but will never let go
+        lock.store(1, Ordering::Release);
+        assert_eq!(tx.try_send(2), Err(ChannelError::Io(ErrorKind::TimedOut)));
+        lock.store(0, Ordering::Release);
+        tx.try_send(2).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let path = temp_path("recv-timeout");
+        let channel = ShmChannel::<u32>::create(&path, 2).unwrap();
+        let rx = channel.receiver().unwrap();
+        check_recv_timeout(channel.sender(), &rx, 7);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mpmc::remote::{Connection, Listener, DEFAULT_PREFETCH};
+use crate::mpmc::{
+    ChannelError, ChannelServer, Codec, Position, RemoteCursor, RemoteReceiver, RemoteSender,
+    Sender,
+};
+
+impl Connection for UnixStream {
+    fn try_clone_connection(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+
+    fn close(&self) {
+        let _ = self.shutdown(Shutdown::Both);
+    }
+
+    fn finish(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+/// Listens on a socket file, which is removed again once the server shuts down.
+struct SocketFile {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Listener for SocketFile {
+    fn accept_connection(&self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = self.listener.accept()?;
+        Ok(Box::new(stream))
+    }
+
+    fn shutdown(&self) {
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The same protocol as over TCP, but for processes on the same host talking over a Unix domain
+/// socket. If the process on the other end dies, the operating system closes its end of the socket
+/// so anything waiting on it gets `ChannelError::Disconnected` instead of waiting forever.
+impl<T, C> ChannelServer<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    /// Serve the channel `tx` sends to on a Unix domain socket created at `path`. It is an error
+    /// for `path` to exist already.
+    pub fn bind_unix<P: AsRef<Path>>(
+        path: P,
+        tx: &Sender<T>,
+        codec: C,
+    ) -> Result<Self, ChannelError> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self::serve(
+            Arc::new(SocketFile { listener, path }),
+            None,
+            tx,
+            codec,
+        ))
+    }
+}
+
+impl<T, C> RemoteSender<T, C>
+where
+    T: Clone,
+    C: Codec<T>,
+{
+    /// Connect to a `ChannelServer` listening on a Unix domain socket.
+    pub fn connect_unix<P: AsRef<Path>>(path: P, codec: C) -> Result<Self, ChannelError> {
+        Self::handshake(Box::new(UnixStream::connect(path)?), codec)
+    }
+}
+
+impl<T, C> RemoteReceiver<T, C>
+where
+    T: Clone + Send + 'static,
+    C: Codec<T> + 'static,
+{
+    /// Connect to a `ChannelServer` listening on a Unix domain socket with a cursor of our own
+    /// starting at the oldest item in the channel.
+    pub fn connect_unix<P: AsRef<Path>>(path: P, codec: C) -> Result<Self, ChannelError> {
+        Self::connect_unix_with(
+            path,
+            codec,
+            RemoteCursor::Own(Position::Oldest),
+            DEFAULT_PREFETCH,
+        )
+    }
+
+    /// Connect to a `ChannelServer` listening on a Unix domain socket with the given cursor and
+    /// number of items to fetch ahead of time.
+    pub fn connect_unix_with<P: AsRef<Path>>(
+        path: P,
+        codec: C,
+        cursor: RemoteCursor,
+        prefetch: u32,
+    ) -> Result<Self, ChannelError> {
+        Self::handshake(
+            Box::new(UnixStream::connect(path)?),
+            codec,
+            cursor,
+            prefetch,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::thread;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, ChannelReceiver, ChannelSender, RawLeCodec};
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("cgraph-uds-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn unix_socket_round_trip() {
+        let path = socket_path("round-trip");
+        let (tx, rx) = sync_channel::<u32>(4);
+        let server = ChannelServer::bind_unix(&path, &tx, RawLeCodec).unwrap();
+        assert_eq!(server.local_addr(), None);
+        let remote_tx = RemoteSender::<u32, _>::connect_unix(&path, RawLeCodec).unwrap();
+        let remote_rx = RemoteReceiver::<u32, _>::connect_unix(&path, RawLeCodec).unwrap();
+        drop(tx);
+
+        let tx_thread = thread::spawn(move || {
+            for i in 0..100 {
+                remote_tx.send(i).unwrap();
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(rx.recv().unwrap(), i);
+            assert_eq!(remote_rx.recv().unwrap(), i);
+        }
+        tx_thread.join().unwrap();
+        assert_eq!(remote_rx.recv(), Err(ChannelError::IsCorked));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn dead_peer_is_disconnected() {
+        let path = socket_path("dead-peer");
+        let listener = UnixListener::bind(&path).unwrap();
+        let fake = thread::spawn(move || {
+            // hand out one credit and then go away like a crashed process would
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut hello = [0u8; 5];
+            io::Read::read_exact(&mut conn, &mut hello).unwrap();
+            let mut welcome = vec![3, 20, 0, 0, 0];
+            welcome.extend_from_slice(&[0; 16]);
+            welcome.extend_from_slice(&1u32.to_le_bytes());
+            io::Write::write_all(&mut conn, &welcome).unwrap();
+        });
+        let remote_tx = RemoteSender::<u32, _>::connect_unix(&path, RawLeCodec).unwrap();
+        fake.join().unwrap();
+        // either the write or waiting for the next credit notices the peer is gone
+        let _ = remote_tx.send(1);
+        assert_eq!(remote_tx.send(2), Err(ChannelError::Disconnected));
+        fs::remove_file(&path).unwrap();
+    }
+}
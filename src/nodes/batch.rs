@@ -0,0 +1,252 @@
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{ChannelReceiver, ChannelSender};
+
+use super::{recv_or_end, recv_until, ComputeNode, Input};
+
+/// Converts a stream of packets of any size into packets of exactly `size` items, keeping the order
+/// of the items. Whatever is left over when the input is corked is sent as one last, shorter
+/// packet.
+#[derive(Clone, Debug)]
+pub struct Rechunk<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    size: usize,
+}
+
+impl<T, R, S> Rechunk<R, S>
+where
+    T: Clone,
+    R: ChannelReceiver<Item = Vec<T>>,
+    S: ChannelSender<Item = Vec<T>>,
+{
+    /// Panics if `size` is 0.
+    pub fn new(name: String, rx: R, tx: S, size: usize) -> Self {
+        assert!(size > 0, "Packet size must not be 0");
+        Self { name, rx, tx, size }
+    }
+}
+
+impl<T, R, S> ComputeNode for Rechunk<R, S>
+where
+    T: Clone + Send,
+    R: ChannelReceiver<Item = Vec<T>> + Send,
+    S: ChannelSender<Item = Vec<T>> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut packet = Vec::with_capacity(self.size);
+        'input: while let Some(v) = recv_or_end(&self.rx) {
+            let mut rest = &v[..];
+            while !rest.is_empty() {
+                let n = rest.len().min(self.size - packet.len());
+                packet.extend_from_slice(&rest[..n]);
+                rest = &rest[n..];
+                if packet.len() == self.size {
+                    let full = mem::replace(&mut packet, Vec::with_capacity(self.size));
+                    if self.tx.send(full).is_err() {
+                        break 'input;
+                    }
+                }
+            }
+        }
+        if !packet.is_empty() {
+            let _ = self.tx.send(packet);
+        }
+        self.tx.cork();
+    }
+}
+
+/// Collects items into batches of up to `size`. Without a linger time batches are only sent once
+/// they are full, and with one a partial batch is sent once its first item has waited that long,
+/// which bounds the latency added when the input is slow. A partial batch is also sent when the
+/// input is corked.
+#[derive(Clone, Debug)]
+pub struct Batch<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    size: usize,
+    linger: Option<Duration>,
+}
+
+impl<T, R, S> Batch<R, S>
+where
+    T: Clone,
+    R: ChannelReceiver<Item = T>,
+    S: ChannelSender<Item = Vec<T>>,
+{
+    /// Panics if `size` is 0.
+    pub fn new(name: String, rx: R, tx: S, size: usize) -> Self {
+        assert!(size > 0, "Batch size must not be 0");
+        Self {
+            name,
+            rx,
+            tx,
+            size,
+            linger: None,
+        }
+    }
+
+    /// Send partial batches once their first item has waited for `linger`.
+    pub fn linger(mut self, linger: Duration) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+}
+
+impl<T, R, S> ComputeNode for Batch<R, S>
+where
+    T: Clone + Send,
+    R: ChannelReceiver<Item = T> + Send,
+    S: ChannelSender<Item = Vec<T>> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut batch = Vec::with_capacity(self.size);
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let full = match recv_until(&self.rx, deadline) {
+                Input::Item(v) => {
+                    if batch.is_empty() {
+                        deadline = self.linger.map(|linger| Instant::now() + linger);
+                    }
+                    batch.push(v);
+                    batch.len() == self.size
+                }
+                Input::Timeout => true,
+                Input::End => break,
+            };
+            if full {
+                deadline = None;
+                let batch = mem::replace(&mut batch, Vec::with_capacity(self.size));
+                if self.tx.send(batch).is_err() {
+                    self.tx.cork();
+                    return;
+                }
+            }
+        }
+        if !batch.is_empty() {
+            let _ = self.tx.send(batch);
+        }
+        self.tx.cork();
+    }
+}
+
+/// Sends every item of every batch it receives on its own, in order.
+#[derive(Clone)]
+pub struct Unbatch<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+}
+
+impl<R, S> Debug for Unbatch<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<T, R, S> Unbatch<R, S>
+where
+    T: Clone,
+    R: ChannelReceiver<Item = Vec<T>>,
+    S: ChannelSender<Item = T>,
+{
+    pub fn new(name: String, rx: R, tx: S) -> Self {
+        Self { name, rx, tx }
+    }
+}
+
+impl<T, R, S> ComputeNode for Unbatch<R, S>
+where
+    T: Clone + Send,
+    R: ChannelReceiver<Item = Vec<T>> + Send,
+    S: ChannelSender<Item = T> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        'input: while let Some(batch) = recv_or_end(&self.rx) {
+            for v in batch {
+                if self.tx.send(v).is_err() {
+                    break 'input;
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+    use crate::mpmc::sync_channel;
+    use crate::nodes::test_util::drain;
+
+    #[test]
+    fn rechunk_and_unbatch() {
+        let (tx, rx) = sync_channel(8);
+        let (chunk_tx, chunk_rx) = sync_channel(8);
+        tx.send(vec![1, 2, 3, 4, 5]).unwrap();
+        tx.send(vec![]).unwrap();
+        tx.send(vec![6]).unwrap();
+        tx.send(vec![7, 8, 9]).unwrap();
+        drop(tx);
+        Rechunk::new("rechunk".into(), rx, chunk_tx, 4).run();
+        let chunks = drain(&chunk_rx);
+        assert_eq!(chunks, [vec![1, 2, 3, 4], vec![5, 6, 7, 8], vec![9]]);
+
+        let (tx, rx) = sync_channel(8);
+        let (out_tx, out_rx) = sync_channel(16);
+        for chunk in chunks {
+            tx.send(chunk).unwrap();
+        }
+        drop(tx);
+        Unbatch::new("unbatch".into(), rx, out_tx).run();
+        assert_eq!(drain(&out_rx), (1..=9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn batch_by_count() {
+        let (tx, rx) = sync_channel(8);
+        let (out_tx, out_rx) = sync_channel(8);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        Batch::new("batch".into(), rx, out_tx, 2).run();
+        assert_eq!(drain(&out_rx), [vec![0, 1], vec![2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn batch_linger() {
+        let (tx, rx) = sync_channel(8);
+        let (out_tx, out_rx) = sync_channel(8);
+        let sender = thread::spawn(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            // long enough for the partial batch to be sent without us
+            thread::sleep(Duration::from_millis(80));
+            tx.send(3).unwrap();
+        });
+        Batch::new("batch".into(), rx, out_tx, 10)
+            .linger(Duration::from_millis(20))
+            .run();
+        sender.join().unwrap();
+        assert_eq!(drain(&out_rx), [vec![1, 2], vec![3]]);
+    }
+}
@@ -0,0 +1,968 @@
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{
+    sync_dir, ChannelError, ChannelReceiver, ChannelSender, Codec, Position, Receiver, Sender,
+};
+
+use super::{channel_error, recv_or_end, report_error, ComputeNode, NodeError};
+
+/// An item in a checkpointed stream. Barriers are sent through the same channels as the items so
+/// every node sees them in the same place relative to the data: a node saves its state when a
+/// barrier reaches it and then passes the barrier on, so all the snapshots for a checkpoint cover
+/// exactly the items which came before the barrier.
+///
+/// Nodes with several inputs have to wait for a barrier to reach all of them before passing it on,
+/// or items from after it on one input would be mixed in with items from before it on another.
+/// `Merge::align_barriers`, `RoundRobin::align_barriers`, `Zip` over `AlignBarriers` and
+/// `StatefulJoin` do this, expecting the same barriers in the same order on every input, as sent
+/// by a single `CheckpointSource`. Only nodes which implement `StatefulNode` save anything:
+/// `CheckpointSource`, `StatefulScan`, `StatefulFold`, `StatefulWindow` and `StatefulJoin`. Other
+/// nodes in a checkpointed part of a graph should not hold on to items between barriers.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Record<T> {
+    Item(T),
+    /// Marks the point in the stream checkpoint `id` was taken at.
+    Barrier(u64),
+}
+
+impl<T> Record<T> {
+    /// Apply `f` to the item, passing barriers through unchanged.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Record<U> {
+        match self {
+            Record::Item(v) => Record::Item(f(v)),
+            Record::Barrier(id) => Record::Barrier(id),
+        }
+    }
+
+    /// The item, or None for a barrier. This strips the barriers out of a stream when used with a
+    /// `FilterMap` node at the end of a checkpointed part of the graph.
+    pub fn into_item(self) -> Option<T> {
+        match self {
+            Record::Item(v) => Some(v),
+            Record::Barrier(_) => None,
+        }
+    }
+
+    /// The id of the checkpoint this marks, or None for an item.
+    pub fn barrier(&self) -> Option<u64> {
+        match self {
+            Record::Item(_) => None,
+            Record::Barrier(id) => Some(*id),
+        }
+    }
+}
+
+/// Lines up the barriers arriving on the inputs of a node which reads each input on a thread of
+/// its own. A thread which has read a barrier waits until every input which is still open has got
+/// to one too, so nothing after the barrier is read from any input until everything before it has
+/// been. The barrier is then passed on once, by whichever thread got there last.
+pub(super) struct BarrierAlignment<B> {
+    state: Mutex<Alignment<B>>,
+    released: Condvar,
+}
+
+struct Alignment<B> {
+    /// Inputs which have not been corked yet.
+    open: usize,
+    /// Inputs waiting at the barrier.
+    waiting: usize,
+    /// The barrier being waited on, from the first input to reach it.
+    barrier: Option<B>,
+    /// Bumped every time a barrier is passed on, so the waiting threads know to carry on.
+    round: u64,
+}
+
+impl<B> BarrierAlignment<B> {
+    pub(super) fn new(inputs: usize) -> Self {
+        Self {
+            state: Mutex::new(Alignment {
+                open: inputs,
+                waiting: 0,
+                barrier: None,
+                round: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Alignment<B>> {
+        self.state.lock().expect("Thread was poisoned")
+    }
+
+    /// Wait at `barrier` until the other open inputs have reached it, calling `pass_on` with it
+    /// from the last thread to get there before any of them carry on.
+    pub(super) fn arrive<F: FnOnce(B)>(&self, barrier: B, pass_on: F) {
+        let mut state = self.lock();
+        state.barrier.get_or_insert(barrier);
+        state.waiting += 1;
+        if state.waiting == state.open {
+            self.release(&mut state, pass_on);
+            return;
+        }
+        let round = state.round;
+        while state.round == round {
+            state = self.released.wait(state).expect("Thread was poisoned");
+        }
+    }
+
+    /// Stop waiting for an input which has been corked, or which its thread stopped reading. If
+    /// it was the last one the others were waiting on, the barrier is passed on here.
+    pub(super) fn end<F: FnOnce(B)>(&self, pass_on: F) {
+        let mut state = self.lock();
+        state.open -= 1;
+        if state.waiting > 0 && state.waiting == state.open {
+            self.release(&mut state, pass_on);
+        }
+    }
+
+    fn release<F: FnOnce(B)>(&self, state: &mut Alignment<B>, pass_on: F) {
+        if let Some(barrier) = state.barrier.take() {
+            pass_on(barrier);
+        }
+        state.waiting = 0;
+        state.round += 1;
+        self.released.notify_all();
+    }
+}
+
+/// A directory of checkpoints. Each checkpoint is a directory named after its id holding one file
+/// for every node which has saved its state for it, so a checkpoint is only complete once all of
+/// the nodes in the graph have saved.
+#[derive(Clone, Debug)]
+pub struct CheckpointStore {
+    dir: PathBuf,
+}
+
+impl CheckpointStore {
+    /// Use `dir` for checkpoints, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Save the snapshot of `node` for checkpoint `id`. This writes a new file and renames it into
+    /// place so a crash can't leave a half written snapshot behind. Node names are used as file
+    /// names, so names which aren't a plain file name, such as ones containing `/`, are rejected.
+    pub fn save(&self, id: u64, node: &str, snapshot: &[u8]) -> io::Result<()> {
+        let path = self.path(id, node)?;
+        let dir = self.dir.join(id.to_string());
+        if !dir.is_dir() {
+            fs::create_dir_all(&dir)?;
+            sync_dir(&self.dir)?;
+        }
+        let tmp = dir.join(format!("{}.tmp", node));
+        let mut file = File::create(&tmp)?;
+        file.write_all(snapshot)?;
+        file.sync_all()?;
+        fs::rename(tmp, path)?;
+        sync_dir(&dir)
+    }
+
+    /// Load the snapshot of `node` for checkpoint `id`, or None if it never saved one.
+    pub fn load(&self, id: u64, node: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(id, node)?) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The newest checkpoint every one of `nodes` has saved its state for, which is the one to
+    /// restore a graph made of those nodes from.
+    pub fn latest(&self, nodes: &[&str]) -> io::Result<Option<u64>> {
+        let mut latest = None;
+        for id in self.ids()? {
+            let mut complete = true;
+            for node in nodes {
+                complete &= self.path(id, node)?.is_file();
+            }
+            if complete && latest.map_or(true, |latest| id > latest) {
+                latest = Some(id);
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Delete every checkpoint older than `id`, once a newer one is complete and they are no
+    /// longer needed.
+    pub fn remove_before(&self, id: u64) -> io::Result<()> {
+        for old in self.ids()?.into_iter().filter(|old| *old < id) {
+            fs::remove_dir_all(self.dir.join(old.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Where the snapshot of `node` for checkpoint `id` is kept.
+    fn path(&self, id: u64, node: &str) -> io::Result<PathBuf> {
+        let plain =
+            !node.is_empty() && node != "." && node != ".." && !node.contains(['/', '\\', '\0']);
+        if !plain {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} can't be used as a snapshot file name", node),
+            ));
+        }
+        Ok(self.dir.join(id.to_string()).join(node))
+    }
+
+    /// The ids of every checkpoint in the directory, complete or not.
+    fn ids(&self) -> io::Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let id = entry.file_name().to_str().and_then(|s| s.parse().ok());
+            if let (Some(id), true) = (id, entry.file_type()?.is_dir()) {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// A compute node with state which can be saved and restored, so a graph can carry on where it
+/// left off after a restart instead of starting over. Snapshots are opaque bytes which only the
+/// node itself needs to understand.
+///
+/// `restore` must be called before the node is run.
+pub trait StatefulNode: ComputeNode {
+    /// Encode the current state of the node.
+    fn snapshot(&self) -> io::Result<Vec<u8>>;
+
+    /// Replace the state of the node with one from an earlier snapshot.
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()>;
+
+    /// Restore the state this node saved for checkpoint `id`.
+    fn restore_from(&self, store: &CheckpointStore, id: u64) -> io::Result<()> {
+        match store.load(id, self.name())? {
+            Some(snapshot) => self.restore(&snapshot),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no snapshot for checkpoint {}", self.name(), id),
+            )),
+        }
+    }
+}
+
+/// Save the state of `node` for checkpoint `id` if it has somewhere to save it. A failure only
+/// leaves the checkpoint incomplete, so it is reported and the node carries on.
+pub(super) fn checkpoint<N: StatefulNode>(
+    node: &N,
+    store: &Option<Arc<CheckpointStore>>,
+    errors: &Option<Sender<NodeError>>,
+    id: u64,
+) {
+    if let Some(store) = store {
+        if let Err(e) = node
+            .snapshot()
+            .and_then(|snapshot| store.save(id, node.name(), &snapshot))
+        {
+            report_error(errors, node.name(), &e);
+        }
+    }
+}
+
+/// Turn an error encoding or decoding a snapshot into one a node can report.
+fn snapshot_error(e: ChannelError) -> io::Error {
+    match e {
+        ChannelError::Io(kind) => io::Error::new(kind, "invalid snapshot"),
+        e => io::Error::other(format!("unable to take snapshot: {:?}", e)),
+    }
+}
+
+/// The error for a snapshot which can't be made sense of.
+pub(super) fn invalid_snapshot() -> io::Error {
+    snapshot_error(ChannelError::Io(io::ErrorKind::InvalidData))
+}
+
+/// Builds a snapshot out of numbers, times and items encoded with a codec, for nodes whose state
+/// is more than a single value. Items are written with their length in front so they can be told
+/// apart again.
+pub(super) struct SnapshotWriter {
+    bytes: Vec<u8>,
+    now: Instant,
+}
+
+impl SnapshotWriter {
+    pub(super) fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            now: Instant::now(),
+        }
+    }
+
+    pub(super) fn u64(&mut self, v: u64) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    /// Times are saved as how long before the snapshot they were, since an `Instant` means nothing
+    /// once the process has restarted.
+    pub(super) fn time(&mut self, t: Instant) {
+        let age = self.now.saturating_duration_since(t);
+        self.u64(u64::try_from(age.as_micros()).unwrap_or(u64::MAX));
+    }
+
+    pub(super) fn optional_time(&mut self, t: Option<Instant>) {
+        self.u64(t.is_some() as u64);
+        if let Some(t) = t {
+            self.time(t);
+        }
+    }
+
+    pub(super) fn item<T, C: Codec<T>>(&mut self, codec: &C, v: &T) -> io::Result<()> {
+        let start = self.bytes.len();
+        self.u64(0);
+        codec.encode(v, &mut self.bytes).map_err(snapshot_error)?;
+        let len = (self.bytes.len() - start - 8) as u64;
+        self.bytes[start..start + 8].copy_from_slice(&len.to_le_bytes());
+        Ok(())
+    }
+
+    pub(super) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads back what a `SnapshotWriter` wrote, in the same order.
+pub(super) struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+    now: Instant,
+}
+
+impl<'a> SnapshotReader<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            now: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            return Err(invalid_snapshot());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(super) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// A time saved by `SnapshotWriter::time`, as if no time had passed since the snapshot was
+    /// taken.
+    pub(super) fn time(&mut self) -> io::Result<Instant> {
+        let age = Duration::from_micros(self.u64()?);
+        Ok(self.now.checked_sub(age).unwrap_or(self.now))
+    }
+
+    pub(super) fn optional_time(&mut self) -> io::Result<Option<Instant>> {
+        match self.u64()? {
+            0 => Ok(None),
+            1 => self.time().map(Some),
+            _ => Err(invalid_snapshot()),
+        }
+    }
+
+    pub(super) fn item<T, C: Codec<T>>(&mut self, codec: &C) -> io::Result<T> {
+        let len = usize::try_from(self.u64()?).unwrap_or(usize::MAX);
+        codec.decode(self.take(len)?).map_err(snapshot_error)
+    }
+
+    /// Check that everything in the snapshot was read.
+    pub(super) fn finish(self) -> io::Result<()> {
+        if !self.bytes.is_empty() {
+            return Err(invalid_snapshot());
+        }
+        Ok(())
+    }
+}
+
+/// Where a `CheckpointSource` is up to.
+#[derive(Clone, Copy, Debug)]
+struct SourceState {
+    /// Position to seek the input to when restoring.
+    restore_to: Option<u64>,
+    next_id: u64,
+}
+
+/// The start of a checkpointed part of a graph. It passes on items from its input and adds a
+/// barrier after every `every` of them, saving the position of its input with each one. Restoring
+/// seeks the input back to that position, so the input must still hold the items after it, such as
+/// a channel with retention or a `PersistentLog` being replayed into one. If the input hasn't got
+/// that far yet, the node waits for it and skips the items before the position. If the items have
+/// already been trimmed from the input, this is reported and the node stops without sending
+/// anything, rather than carrying on from the wrong place.
+///
+/// A checkpoint which can't be saved is reported on the error channel, or standard error without
+/// one, and left incomplete while the node carries on.
+pub struct CheckpointSource<T: Clone, S> {
+    name: String,
+    rx: Receiver<T>,
+    tx: S,
+    every: u64,
+    state: Mutex<SourceState>,
+    store: Option<Arc<CheckpointStore>>,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<T: Clone, S> Debug for CheckpointSource<T, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<T, S> CheckpointSource<T, S>
+where
+    T: Clone,
+    S: ChannelSender<Item = Record<T>>,
+{
+    /// Add a barrier after every `every` items. Panics if it is 0.
+    pub fn new(name: String, rx: Receiver<T>, tx: S, every: u64) -> Self {
+        assert!(every > 0, "Barriers must be at least one item apart");
+        Self {
+            name,
+            rx,
+            tx,
+            every,
+            state: Mutex::new(SourceState {
+                restore_to: None,
+                next_id: 0,
+            }),
+            store: None,
+            errors: None,
+        }
+    }
+
+    /// Save snapshots to this store.
+    pub fn store(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SourceState> {
+        self.state.lock().expect("Thread was poisoned")
+    }
+
+    /// Move the input to `position`, waiting for it to get there if it hasn't yet.
+    fn seek(&self, position: u64) -> io::Result<()> {
+        let seek_error = |e| channel_error(e, "unable to seek input");
+        self.rx
+            .seek(Position::Offset(position))
+            .map_err(seek_error)?;
+        let mut at = self.rx.position().map_err(seek_error)?;
+        if at > position {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "input no longer holds position {}, the oldest it has is {}",
+                    position, at
+                ),
+            ));
+        }
+        // everything before the position was sent before the checkpoint was taken
+        while at < position {
+            if recv_or_end(&self.rx).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "input ended at {} before reaching position {}",
+                        at, position
+                    ),
+                ));
+            }
+            at += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<T, S> ComputeNode for CheckpointSource<T, S>
+where
+    T: Clone + Send,
+    S: ChannelSender<Item = Record<T>> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let restore_to = self.lock().restore_to.take();
+        if let Some(position) = restore_to {
+            if let Err(e) = self.seek(position) {
+                report_error(&self.errors, &self.name, &e);
+                self.tx.cork();
+                return;
+            }
+        }
+        let mut count = 0u64;
+        while let Some(v) = recv_or_end(&self.rx) {
+            if self.tx.send(Record::Item(v)).is_err() {
+                break;
+            }
+            count += 1;
+            if count % self.every != 0 {
+                continue;
+            }
+            let id = {
+                let mut state = self.lock();
+                state.next_id += 1;
+                state.next_id - 1
+            };
+            checkpoint(self, &self.store, &self.errors, id);
+            if self.tx.send(Record::Barrier(id)).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+impl<T, S> StatefulNode for CheckpointSource<T, S>
+where
+    T: Clone + Send,
+    S: ChannelSender<Item = Record<T>> + Send,
+{
+    /// The position of the input and the id of the next checkpoint.
+    fn snapshot(&self) -> io::Result<Vec<u8>> {
+        let position = self.rx.position().map_err(snapshot_error)?;
+        let mut snapshot = position.to_le_bytes().to_vec();
+        snapshot.extend_from_slice(&self.lock().next_id.to_le_bytes());
+        Ok(snapshot)
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        if snapshot.len() != 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "source snapshots are 16 bytes",
+            ));
+        }
+        let (position, next_id) = snapshot.split_at(8);
+        let mut state = self.lock();
+        state.restore_to = Some(u64::from_le_bytes(position.try_into().unwrap()));
+        state.next_id = u64::from_le_bytes(next_id.try_into().unwrap());
+        Ok(())
+    }
+}
+
+/// A `Scan` over a checkpointed stream whose accumulator is saved with every barrier it receives,
+/// using `codec` to turn it into bytes. Barriers are passed on once the state has been saved. The
+/// accumulator is kept between runs, so a restored node picks up where the snapshot left off.
+/// Failing to save the state is reported on the error channel, or standard error without one,
+/// and the barrier is still passed on.
+pub struct StatefulScan<R, S, A, F, C, T, O> {
+    name: String,
+    rx: R,
+    tx: S,
+    state: Mutex<A>,
+    codec: C,
+    f: F,
+    store: Option<Arc<CheckpointStore>>,
+    errors: Option<Sender<NodeError>>,
+    _items: PhantomData<fn(T) -> O>,
+}
+
+impl<R, S, A, F, C, T, O> Debug for StatefulScan<R, S, A, F, C, T, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, A, F, C, T, O> StatefulScan<R, S, A, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+    S: ChannelSender<Item = Record<O>>,
+    F: Fn(&mut A, T) -> Option<O>,
+    C: Codec<A>,
+{
+    /// Call `f` with the accumulator and each item, passing on what it returns until it returns
+    /// None.
+    pub fn new(name: String, rx: R, tx: S, init: A, codec: C, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            state: Mutex::new(init),
+            codec,
+            f,
+            store: None,
+            errors: None,
+            _items: PhantomData,
+        }
+    }
+
+    /// Save snapshots to this store.
+    pub fn store(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+}
+
+impl<R, S, A, F, C, T, O> ComputeNode for StatefulScan<R, S, A, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<O>> + Send,
+    A: Send,
+    F: Fn(&mut A, T) -> Option<O> + Send,
+    C: Codec<A>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(record) = recv_or_end(&self.rx) {
+            let out = match record {
+                Record::Item(v) => {
+                    let mut state = self.state.lock().expect("Thread was poisoned");
+                    match (self.f)(&mut state, v) {
+                        Some(out) => Record::Item(out),
+                        None => break,
+                    }
+                }
+                Record::Barrier(id) => {
+                    checkpoint(self, &self.store, &self.errors, id);
+                    Record::Barrier(id)
+                }
+            };
+            if self.tx.send(out).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+impl<R, S, A, F, C, T, O> StatefulNode for StatefulScan<R, S, A, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<O>> + Send,
+    A: Send,
+    F: Fn(&mut A, T) -> Option<O> + Send,
+    C: Codec<A>,
+{
+    fn snapshot(&self) -> io::Result<Vec<u8>> {
+        let mut snapshot = Vec::new();
+        let state = self.state.lock().expect("Thread was poisoned");
+        self.codec
+            .encode(&state, &mut snapshot)
+            .map_err(snapshot_error)?;
+        Ok(snapshot)
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        let state = self.codec.decode(snapshot).map_err(snapshot_error)?;
+        *self.state.lock().expect("Thread was poisoned") = state;
+        Ok(())
+    }
+}
+
+/// A `Fold` over a checkpointed stream whose accumulator is saved with every barrier it receives,
+/// using `codec` to turn it into bytes. Barriers are passed on once the state has been saved, and
+/// the accumulator is sent once the input is corked. As with `StatefulScan`, the accumulator is
+/// kept between runs and failing to save it is reported while the barrier is still passed on.
+pub struct StatefulFold<R, S, A, F, C, T> {
+    name: String,
+    rx: R,
+    tx: S,
+    /// Only empty while `f` has the accumulator.
+    state: Mutex<Option<A>>,
+    codec: C,
+    f: F,
+    store: Option<Arc<CheckpointStore>>,
+    errors: Option<Sender<NodeError>>,
+    _items: PhantomData<fn(T)>,
+}
+
+impl<R, S, A, F, C, T> Debug for StatefulFold<R, S, A, F, C, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, A, F, C, T> StatefulFold<R, S, A, F, C, T>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+    S: ChannelSender<Item = Record<A>>,
+    A: Clone,
+    F: Fn(A, T) -> A,
+    C: Codec<A>,
+{
+    pub fn new(name: String, rx: R, tx: S, init: A, codec: C, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            state: Mutex::new(Some(init)),
+            codec,
+            f,
+            store: None,
+            errors: None,
+            _items: PhantomData,
+        }
+    }
+
+    /// Save snapshots to this store.
+    pub fn store(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<A>> {
+        self.state.lock().expect("Thread was poisoned")
+    }
+}
+
+impl<R, S, A, F, C, T> ComputeNode for StatefulFold<R, S, A, F, C, T>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<A>> + Send,
+    A: Clone + Send,
+    F: Fn(A, T) -> A + Send,
+    C: Codec<A>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(record) = recv_or_end(&self.rx) {
+            if self.tx.is_corked() {
+                return;
+            }
+            match record {
+                Record::Item(v) => {
+                    let mut state = self.lock();
+                    let acc = state.take().expect("Accumulator went missing");
+                    *state = Some((self.f)(acc, v));
+                }
+                Record::Barrier(id) => {
+                    checkpoint(self, &self.store, &self.errors, id);
+                    if self.tx.send(Record::Barrier(id)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        let acc = self.lock().clone().expect("Accumulator went missing");
+        let _ = self.tx.send(Record::Item(acc));
+        self.tx.cork();
+    }
+}
+
+impl<R, S, A, F, C, T> StatefulNode for StatefulFold<R, S, A, F, C, T>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<A>> + Send,
+    A: Clone + Send,
+    F: Fn(A, T) -> A + Send,
+    C: Codec<A>,
+{
+    fn snapshot(&self) -> io::Result<Vec<u8>> {
+        let mut snapshot = Vec::new();
+        let state = self.lock();
+        let acc = state.as_ref().expect("Accumulator went missing");
+        self.codec
+            .encode(acc, &mut snapshot)
+            .map_err(snapshot_error)?;
+        Ok(snapshot)
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        let acc = self.codec.decode(snapshot).map_err(snapshot_error)?;
+        *self.lock() = Some(acc);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::{sync_channel, RawLeCodec};
+    use crate::nodes::test_util::{drain, input, temp_dir};
+
+    /// Run a source feeding a running total over 1 to 10, restoring both from `restore` first.
+    fn run_sum(store: &Arc<CheckpointStore>, restore: Option<u64>) -> Vec<Record<u64>> {
+        let (tx, rx) = sync_channel(16);
+        for i in 1..=10u64 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let (mid_tx, mid_rx) = sync_channel(32);
+        let (out_tx, out_rx) = sync_channel(32);
+        let source = CheckpointSource::new("source".into(), rx, mid_tx, 4).store(store.clone());
+        let sum = StatefulScan::new("sum".into(), mid_rx, out_tx, 0u64, RawLeCodec, |sum, v| {
+            *sum += v;
+            Some(*sum)
+        })
+        .store(store.clone());
+        if let Some(id) = restore {
+            source.restore_from(store, id).unwrap();
+            sum.restore_from(store, id).unwrap();
+        }
+        source.run();
+        sum.run();
+        drain(&out_rx)
+    }
+
+    #[test]
+    fn checkpoint_and_restore() {
+        let dir = temp_dir("checkpoint");
+        let store = Arc::new(CheckpointStore::open(&dir).unwrap());
+        let nodes = ["source", "sum"];
+        assert_eq!(store.latest(&nodes).unwrap(), None);
+
+        let out = run_sum(&store, None);
+        assert_eq!(
+            out[3..6],
+            [Record::Item(10), Record::Barrier(0), Record::Item(15)]
+        );
+        assert_eq!(out[9], Record::Barrier(1));
+        assert_eq!(out.len(), 12);
+        assert_eq!(store.latest(&nodes).unwrap(), Some(1));
+
+        // the restored graph skips the items before the barrier and carries on from their total
+        let out = run_sum(&store, Some(1));
+        assert_eq!(out, [Record::Item(45), Record::Item(55)]);
+
+        // a checkpoint only some of the nodes saved is not used
+        store.save(2, "source", &[0; 16]).unwrap();
+        assert_eq!(store.latest(&nodes).unwrap(), Some(1));
+        store.remove_before(1).unwrap();
+        assert_eq!(store.load(0, "sum").unwrap(), None);
+        assert!(store.load(1, "sum").unwrap().is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fold_checkpoint() {
+        let dir = temp_dir("fold");
+        let store = Arc::new(CheckpointStore::open(&dir).unwrap());
+        let records = [1, 2].map(Record::Item);
+        let rx = input(&[&records[..], &[Record::Barrier(0), Record::Item(3u64)]].concat());
+        let (tx, out_rx) = sync_channel(8);
+        let fold = StatefulFold::new("fold".into(), rx, tx, 0u64, RawLeCodec, |sum, v| sum + v)
+            .store(store.clone());
+        fold.run();
+        assert_eq!(drain(&out_rx), [Record::Barrier(0), Record::Item(6)]);
+
+        // a restored fold only sees what came after the barrier
+        let (tx, out_rx) = sync_channel(8);
+        let fold = StatefulFold::new(
+            "fold".into(),
+            input(&[Record::Item(3)]),
+            tx,
+            0u64,
+            RawLeCodec,
+            |sum, v| sum + v,
+        );
+        fold.restore_from(&store, 0).unwrap();
+        fold.run();
+        assert_eq!(drain(&out_rx), [Record::Item(6)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_errors() {
+        let dir = temp_dir("restore");
+        let store = CheckpointStore::open(&dir).unwrap();
+        let (tx, rx) = sync_channel::<Record<u32>>(4);
+        let scan = StatefulScan::new("scan".into(), rx, tx, 0u32, RawLeCodec, |_, v| Some(v));
+        let missing = scan.restore_from(&store, 3).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        let truncated = scan.restore(&[1, 2]).unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::InvalidData);
+
+        scan.restore(&7u32.to_le_bytes()).unwrap();
+        assert_eq!(scan.snapshot().unwrap(), 7u32.to_le_bytes());
+        assert_eq!(Record::Item(2).map(|v| v * 2).into_item(), Some(4),);
+        assert_eq!(Record::<u32>::Barrier(1).into_item(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn store_names() {
+        let dir = temp_dir("store-names");
+        let store = CheckpointStore::open(&dir).unwrap();
+        // names which only differ after a dot don't share a temporary file
+        store.save(0, "x.a", &[1]).unwrap();
+        store.save(0, "x.b", &[2]).unwrap();
+        assert_eq!(store.load(0, "x.a").unwrap(), Some(vec![1]));
+        assert_eq!(store.load(0, "x.b").unwrap(), Some(vec![2]));
+
+        for name in ["", "..", "../escape", "a/b"] {
+            let e = store.save(1, name, &[0]).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{:?}", name);
+            assert!(store.load(0, name).is_err());
+        }
+        assert!(!dir.parent().unwrap().join("escape").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn source_snapshot(position: u64) -> Vec<u8> {
+        let mut snapshot = position.to_le_bytes().to_vec();
+        snapshot.extend_from_slice(&0u64.to_le_bytes());
+        snapshot
+    }
+
+    #[test]
+    fn restore_position_not_in_input() {
+        // the items before the checkpoint have already been read and let go of
+        let (tx, rx) = sync_channel(4);
+        for i in 1..=3u32 {
+            tx.send(i).unwrap();
+            rx.recv().unwrap();
+        }
+        let (errors, errors_rx) = sync_channel(4);
+        let (out_tx, out_rx) = sync_channel(4);
+        let source = CheckpointSource::new("source".into(), rx, out_tx, 10).errors(errors);
+        source.restore(&source_snapshot(1)).unwrap();
+        source.run();
+        assert_eq!(
+            errors_rx.try_recv().unwrap().unwrap().kind,
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(out_rx.recv(), Err(ChannelError::IsCorked));
+        drop(tx);
+
+        // the input hasn't got to the checkpoint yet, so the source waits for it
+        let (tx, rx) = sync_channel(4);
+        let (out_tx, out_rx) = sync_channel(4);
+        let source = CheckpointSource::new("source".into(), rx, out_tx, 10);
+        source.restore(&source_snapshot(3)).unwrap();
+        let node = std::thread::spawn(move || source.run());
+        for i in 1..=5u32 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        node.join().unwrap();
+        assert_eq!(drain(&out_rx), [Record::Item(4), Record::Item(5)]);
+    }
+}
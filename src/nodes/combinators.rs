@@ -0,0 +1,572 @@
+//! Nodes which transform a single stream the way the iterator adapters of the same names do.
+//!
+//! Every node here runs until its input is corked or its output is, and corks its output when it
+//! finishes so the rest of the graph knows the stream has ended. Since corking affects every sender
+//! to the channel, each of these should have an output channel of its own.
+
+use std::fmt::{self, Debug, Formatter};
+
+use crate::mpmc::{ChannelReceiver, ChannelSender};
+
+use super::{recv_or_end, ComputeNode};
+
+macro_rules! impl_debug {
+    ($($node:ident),*) => {$(
+        impl<R, S, F> Debug for $node<R, S, F> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.name.fmt(f)
+            }
+        }
+    )*};
+}
+
+/// Sends `f(item)` for every item.
+#[derive(Clone)]
+pub struct Map<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    f: F,
+}
+
+impl<R, S, F> Map<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    F: Fn(R::Item) -> S::Item,
+{
+    pub fn new(name: String, rx: R, tx: S, f: F) -> Self {
+        Self { name, rx, tx, f }
+    }
+}
+
+impl<R, S, F> ComputeNode for Map<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    F: Fn(R::Item) -> S::Item + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if self.tx.send((self.f)(v)).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Only passes on the items `predicate` returns true for.
+#[derive(Clone)]
+pub struct Filter<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    predicate: F,
+}
+
+impl<R, S, F> Filter<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+    F: Fn(&R::Item) -> bool,
+{
+    pub fn new(name: String, rx: R, tx: S, predicate: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            predicate,
+        }
+    }
+}
+
+impl<R, S, F> ComputeNode for Filter<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+    F: Fn(&R::Item) -> bool + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if (self.predicate)(&v) && self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Sends the result of `f(item)` for every item it returns `Some` for.
+#[derive(Clone)]
+pub struct FilterMap<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    f: F,
+}
+
+impl<R, S, F> FilterMap<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    F: Fn(R::Item) -> Option<S::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, f: F) -> Self {
+        Self { name, rx, tx, f }
+    }
+}
+
+impl<R, S, F> ComputeNode for FilterMap<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    F: Fn(R::Item) -> Option<S::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if let Some(o) = (self.f)(v) {
+                if self.tx.send(o).is_err() {
+                    break;
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Sends everything `f(item)` yields for every item, in order.
+#[derive(Clone)]
+pub struct FlatMap<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    f: F,
+}
+
+impl<R, S, F, I> FlatMap<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    F: Fn(R::Item) -> I,
+    I: IntoIterator<Item = S::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, f: F) -> Self {
+        Self { name, rx, tx, f }
+    }
+}
+
+impl<R, S, F, I> ComputeNode for FlatMap<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    F: Fn(R::Item) -> I + Send,
+    I: IntoIterator<Item = S::Item>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        'input: while let Some(v) = recv_or_end(&self.rx) {
+            for o in (self.f)(v) {
+                if self.tx.send(o).is_err() {
+                    break 'input;
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Calls `f` with a reference to every item before passing it on unchanged, which is useful for
+/// logging or debugging what flows through part of a graph.
+#[derive(Clone)]
+pub struct Inspect<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    f: F,
+}
+
+impl<R, S, F> Inspect<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+    F: Fn(&R::Item),
+{
+    pub fn new(name: String, rx: R, tx: S, f: F) -> Self {
+        Self { name, rx, tx, f }
+    }
+}
+
+impl<R, S, F> ComputeNode for Inspect<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+    F: Fn(&R::Item) + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            (self.f)(&v);
+            if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Threads some state through the stream. `f` is given the state, starting from a copy of `init`
+/// every time the node is run, along with each item and returns what to send. Returning `None` ends
+/// the stream, like `Iterator::scan`.
+#[derive(Clone)]
+pub struct Scan<R, S, A, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    init: A,
+    f: F,
+}
+
+impl<R, S, A, F> Debug for Scan<R, S, A, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, A, F> Scan<R, S, A, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    A: Clone,
+    F: Fn(&mut A, R::Item) -> Option<S::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, init: A, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            init,
+            f,
+        }
+    }
+}
+
+impl<R, S, A, F> ComputeNode for Scan<R, S, A, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    A: Clone + Send,
+    F: Fn(&mut A, R::Item) -> Option<S::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut state = self.init.clone();
+        while let Some(v) = recv_or_end(&self.rx) {
+            let sent = match (self.f)(&mut state, v) {
+                Some(o) => self.tx.send(o).is_ok(),
+                None => false,
+            };
+            if !sent {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Combines every item into a single value with `f`, starting from a copy of `init`, and sends it
+/// once the input is corked. Nothing is sent if the output is corked first.
+#[derive(Clone)]
+pub struct Fold<R, S: ChannelSender, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    init: S::Item,
+    f: F,
+}
+
+impl<R, S: ChannelSender, F> Debug for Fold<R, S, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, F> Fold<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    F: Fn(S::Item, R::Item) -> S::Item,
+{
+    pub fn new(name: String, rx: R, tx: S, init: S::Item, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            init,
+            f,
+        }
+    }
+}
+
+impl<R, S, F> ComputeNode for Fold<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    S::Item: Send,
+    F: Fn(S::Item, R::Item) -> S::Item + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut acc = self.init.clone();
+        while let Some(v) = recv_or_end(&self.rx) {
+            if self.tx.is_corked() {
+                return;
+            }
+            acc = (self.f)(acc, v);
+        }
+        let _ = self.tx.send(acc);
+        self.tx.cork();
+    }
+}
+
+/// Passes on the first `n` items and then ends the stream without waiting for the rest.
+#[derive(Clone, Debug)]
+pub struct Take<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    n: usize,
+}
+
+impl<R, S> Take<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, n: usize) -> Self {
+        Self { name, rx, tx, n }
+    }
+}
+
+impl<R, S> ComputeNode for Take<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        for _ in 0..self.n {
+            let sent = match recv_or_end(&self.rx) {
+                Some(v) => self.tx.send(v).is_ok(),
+                None => false,
+            };
+            if !sent {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Discards the first `n` items and passes on everything after them.
+#[derive(Clone, Debug)]
+pub struct Skip<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    n: usize,
+}
+
+impl<R, S> Skip<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, n: usize) -> Self {
+        Self { name, rx, tx, n }
+    }
+}
+
+impl<R, S> ComputeNode for Skip<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut skipped = 0;
+        while let Some(v) = recv_or_end(&self.rx) {
+            if skipped < self.n {
+                skipped += 1;
+            } else if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Passes on items until `predicate` returns false for one, then ends the stream without passing
+/// that item on.
+#[derive(Clone)]
+pub struct TakeWhile<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    predicate: F,
+}
+
+impl<R, S, F> TakeWhile<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+    F: Fn(&R::Item) -> bool,
+{
+    pub fn new(name: String, rx: R, tx: S, predicate: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            predicate,
+        }
+    }
+}
+
+impl<R, S, F> ComputeNode for TakeWhile<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+    F: Fn(&R::Item) -> bool + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if !(self.predicate)(&v) || self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+impl_debug!(Map, Filter, FilterMap, FlatMap, Inspect, TakeWhile);
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, ChannelError};
+    use crate::nodes::test_util::{drain, input};
+
+    #[test]
+    fn map_filter_flat_map() {
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        Map::new("map".into(), rx, tx, |v: i32| v * 10).run();
+        assert_eq!(drain(&out), [10, 20, 30, 40]);
+
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        Filter::new("filter".into(), rx, tx, |v: &i32| v % 2 == 0).run();
+        assert_eq!(drain(&out), [2, 4]);
+
+        let rx = input(&["1", "x", "3"]);
+        let (tx, out) = sync_channel(8);
+        FilterMap::new("parse".into(), rx, tx, |v: &str| v.parse::<u8>().ok()).run();
+        assert_eq!(drain(&out), [1, 3]);
+
+        let rx = input(&[1, 2, 3]);
+        let (tx, out) = sync_channel(8);
+        FlatMap::new("repeat".into(), rx, tx, |v: usize| vec![v; v]).run();
+        assert_eq!(drain(&out), [1, 2, 2, 3, 3, 3]);
+
+        let seen = Mutex::new(Vec::new());
+        let rx = input(&[5, 6]);
+        let (tx, out) = sync_channel(8);
+        Inspect::new("inspect".into(), rx, tx, |v: &i32| {
+            seen.lock().unwrap().push(*v)
+        })
+        .run();
+        assert_eq!(drain(&out), [5, 6]);
+        assert_eq!(*seen.lock().unwrap(), [5, 6]);
+    }
+
+    #[test]
+    fn scan_and_fold() {
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        let scan = Scan::new("sum".into(), rx, tx, 0, |acc: &mut i32, v: i32| {
+            *acc += v;
+            Some(*acc).filter(|&s| s < 6)
+        });
+        scan.run();
+        assert_eq!(drain(&out), [1, 3]);
+
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        Fold::new("total".into(), rx, tx, 0, |acc: i32, v: i32| acc + v).run();
+        assert_eq!(drain(&out), [10]);
+
+        // an empty stream still produces the initial value
+        let rx = input::<i32>(&[]);
+        let (tx, out) = sync_channel(8);
+        Fold::new("empty".into(), rx, tx, 7, |acc, v| acc + v).run();
+        assert_eq!(drain(&out), [7]);
+    }
+
+    #[test]
+    fn take_skip_take_while() {
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        // the rest of the input is left unread
+        let unread = rx.clone();
+        Take::new("take".into(), rx, tx, 2).run();
+        assert_eq!(drain(&out), [1, 2]);
+        assert_eq!(unread.try_recv(), Ok(Some(1)));
+
+        let rx = input(&[1, 2, 3, 4]);
+        let (tx, out) = sync_channel(8);
+        Skip::new("skip".into(), rx, tx, 3).run();
+        assert_eq!(drain(&out), [4]);
+
+        let rx = input(&[1, 2, 5, 3]);
+        let (tx, out) = sync_channel(8);
+        TakeWhile::new("small".into(), rx, tx, |v: &i32| *v < 4).run();
+        assert_eq!(drain(&out), [1, 2]);
+        assert_eq!(out.recv(), Err(ChannelError::IsCorked));
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt::{self, Debug, Formatter};
+use std::thread;
+
+use crate::mpmc::{ChannelReceiver, ChannelSender};
+
+use super::checkpoint::{BarrierAlignment, Record};
+use super::{recv_or_end, ComputeNode};
+
+/// A group of inputs a `Zip` node can read from in lockstep. This is implemented for tuples of up
+/// to six receivers, which may all have different item types, and for a `Vec` of receivers of the
+/// same type, as well as for the same inputs of checkpointed streams wrapped in `AlignBarriers`.
+pub trait ZipInputs: Send {
+    type Item: Clone;
+
+    /// Receive one item from every input, in order, or None once any of them has been corked.
+    fn recv_all(&self) -> Option<Self::Item>;
+}
+
+/// Inputs of checkpointed streams for a `Zip` node which keeps barriers in place. Once a barrier
+/// is read from any input, every other input is read up to its next barrier, discarding the items
+/// before it which can no longer be made into a complete tuple, and a single barrier is sent.
+/// Barriers are expected to arrive in the same order on every input, as they do when all of them
+/// are fed from the same `CheckpointSource`.
+#[derive(Clone, Debug)]
+pub struct AlignBarriers<I>(pub I);
+
+/// Read from a checkpointed stream up to and including its next barrier, or None if it is corked
+/// first.
+fn skip_to_barrier<R, T>(rx: &R) -> Option<()>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+{
+    while recv_or_end(rx)?.barrier().is_none() {}
+    Some(())
+}
+
+macro_rules! impl_zip_inputs {
+    ($($r:ident $t:ident $i:tt),+) => {
+        impl<$($r: ChannelReceiver + Send),+> ZipInputs for ($($r,)+) {
+            type Item = ($($r::Item,)+);
+
+            fn recv_all(&self) -> Option<Self::Item> {
+                Some(($(recv_or_end(&self.$i)?,)+))
+            }
+        }
+
+        impl<$($r, $t),+> AlignBarriers<($($r,)+)>
+        where
+            $($r: ChannelReceiver<Item = Record<$t>>),+
+        {
+            /// Read every input other than `except` up to its next barrier.
+            fn skip_to_barrier(&self, except: usize) -> Option<()> {
+                $(
+                    if $i != except {
+                        skip_to_barrier(&self.0.$i)?;
+                    }
+                )+
+                Some(())
+            }
+        }
+
+        impl<$($r, $t),+> ZipInputs for AlignBarriers<($($r,)+)>
+        where
+            $($r: ChannelReceiver<Item = Record<$t>> + Send, $t: Clone),+
+        {
+            type Item = Record<($($t,)+)>;
+
+            fn recv_all(&self) -> Option<Self::Item> {
+                Some(Record::Item(($(
+                    match recv_or_end(&self.0.$i)? {
+                        Record::Item(v) => v,
+                        Record::Barrier(id) => {
+                            self.skip_to_barrier($i)?;
+                            return Some(Record::Barrier(id));
+                        }
+                    },
+                )+)))
+            }
+        }
+    };
+}
+
+impl_zip_inputs!(R0 T0 0, R1 T1 1);
+impl_zip_inputs!(R0 T0 0, R1 T1 1, R2 T2 2);
+impl_zip_inputs!(R0 T0 0, R1 T1 1, R2 T2 2, R3 T3 3);
+impl_zip_inputs!(R0 T0 0, R1 T1 1, R2 T2 2, R3 T3 3, R4 T4 4);
+impl_zip_inputs!(R0 T0 0, R1 T1 1, R2 T2 2, R3 T3 3, R4 T4 4, R5 T5 5);
+
+impl<R: ChannelReceiver + Send> ZipInputs for Vec<R> {
+    type Item = Vec<R::Item>;
+
+    /// No inputs are treated as having ended rather than producing empty items forever.
+    fn recv_all(&self) -> Option<Self::Item> {
+        if self.is_empty() {
+            return None;
+        }
+        self.iter().map(recv_or_end).collect()
+    }
+}
+
+impl<R, T> ZipInputs for AlignBarriers<Vec<R>>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    T: Clone,
+{
+    type Item = Record<Vec<T>>;
+
+    fn recv_all(&self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut items = Vec::with_capacity(self.0.len());
+        for (i, rx) in self.0.iter().enumerate() {
+            match recv_or_end(rx)? {
+                Record::Item(v) => items.push(v),
+                Record::Barrier(id) => {
+                    for (_, rx) in self.0.iter().enumerate().filter(|(j, _)| *j != i) {
+                        skip_to_barrier(rx)?;
+                    }
+                    return Some(Record::Barrier(id));
+                }
+            }
+        }
+        Some(Record::Item(items))
+    }
+}
+
+/// Combines one item from each of its inputs at a time into a tuple, or a `Vec` when given a `Vec`
+/// of inputs. Ends as soon as any input is corked, discarding anything received from the others
+/// for an incomplete tuple.
+#[derive(Clone)]
+pub struct Zip<I, S> {
+    name: String,
+    inputs: I,
+    tx: S,
+}
+
+impl<I, S> Debug for Zip<I, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<I, S> Zip<I, S>
+where
+    I: ZipInputs,
+    S: ChannelSender<Item = I::Item>,
+{
+    pub fn new(name: String, inputs: I, tx: S) -> Self {
+        Self { name, inputs, tx }
+    }
+}
+
+impl<I, S> ComputeNode for Zip<I, S>
+where
+    I: ZipInputs,
+    S: ChannelSender<Item = I::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = self.inputs.recv_all() {
+            if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Picks out the checkpoint barriers in a stream for the nodes which align them.
+type IsBarrier<T> = fn(&T) -> Option<u64>;
+
+/// Passes on items from all of its inputs as soon as they are available, so the order between
+/// inputs is not defined while the order of each input is kept. Ends once every input has been
+/// corked.
+///
+/// Every input is read on a thread of its own while the node runs.
+#[derive(Clone)]
+pub struct Merge<R: ChannelReceiver, S> {
+    name: String,
+    inputs: Vec<R>,
+    tx: S,
+    /// Picks out checkpoint barriers when they are being aligned.
+    barrier: Option<IsBarrier<R::Item>>,
+}
+
+impl<R: ChannelReceiver, S> Debug for Merge<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S> Merge<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, inputs: Vec<R>, tx: S) -> Self {
+        Self {
+            name,
+            inputs,
+            tx,
+            barrier: None,
+        }
+    }
+}
+
+impl<R, S, T> Merge<R, S>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+    S: ChannelSender<Item = R::Item>,
+{
+    /// Keep checkpoint barriers in place. An input which reaches a barrier isn't read from again
+    /// until every other open input has reached it too, and the barrier is then passed on once, so
+    /// everything before it on any input is sent ahead of it and nothing after it is. Barriers are
+    /// expected to arrive in the same order on every input, as they do when all of them are fed
+    /// from the same `CheckpointSource`.
+    pub fn align_barriers(mut self) -> Self {
+        self.barrier = Some(Record::barrier);
+        self
+    }
+}
+
+impl<R, S> ComputeNode for Merge<R, S>
+where
+    R: ChannelReceiver + Send + Sync,
+    R::Item: Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let alignment = BarrierAlignment::new(self.inputs.len());
+        let (alignment, barrier) = (&alignment, self.barrier);
+        thread::scope(|scope| {
+            for rx in &self.inputs {
+                let tx = self.tx.clone();
+                scope.spawn(move || {
+                    let pass_on = |b| {
+                        let _ = tx.send(b);
+                    };
+                    while let Some(v) = recv_or_end(rx) {
+                        if barrier.is_some_and(|barrier| barrier(&v).is_some()) {
+                            alignment.arrive(v, pass_on);
+                        } else if tx.send(v).is_err() {
+                            break;
+                        }
+                    }
+                    alignment.end(pass_on);
+                });
+            }
+        });
+        self.tx.cork();
+    }
+}
+
+/// Takes one item from each of its inputs in turn. Inputs which have been corked are skipped, and
+/// the node ends once all of them have been. A slow input holds up the others, so use `Merge`
+/// instead if the inputs do not need to be interleaved evenly.
+#[derive(Clone)]
+pub struct RoundRobin<R: ChannelReceiver, S> {
+    name: String,
+    inputs: Vec<R>,
+    tx: S,
+    /// Picks out checkpoint barriers when they are being aligned.
+    barrier: Option<IsBarrier<R::Item>>,
+}
+
+impl<R: ChannelReceiver, S> Debug for RoundRobin<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S> RoundRobin<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, inputs: Vec<R>, tx: S) -> Self {
+        Self {
+            name,
+            inputs,
+            tx,
+            barrier: None,
+        }
+    }
+}
+
+impl<R, S, T> RoundRobin<R, S>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+    S: ChannelSender<Item = R::Item>,
+{
+    /// Keep checkpoint barriers in place as `Merge::align_barriers` does. Inputs waiting at a
+    /// barrier are skipped until every open input has reached it.
+    pub fn align_barriers(mut self) -> Self {
+        self.barrier = Some(Record::barrier);
+        self
+    }
+}
+
+impl<R, S> ComputeNode for RoundRobin<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut open: Vec<&R> = self.inputs.iter().collect();
+        // which of the open inputs are waiting at `barrier`
+        let mut waiting = vec![false; open.len()];
+        let mut barrier = None;
+        'input: while !open.is_empty() {
+            let mut i = 0;
+            while i < open.len() {
+                if waiting[i] {
+                    i += 1;
+                    continue;
+                }
+                match recv_or_end(open[i]) {
+                    Some(v) => {
+                        if self.barrier.is_some_and(|barrier| barrier(&v).is_some()) {
+                            barrier.get_or_insert(v);
+                            waiting[i] = true;
+                        } else if self.tx.send(v).is_err() {
+                            break 'input;
+                        }
+                        i += 1;
+                    }
+                    None => {
+                        open.remove(i);
+                        waiting.remove(i);
+                    }
+                }
+                if !waiting.is_empty() && waiting.iter().all(|w| *w) {
+                    waiting.iter_mut().for_each(|w| *w = false);
+                    if let Some(b) = barrier.take() {
+                        if self.tx.send(b).is_err() {
+                            break 'input;
+                        }
+                    }
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::{sync_channel, Receiver};
+    use crate::nodes::test_util::{drain, input};
+
+    #[test]
+    fn zip_tuples_and_vecs() {
+        let (tx, rx) = sync_channel(8);
+        let inputs = (input(&[1, 2, 3]), input(&["a", "b"]), input(&[0.5]));
+        Zip::new("zip".into(), inputs, tx).run();
+        assert_eq!(drain(&rx), [(1, "a", 0.5)]);
+
+        let (tx, rx) = sync_channel(8);
+        let inputs = vec![input(&[1, 2]), input(&[3, 4]), input(&[5, 6, 7])];
+        Zip::new("zip".into(), inputs, tx).run();
+        assert_eq!(drain(&rx), [vec![1, 3, 5], vec![2, 4, 6]]);
+    }
+
+    #[test]
+    fn merge_everything() {
+        let (tx, rx) = sync_channel(16);
+        let inputs = vec![input(&[1, 2, 3]), input(&[]), input(&[4, 5])];
+        Merge::new("merge".into(), inputs, tx).run();
+        let mut out = drain(&rx);
+        out.sort_unstable();
+        assert_eq!(out, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn round_robin_skips_ended_inputs() {
+        let (tx, rx) = sync_channel(16);
+        let inputs = vec![input(&[1, 4, 6]), input(&[2]), input(&[3, 5])];
+        RoundRobin::new("round-robin".into(), inputs, tx).run();
+        assert_eq!(drain(&rx), [1, 2, 3, 4, 5, 6]);
+    }
+
+    fn records(items: &[Option<u32>]) -> Receiver<Record<u32>> {
+        let records: Vec<_> = items
+            .iter()
+            .map(|v| v.map_or(Record::Barrier(0), Record::Item))
+            .collect();
+        input(&records)
+    }
+
+    #[test]
+    fn align_barriers() {
+        let (tx, rx) = sync_channel(8);
+        let inputs = (
+            records(&[Some(1), Some(2), None, Some(3)]),
+            records(&[Some(4), None]),
+        );
+        Zip::new("zip".into(), AlignBarriers(inputs), tx).run();
+        // 2 is dropped as it was never paired up before the barrier
+        assert_eq!(drain(&rx), [Record::Item((1, 4)), Record::Barrier(0)]);
+
+        let (tx, rx) = sync_channel(8);
+        let inputs = vec![
+            records(&[Some(1), None, Some(2)]),
+            records(&[None, Some(3)]),
+        ];
+        Zip::new("zip".into(), AlignBarriers(inputs), tx).run();
+        assert_eq!(drain(&rx), [Record::Barrier(0), Record::Item(vec![2, 3])]);
+
+        let (tx, rx) = sync_channel(16);
+        let inputs = vec![
+            records(&[Some(1), Some(2), None, Some(3)]),
+            records(&[Some(4), None, Some(5)]),
+            records(&[]),
+        ];
+        Merge::new("merge".into(), inputs, tx)
+            .align_barriers()
+            .run();
+        let out = drain(&rx);
+        assert_eq!(out.len(), 6);
+        // everything from before the barrier is sent ahead of it
+        let mut before = out[..3].to_vec();
+        before.sort_unstable_by_key(|r| r.clone().into_item());
+        assert_eq!(before, [1, 2, 4].map(Record::Item));
+        assert_eq!(out[3], Record::Barrier(0));
+
+        let (tx, rx) = sync_channel(16);
+        let inputs = vec![
+            records(&[Some(1), None, Some(3)]),
+            records(&[Some(4), Some(5), None, Some(6)]),
+        ];
+        RoundRobin::new("round-robin".into(), inputs, tx)
+            .align_barriers()
+            .run();
+        let expected = [Some(1), Some(4), Some(5), None, Some(3), Some(6)];
+        assert_eq!(drain(&rx), drain(&records(&expected)));
+    }
+}
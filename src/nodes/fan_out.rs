@@ -0,0 +1,332 @@
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+
+use crate::mpmc::{partition_of, ChannelReceiver, ChannelSender};
+
+use super::{recv_or_end, ComputeNode};
+
+/// Function which picks the output an item is sent to.
+type RouteFn<T> = Box<dyn Fn(&T) -> usize + Send>;
+
+/// Sends each item to one of its outputs based on its content. Outputs which have been corked
+/// miss out on the items routed to them while the rest carry on, and the node ends once its input
+/// or every output has been corked.
+pub struct Router<R: ChannelReceiver, S> {
+    name: String,
+    rx: R,
+    outputs: Vec<S>,
+    route: RouteFn<R::Item>,
+}
+
+impl<R: ChannelReceiver, S> Debug for Router<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S> Router<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    /// Send each item to the output at the index `route` returns for it. Items given an index past
+    /// the last output are discarded.
+    pub fn new<F>(name: String, rx: R, outputs: Vec<S>, route: F) -> Self
+    where
+        F: Fn(&R::Item) -> usize + Send + 'static,
+    {
+        Self {
+            name,
+            rx,
+            outputs,
+            route: Box::new(route),
+        }
+    }
+
+    /// Send items `predicate` returns true for to `matched` and everything else to `rest`.
+    pub fn split<F>(name: String, rx: R, matched: S, rest: S, predicate: F) -> Self
+    where
+        F: Fn(&R::Item) -> bool + Send + 'static,
+    {
+        Self::new(name, rx, vec![matched, rest], move |v| {
+            if predicate(v) {
+                0
+            } else {
+                1
+            }
+        })
+    }
+
+    /// Send items with the same key to the same output by hashing the key, the same way a
+    /// `PartitionedSender` does.
+    pub fn by_key<K, F>(name: String, rx: R, outputs: Vec<S>, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&R::Item) -> K + Send + 'static,
+    {
+        let n = outputs.len().max(1);
+        Self::new(name, rx, outputs, move |v| partition_of(&key(v), n))
+    }
+}
+
+impl<R, S> ComputeNode for Router<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if let Some(tx) = self.outputs.get((self.route)(&v)) {
+                // a corked output only means nobody wants this kind of item anymore
+                let _ = tx.send(v);
+            }
+            if self.outputs.iter().all(|tx| tx.is_corked()) {
+                break;
+            }
+        }
+        for tx in &self.outputs {
+            tx.cork();
+        }
+    }
+}
+
+/// A group of outputs an `Unzip` node splits tuples between. This is implemented for tuples of up
+/// to six senders, one for each field of the tuple.
+pub trait UnzipOutputs: Send {
+    type Item;
+
+    /// Send every field to its output, skipping outputs which have been corked. Returns false once
+    /// every output has been corked.
+    fn send_all(&self, v: Self::Item) -> bool;
+
+    fn cork_all(&self);
+}
+
+macro_rules! impl_unzip_outputs {
+    ($($s:ident $v:ident $i:tt),+) => {
+        impl<$($s: ChannelSender + Send),+> UnzipOutputs for ($($s,)+) {
+            type Item = ($($s::Item,)+);
+
+            fn send_all(&self, v: Self::Item) -> bool {
+                let ($($v,)+) = v;
+                let mut open = false;
+                $(
+                    if !self.$i.is_corked() {
+                        open |= self.$i.send($v).is_ok();
+                    }
+                )+
+                open
+            }
+
+            fn cork_all(&self) {
+                $(self.$i.cork();)+
+            }
+        }
+    };
+}
+
+impl_unzip_outputs!(S0 v0 0, S1 v1 1);
+impl_unzip_outputs!(S0 v0 0, S1 v1 1, S2 v2 2);
+impl_unzip_outputs!(S0 v0 0, S1 v1 1, S2 v2 2, S3 v3 3);
+impl_unzip_outputs!(S0 v0 0, S1 v1 1, S2 v2 2, S3 v3 3, S4 v4 4);
+impl_unzip_outputs!(S0 v0 0, S1 v1 1, S2 v2 2, S3 v3 3, S4 v4 4, S5 v5 5);
+
+/// Splits a stream of tuples into one stream for each field. Outputs which have been corked are
+/// skipped, and the node ends once its input or every output has been corked.
+#[derive(Clone)]
+pub struct Unzip<R, O> {
+    name: String,
+    rx: R,
+    outputs: O,
+}
+
+impl<R, O> Debug for Unzip<R, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, O> Unzip<R, O>
+where
+    R: ChannelReceiver,
+    O: UnzipOutputs<Item = R::Item>,
+{
+    pub fn new(name: String, rx: R, outputs: O) -> Self {
+        Self { name, rx, outputs }
+    }
+}
+
+impl<R, O> ComputeNode for Unzip<R, O>
+where
+    R: ChannelReceiver + Send,
+    O: UnzipOutputs<Item = R::Item>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            if !self.outputs.send_all(v) {
+                break;
+            }
+        }
+        self.outputs.cork_all();
+    }
+}
+
+/// What a `Tee` does when one of its outputs is full.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum TeeMode {
+    /// Wait for room in every output, so the slowest output sets the pace for all of them.
+    #[default]
+    Block,
+    /// Skip outputs without room, so a slow output misses items instead of holding up the rest.
+    DropWhenFull,
+}
+
+/// Copies every item to each of its outputs. Unlike cloning a `Receiver`, the outputs are separate
+/// channels with their own bounds and policies. Outputs which have been corked are skipped, and the
+/// node ends once its input or every output has been corked.
+#[derive(Clone)]
+pub struct Tee<R, S> {
+    name: String,
+    rx: R,
+    outputs: Vec<S>,
+    mode: TeeMode,
+}
+
+impl<R, S> Debug for Tee<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S> Tee<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, rx: R, outputs: Vec<S>) -> Self {
+        Self {
+            name,
+            rx,
+            outputs,
+            mode: TeeMode::default(),
+        }
+    }
+
+    /// Set what to do when an output is full.
+    pub fn mode(mut self, mode: TeeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl<R, S> ComputeNode for Tee<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            let mut open = false;
+            for tx in self.outputs.iter().filter(|tx| !tx.is_corked()) {
+                let sent = match self.mode {
+                    TeeMode::Block => tx.send(v.clone()),
+                    TeeMode::DropWhenFull => tx.try_send(v.clone()).map(|_| ()),
+                };
+                open |= sent.is_ok();
+            }
+            if !open {
+                break;
+            }
+        }
+        for tx in &self.outputs {
+            tx.cork();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::sync_channel;
+    use crate::nodes::test_util::{drain, input};
+
+    #[test]
+    fn route_by_index_and_predicate() {
+        let (small_tx, small_rx) = sync_channel(8);
+        let (big_tx, big_rx) = sync_channel(8);
+        // anything from 100 up goes nowhere
+        let router = Router::new(
+            "router".into(),
+            input(&[1, 50, 200, 3]),
+            vec![small_tx, big_tx],
+            |v: &i32| (*v / 50) as usize,
+        );
+        router.run();
+        assert_eq!(drain(&small_rx), [1, 3]);
+        assert_eq!(drain(&big_rx), [50]);
+
+        let (even_tx, even_rx) = sync_channel(8);
+        let (odd_tx, odd_rx) = sync_channel(8);
+        let split = |v: &i32| v % 2 == 0;
+        Router::split("split".into(), input(&[1, 2, 3, 4]), even_tx, odd_tx, split).run();
+        assert_eq!(drain(&even_rx), [2, 4]);
+        assert_eq!(drain(&odd_rx), [1, 3]);
+    }
+
+    #[test]
+    fn route_by_key() {
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..3).map(|_| sync_channel(16)).unzip();
+        let items: Vec<_> = (0..12).map(|i| (i % 4, i)).collect();
+        Router::by_key("by-key".into(), input(&items), txs, |v: &(i32, i32)| v.0).run();
+        let mut total = 0;
+        let mut owner = std::collections::HashMap::new();
+        for (i, rx) in rxs.iter().enumerate() {
+            for (key, _) in drain(rx) {
+                assert_eq!(*owner.entry(key).or_insert(i), i);
+                total += 1;
+            }
+        }
+        assert_eq!(total, 12);
+    }
+
+    #[test]
+    fn unzip_tuples() {
+        let (a_tx, a_rx) = sync_channel(8);
+        let (b_tx, b_rx) = sync_channel(8);
+        let items = [(1, 'a'), (2, 'b')];
+        Unzip::new("unzip".into(), input(&items), (a_tx, b_tx)).run();
+        assert_eq!(drain(&a_rx), [1, 2]);
+        assert_eq!(drain(&b_rx), ['a', 'b']);
+    }
+
+    #[test]
+    fn tee_modes() {
+        let (fast_tx, fast_rx) = sync_channel(8);
+        let (slow_tx, slow_rx) = sync_channel(2);
+        let tee = Tee::new("tee".into(), input(&[1, 2, 3, 4]), vec![fast_tx, slow_tx])
+            .mode(TeeMode::DropWhenFull);
+        tee.run();
+        assert_eq!(drain(&fast_rx), [1, 2, 3, 4]);
+        assert_eq!(drain(&slow_rx), [1, 2]);
+
+        // a corked output doesn't stop the others
+        let (a_tx, a_rx) = sync_channel(8);
+        let (b_tx, b_rx) = sync_channel::<i32>(8);
+        b_tx.cork();
+        drop(b_rx);
+        Tee::new("tee".into(), input(&[1, 2]), vec![a_tx, b_tx]).run();
+        assert_eq!(drain(&a_rx), [1, 2]);
+    }
+}
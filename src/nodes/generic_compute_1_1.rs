@@ -9,6 +9,7 @@ use super::ComputeNode;
 /// of generic compute nodes that need only take a function and the appropriate channel connections
 /// and then can handle the rest of the boilerplate.
 #[derive(Clone)]
+#[allow(non_camel_case_types)]
 pub struct GenericComputeNode_1_1<I1, O1, R1, S1, F> {
     /// f will always be called with at least one `Some` value and will only start passing `None`
     /// values once that input is exhausted.
@@ -32,7 +33,7 @@ where
     O1: Clone + Send,
     S1: ChannelSender<Item = O1> + Send,
     R1: ChannelReceiver<Item = I1> + Send,
-    F: Fn(Option<I1>) -> (Option<O1>) + Send,
+    F: Fn(Option<I1>) -> Option<O1> + Send,
 {
     fn name(&self) -> &str {
         &self.name
@@ -44,14 +45,15 @@ where
                 Ok(i1) => Some(i1),
                 Err(ChannelError::IsCorked) => None,
                 Err(ChannelError::Poisoned) => panic!("Thread was poisoned"),
+                Err(e) => panic!("Unable to receive: {:?}", e),
             };
             if i1.is_none() {
                 // all inputs have been exhausted
                 break;
             }
-            let (o1) = (self.f)(i1);
+            let o1 = (self.f)(i1);
             if let Some(o1) = o1 {
-                self.tx1.send(o1);
+                let _ = self.tx1.send(o1);
             }
         }
     }
@@ -63,18 +65,18 @@ where
     O1: Clone,
     S1: ChannelSender<Item = O1>,
     R1: ChannelReceiver<Item = I1>,
-    F: Fn(Option<I1>) -> (Option<O1>),
+    F: Fn(Option<I1>) -> Option<O1>,
 {
-    pub fn new(name: String, rx: (R1), tx: (S1), f: F) -> Self {
-        let (rx1) = rx;
-        let (tx1) = tx;
+    pub fn new(name: String, rx: R1, tx: S1, f: F) -> Self {
+        let rx1 = rx;
+        let tx1 = tx;
         Self {
             f,
             tx1,
             rx1,
             name,
-            _phantom_i: PhantomData::default(),
-            _phantom_o: PhantomData::default(),
+            _phantom_i: PhantomData,
+            _phantom_o: PhantomData,
         }
     }
 }
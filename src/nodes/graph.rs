@@ -0,0 +1,62 @@
+use std::thread::{self, JoinHandle};
+
+use crate::mpmc::Receiver;
+
+use super::ComputeNode;
+
+/// Run a node on a thread of its own, named after the node.
+pub fn spawn<N: ComputeNode + 'static>(node: N) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name(node.name().to_string())
+        .spawn(move || node.run())
+        .expect("Unable to spawn node thread")
+}
+
+/// Start one worker for each partition of a `PartitionedSender`, so every key is processed by
+/// exactly one worker. `make_node` is given the index of the partition and its receiver and
+/// returns the worker for it, typically the same node each time with a different input.
+pub fn spawn_partitioned<T, N, F>(receivers: Vec<Receiver<T>>, make_node: F) -> Vec<JoinHandle<()>>
+where
+    T: Clone,
+    N: ComputeNode + 'static,
+    F: Fn(usize, Receiver<T>) -> N,
+{
+    receivers
+        .into_iter()
+        .enumerate()
+        .map(|(i, rx)| spawn(make_node(i, rx)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::{partitioned_channel, sync_channel, ChannelReceiver, ChannelSender};
+    use crate::nodes::GenericComputeNode_1_1;
+
+    #[test]
+    fn one_worker_per_partition() {
+        let (tx, rxs) = partitioned_channel(4, 8, |v: &(u8, u32)| v.0);
+        let (out_tx, out_rx) = sync_channel(100);
+        let workers = spawn_partitioned(rxs, |i, rx| {
+            // tag everything with the partition which processed it
+            GenericComputeNode_1_1::new(format!("worker-{}", i), rx, out_tx.clone(), move |v| {
+                v.map(|(key, _)| (key, i))
+            })
+        });
+        drop(out_tx);
+        for i in 0..100 {
+            tx.send(((i % 10) as u8, i)).unwrap();
+        }
+        drop(tx);
+
+        let mut seen = std::collections::HashMap::new();
+        while let Ok((key, worker)) = out_rx.recv() {
+            assert_eq!(*seen.entry(key).or_insert(worker), worker);
+        }
+        assert_eq!(seen.len(), 10);
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,1090 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{
+    sync_channel, ChannelError, ChannelReceiver, ChannelSender, Codec, Receiver, Sender,
+};
+
+use super::checkpoint::{
+    checkpoint, BarrierAlignment, CheckpointStore, Record, SnapshotReader, SnapshotWriter,
+    StatefulNode,
+};
+use super::{recv_or_end, recv_until, ComputeNode, Input, NodeError};
+
+/// How many items from either input may be waiting to be joined at a time.
+const EVENT_BOUND: usize = 64;
+
+/// How long unmatched items are kept around by a `Join` node in case a match for them arrives.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum JoinWindow {
+    /// Keep items for this long after they were received.
+    Time(Duration),
+    /// Keep the latest `n` items from each input for every key.
+    Count(usize),
+}
+
+impl Default for JoinWindow {
+    /// Only the latest item for every key, which joins a stream against the current value of
+    /// another like a table lookup.
+    fn default() -> Self {
+        JoinWindow::Count(1)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum JoinMode {
+    Inner,
+    LeftOuter,
+}
+
+/// Builds the output of a join from a left item and the right item it matched, if any.
+type Combine<L, R, O> = fn(L, Option<R>) -> O;
+
+#[derive(Clone)]
+enum Event<L, R> {
+    Left(L),
+    Right(R),
+    LeftEnd,
+    RightEnd,
+    /// Both inputs of a `StatefulJoin` have reached this barrier.
+    Barrier(u64),
+}
+
+struct Held<T> {
+    received: Instant,
+    /// When this was held relative to the other items from the same input, set by `Side::hold`.
+    seq: u64,
+    value: T,
+    /// Whether this has been part of a pair yet.
+    matched: bool,
+}
+
+/// The items held for one key, oldest first.
+struct Keyed<T> {
+    items: VecDeque<Held<T>>,
+    /// Sequence number of the latest item held for this key.
+    latest: u64,
+}
+
+/// Items held from one input of a join.
+struct Side<K, T> {
+    keys: HashMap<K, Keyed<T>>,
+    /// The keys items were held for, in the order they were held, so the items which expire first
+    /// and the keys which were held least recently can be found without looking through every key.
+    /// This is only kept for time windows and for count windows with a key limit, and entries
+    /// which no longer refer to a held item are skipped.
+    order: VecDeque<(u64, Instant, K)>,
+    next_seq: u64,
+}
+
+impl<K: Eq + Hash + Clone, T> Side<K, T> {
+    fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+        }
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut VecDeque<Held<T>>> {
+        self.keys.get_mut(key).map(|k| &mut k.items)
+    }
+
+    /// Hold an item for a key, letting go of whatever the window and key limit no longer have room
+    /// for.
+    fn hold<F>(
+        &mut self,
+        key: K,
+        mut held: Held<T>,
+        window: JoinWindow,
+        max_keys: Option<usize>,
+        mut release: F,
+    ) -> Result<(), ChannelError>
+    where
+        F: FnMut(Held<T>) -> Result<(), ChannelError>,
+    {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        held.seq = seq;
+        let received = held.received;
+        let keyed = self.keys.entry(key.clone()).or_insert_with(|| Keyed {
+            items: VecDeque::new(),
+            latest: seq,
+        });
+        keyed.items.push_back(held);
+        keyed.latest = seq;
+        let n = match window {
+            JoinWindow::Time(_) => {
+                self.order.push_back((seq, received, key));
+                return Ok(());
+            }
+            JoinWindow::Count(n) => n,
+        };
+        while keyed.items.len() > n {
+            release(keyed.items.pop_front().unwrap())?;
+        }
+        let max_keys = match max_keys {
+            Some(max_keys) => max_keys,
+            None => return Ok(()),
+        };
+        self.order.push_back((seq, received, key));
+        while self.keys.len() > max_keys {
+            let (seq, _, key) = match self.order.pop_front() {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if self.keys.get(&key).is_some_and(|k| k.latest == seq) {
+                for held in self.keys.remove(&key).unwrap().items {
+                    release(held)?;
+                }
+            }
+        }
+        // keys which are held over and over leave a trail of entries behind
+        if self.order.len() > 2 * self.keys.len() {
+            let keys = &self.keys;
+            self.order
+                .retain(|(seq, _, key)| keys.get(key).is_some_and(|k| k.latest == *seq));
+        }
+        Ok(())
+    }
+
+    /// Let go of everything in a time window which has been held for longer than it.
+    fn expire<F>(
+        &mut self,
+        window: Duration,
+        now: Instant,
+        mut release: F,
+    ) -> Result<(), ChannelError>
+    where
+        F: FnMut(Held<T>) -> Result<(), ChannelError>,
+    {
+        while let Some(&(_, received, _)) = self.order.front() {
+            if received + window > now {
+                break;
+            }
+            let (_, _, key) = self.order.pop_front().unwrap();
+            if let Some(keyed) = self.keys.get_mut(&key) {
+                while keyed.items.front().is_some_and(|h| h.received <= received) {
+                    release(keyed.items.pop_front().unwrap())?;
+                }
+                if keyed.items.is_empty() {
+                    self.keys.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// When the oldest item in a time window was received.
+    fn oldest(&self) -> Option<Instant> {
+        self.order.front().map(|&(_, received, _)| received)
+    }
+
+    /// Let go of everything.
+    fn clear<F>(&mut self, mut release: F) -> Result<(), ChannelError>
+    where
+        F: FnMut(Held<T>) -> Result<(), ChannelError>,
+    {
+        self.order.clear();
+        for (_, keyed) in self.keys.drain() {
+            for held in keyed.items {
+                release(held)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How a join pairs items up and how long it holds on to them, shared by `Join` and
+/// `StatefulJoin`.
+#[derive(Clone, Copy)]
+struct JoinRules {
+    window: JoinWindow,
+    max_keys: Option<usize>,
+    mode: JoinMode,
+}
+
+impl JoinRules {
+    fn new(mode: JoinMode) -> Self {
+        Self {
+            window: JoinWindow::default(),
+            max_keys: None,
+            mode,
+        }
+    }
+
+    fn window(mut self, window: JoinWindow) -> Self {
+        if let JoinWindow::Count(n) = window {
+            assert!(n > 0, "Join window must hold at least one item");
+        }
+        self.window = window;
+        self
+    }
+
+    fn max_keys(mut self, keys: usize) -> Self {
+        assert!(keys > 0, "Join must hold at least one key");
+        self.max_keys = Some(keys);
+        self
+    }
+}
+
+struct JoinState<K, L, R> {
+    left: Side<K, L>,
+    right: Side<K, R>,
+}
+
+impl<K, L, R> JoinState<K, L, R>
+where
+    K: Eq + Hash + Clone,
+    L: Clone,
+    R: Clone,
+{
+    fn new() -> Self {
+        Self {
+            left: Side::new(),
+            right: Side::new(),
+        }
+    }
+
+    /// Let go of a left item, sending it on its own if this is an outer join and it never matched.
+    fn release<P>(rules: &JoinRules, held: Held<L>, send: &mut P) -> Result<(), ChannelError>
+    where
+        P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    {
+        if rules.mode == JoinMode::LeftOuter && !held.matched {
+            send(held.value, None)?;
+        }
+        Ok(())
+    }
+
+    fn on_left<P>(
+        &mut self,
+        rules: &JoinRules,
+        key: K,
+        l: L,
+        right_open: bool,
+        send: &mut P,
+    ) -> Result<(), ChannelError>
+    where
+        P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    {
+        let mut matched = false;
+        if let Some(held) = self.right.get_mut(&key) {
+            for r in held.iter_mut() {
+                send(l.clone(), Some(r.value.clone()))?;
+                r.matched = true;
+                matched = true;
+            }
+        }
+        let held = Held {
+            received: Instant::now(),
+            seq: 0,
+            value: l,
+            matched,
+        };
+        if !right_open {
+            // nothing else can pair with it
+            return Self::release(rules, held, send);
+        }
+        self.left
+            .hold(key, held, rules.window, rules.max_keys, |h| {
+                Self::release(rules, h, send)
+            })
+    }
+
+    fn on_right<P>(
+        &mut self,
+        rules: &JoinRules,
+        key: K,
+        r: R,
+        left_open: bool,
+        send: &mut P,
+    ) -> Result<(), ChannelError>
+    where
+        P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    {
+        let mut matched = false;
+        if let Some(held) = self.left.get_mut(&key) {
+            for l in held.iter_mut() {
+                send(l.value.clone(), Some(r.clone()))?;
+                l.matched = true;
+                matched = true;
+            }
+        }
+        if !left_open {
+            return Ok(());
+        }
+        let held = Held {
+            received: Instant::now(),
+            seq: 0,
+            value: r,
+            matched,
+        };
+        self.right
+            .hold(key, held, rules.window, rules.max_keys, |_| Ok(()))
+    }
+
+    /// Let go of everything which has been held for longer than a time window.
+    fn expire<P>(
+        &mut self,
+        rules: &JoinRules,
+        now: Instant,
+        send: &mut P,
+    ) -> Result<(), ChannelError>
+    where
+        P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    {
+        let window = match rules.window {
+            JoinWindow::Time(window) => window,
+            JoinWindow::Count(_) => return Ok(()),
+        };
+        self.left
+            .expire(window, now, |h| Self::release(rules, h, send))?;
+        self.right.expire(window, now, |_| Ok(()))
+    }
+
+    /// When the next held item will need to be let go, if ever.
+    fn next_expiry(&self, rules: &JoinRules) -> Option<Instant> {
+        let window = match rules.window {
+            JoinWindow::Time(window) => window,
+            JoinWindow::Count(_) => return None,
+        };
+        let left = self.left.oldest();
+        let right = self.right.oldest();
+        left.into_iter().chain(right).min().map(|t| t + window)
+    }
+
+    fn release_left<P>(&mut self, rules: &JoinRules, send: &mut P) -> Result<(), ChannelError>
+    where
+        P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    {
+        self.left.clear(|h| Self::release(rules, h, send))
+    }
+
+    /// Encode everything being held, for `StatefulJoin`.
+    fn save<C>(&self, codec: &C) -> io::Result<Vec<u8>>
+    where
+        C: Codec<L> + Codec<R>,
+    {
+        let mut snapshot = SnapshotWriter::new();
+        self.left.save(&mut snapshot, codec)?;
+        self.right.save(&mut snapshot, codec)?;
+        Ok(snapshot.finish())
+    }
+
+    /// Rebuild the state `save` encoded by holding the items again in the order they were first
+    /// held.
+    fn load<C, KL, KR>(
+        rules: &JoinRules,
+        codec: &C,
+        (left_key, right_key): (&KL, &KR),
+        snapshot: &[u8],
+    ) -> io::Result<Self>
+    where
+        C: Codec<L> + Codec<R>,
+        KL: Fn(&L) -> K,
+        KR: Fn(&R) -> K,
+    {
+        let mut snapshot = SnapshotReader::new(snapshot);
+        let left = Side::<K, L>::load(&mut snapshot, codec)?;
+        let right = Side::<K, R>::load(&mut snapshot, codec)?;
+        snapshot.finish()?;
+        let mut state = Self::new();
+        // holding only fails if letting go of an item does, and nothing is sent for these
+        for held in left {
+            let key = left_key(&held.value);
+            let _ = state
+                .left
+                .hold(key, held, rules.window, rules.max_keys, |_| Ok(()));
+        }
+        for held in right {
+            let key = right_key(&held.value);
+            let _ = state
+                .right
+                .hold(key, held, rules.window, rules.max_keys, |_| Ok(()));
+        }
+        Ok(state)
+    }
+}
+
+impl<K, T> Side<K, T> {
+    /// Write the held items in the order they were held.
+    fn save<C: Codec<T>>(&self, snapshot: &mut SnapshotWriter, codec: &C) -> io::Result<()> {
+        let mut held: Vec<&Held<T>> = self.keys.values().flat_map(|k| &k.items).collect();
+        held.sort_unstable_by_key(|h| h.seq);
+        snapshot.u64(held.len() as u64);
+        for h in held {
+            snapshot.time(h.received);
+            snapshot.u64(h.matched as u64);
+            snapshot.item(codec, &h.value)?;
+        }
+        Ok(())
+    }
+
+    /// Read back the items `save` wrote.
+    fn load<C: Codec<T>>(snapshot: &mut SnapshotReader, codec: &C) -> io::Result<Vec<Held<T>>> {
+        let mut held = Vec::new();
+        for _ in 0..snapshot.u64()? {
+            held.push(Held {
+                received: snapshot.time()?,
+                seq: 0,
+                matched: snapshot.u64()? != 0,
+                value: snapshot.item(codec)?,
+            });
+        }
+        Ok(held)
+    }
+}
+
+/// Pair up the items arriving from both inputs of a join until both have been corked. `barrier`
+/// is called for each barrier once both inputs have reached it.
+fn join_events<K, L, R, KL, KR, P, B>(
+    rules: &JoinRules,
+    events: &Receiver<Event<L, R>>,
+    state: &Mutex<JoinState<K, L, R>>,
+    (left_key, right_key): (&KL, &KR),
+    send: &mut P,
+    mut barrier: B,
+) -> Result<(), ChannelError>
+where
+    K: Eq + Hash + Clone,
+    L: Clone,
+    R: Clone,
+    KL: Fn(&L) -> K,
+    KR: Fn(&R) -> K,
+    P: FnMut(L, Option<R>) -> Result<(), ChannelError>,
+    B: FnMut(u64) -> Result<(), ChannelError>,
+{
+    let lock = || state.lock().expect("Thread was poisoned");
+    let (mut left_open, mut right_open) = (true, true);
+    while left_open || right_open {
+        let event = match recv_until(events, lock().next_expiry(rules)) {
+            Input::Item(event) => Some(event),
+            Input::Timeout => None,
+            Input::End => break,
+        };
+        if let Some(Event::Barrier(id)) = event {
+            barrier(id)?;
+        }
+        let mut state = lock();
+        match event {
+            Some(Event::Left(l)) => state.on_left(rules, left_key(&l), l, right_open, send)?,
+            Some(Event::Right(r)) => state.on_right(rules, right_key(&r), r, left_open, send)?,
+            Some(Event::LeftEnd) => {
+                left_open = false;
+                state.right.clear(|_| Ok(()))?;
+            }
+            Some(Event::RightEnd) => {
+                right_open = false;
+                state.release_left(rules, send)?;
+            }
+            Some(Event::Barrier(_)) | None => {}
+        }
+        state.expire(rules, Instant::now(), send)?;
+    }
+    let mut state = lock();
+    state.release_left(rules, send)
+}
+
+/// Correlates two streams by key. Every item received on either input is paired with each item
+/// being held from the other input with the same key, and is then held for the `JoinWindow` so
+/// items arriving later on the other input can be paired with it too. Pairs are sent in the order
+/// they are found, with the left item first.
+///
+/// In a left outer join, left items which are let go without ever being paired are sent on their
+/// own, with `None` in place of the right item.
+///
+/// Once an input is corked, the items held from the other input can no longer be paired with
+/// anything new, so they are let go right away. The node ends once both inputs have been corked.
+/// Time windows are measured from when items arrive at the node.
+pub struct Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver,
+    RR: ChannelReceiver,
+    S: ChannelSender,
+{
+    name: String,
+    left: RL,
+    right: RR,
+    tx: S,
+    left_key: KL,
+    right_key: KR,
+    rules: JoinRules,
+    combine: Combine<RL::Item, RR::Item, S::Item>,
+}
+
+impl<RL, RR, S, KL, KR> Debug for Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver,
+    RR: ChannelReceiver,
+    S: ChannelSender,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<RL, RR, S, KL, KR, K> Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver,
+    RR: ChannelReceiver,
+    S: ChannelSender<Item = (RL::Item, RR::Item)>,
+    KL: Fn(&RL::Item) -> K,
+    KR: Fn(&RR::Item) -> K,
+    K: Eq + Hash,
+{
+    /// Join two streams, only sending the items which were paired up.
+    pub fn inner(name: String, left: RL, right: RR, tx: S, left_key: KL, right_key: KR) -> Self {
+        Self {
+            name,
+            left,
+            right,
+            tx,
+            left_key,
+            right_key,
+            rules: JoinRules::new(JoinMode::Inner),
+            combine: |l, r| (l, r.expect("Inner joins only send pairs")),
+        }
+    }
+}
+
+impl<RL, RR, S, KL, KR, K> Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver,
+    RR: ChannelReceiver,
+    S: ChannelSender<Item = (RL::Item, Option<RR::Item>)>,
+    KL: Fn(&RL::Item) -> K,
+    KR: Fn(&RR::Item) -> K,
+    K: Eq + Hash,
+{
+    /// Join two streams, also sending every left item which was never paired up.
+    pub fn left_outer(
+        name: String,
+        left: RL,
+        right: RR,
+        tx: S,
+        left_key: KL,
+        right_key: KR,
+    ) -> Self {
+        Self {
+            name,
+            left,
+            right,
+            tx,
+            left_key,
+            right_key,
+            rules: JoinRules::new(JoinMode::LeftOuter),
+            combine: |l, r| (l, r),
+        }
+    }
+}
+
+impl<RL, RR, S, KL, KR, K> Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver,
+    RR: ChannelReceiver,
+    S: ChannelSender,
+    KL: Fn(&RL::Item) -> K,
+    KR: Fn(&RR::Item) -> K,
+    K: Eq + Hash + Clone,
+{
+    /// Set how long unmatched items are held for.
+    pub fn window(mut self, window: JoinWindow) -> Self {
+        self.rules = self.rules.window(window);
+        self
+    }
+
+    /// Hold items for at most this many keys from each input in a count window, letting go of the
+    /// keys which have gone the longest without a new item first. Otherwise every key ever seen
+    /// is held until the other input is corked. Time windows let go of keys once their items
+    /// expire, so this has no effect on them.
+    pub fn max_keys(mut self, keys: usize) -> Self {
+        self.rules = self.rules.max_keys(keys);
+        self
+    }
+}
+
+/// Pass everything from one input of a join on to the node, followed by `end` once it is corked.
+fn forward<R, L, RI, T>(rx: &R, events: &Sender<Event<L, RI>>, wrap: T, end: Event<L, RI>)
+where
+    R: ChannelReceiver,
+    L: Clone,
+    RI: Clone,
+    T: Fn(R::Item) -> Event<L, RI>,
+{
+    while let Some(v) = recv_or_end(rx) {
+        if events.send(wrap(v)).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(end);
+}
+
+impl<RL, RR, S, KL, KR, K> ComputeNode for Join<RL, RR, S, KL, KR>
+where
+    RL: ChannelReceiver + Send + Sync,
+    RR: ChannelReceiver + Send + Sync,
+    RL::Item: Send,
+    RR::Item: Send,
+    S: ChannelSender + Send,
+    KL: Fn(&RL::Item) -> K + Send,
+    KR: Fn(&RR::Item) -> K + Send,
+    K: Eq + Hash + Clone,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let (events_tx, events) = sync_channel(EVENT_BOUND);
+        let (left, right) = (&self.left, &self.right);
+        thread::scope(|scope| {
+            let left_tx = events_tx.clone();
+            scope.spawn(move || forward(left, &left_tx, Event::Left, Event::LeftEnd));
+            let right_tx = events_tx.clone();
+            scope.spawn(move || forward(right, &right_tx, Event::Right, Event::RightEnd));
+            let send = &mut |l, r| self.tx.send((self.combine)(l, r));
+            // an error only means the output was corked
+            let _ = join_events(
+                &self.rules,
+                &events,
+                &Mutex::new(JoinState::new()),
+                (&self.left_key, &self.right_key),
+                send,
+                |_| Ok(()),
+            );
+            // let the readers go if we stopped early
+            events_tx.cork();
+        });
+        self.tx.cork();
+    }
+}
+
+/// A `Join` of two checkpointed streams which saves the items it is holding with every barrier,
+/// using `codec` to turn them into bytes. An input which reaches a barrier isn't read from again
+/// until the other input has reached it too, so the snapshot covers exactly the items from before
+/// the barrier on both inputs, and a single barrier is then passed on. Barriers are expected to
+/// arrive in the same order on both inputs, as they do when both are fed from the same
+/// `CheckpointSource`.
+///
+/// As with `StatefulScan`, the held items are kept between runs and failing to save them is
+/// reported while the barrier is still passed on. Times are saved as how long ago they were, so
+/// restored items expire as if no time had passed while the graph was stopped.
+pub struct StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, O> {
+    name: String,
+    left: RL,
+    right: RR,
+    tx: S,
+    left_key: KL,
+    right_key: KR,
+    rules: JoinRules,
+    combine: Combine<L, R, O>,
+    state: Mutex<JoinState<K, L, R>>,
+    codec: C,
+    store: Option<Arc<CheckpointStore>>,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R, O> Debug for StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R> StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, (L, R)>
+where
+    RL: ChannelReceiver<Item = Record<L>>,
+    RR: ChannelReceiver<Item = Record<R>>,
+    S: ChannelSender<Item = Record<(L, R)>>,
+    KL: Fn(&L) -> K,
+    KR: Fn(&R) -> K,
+    K: Eq + Hash + Clone,
+    C: Codec<L> + Codec<R>,
+    L: Clone,
+    R: Clone,
+{
+    /// Join two streams, only sending the items which were paired up.
+    pub fn inner(
+        name: String,
+        left: RL,
+        right: RR,
+        tx: S,
+        left_key: KL,
+        right_key: KR,
+        codec: C,
+    ) -> Self {
+        Self {
+            name,
+            left,
+            right,
+            tx,
+            left_key,
+            right_key,
+            rules: JoinRules::new(JoinMode::Inner),
+            combine: |l, r| (l, r.expect("Inner joins only send pairs")),
+            state: Mutex::new(JoinState::new()),
+            codec,
+            store: None,
+            errors: None,
+        }
+    }
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R> StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, (L, Option<R>)>
+where
+    RL: ChannelReceiver<Item = Record<L>>,
+    RR: ChannelReceiver<Item = Record<R>>,
+    S: ChannelSender<Item = Record<(L, Option<R>)>>,
+    KL: Fn(&L) -> K,
+    KR: Fn(&R) -> K,
+    K: Eq + Hash + Clone,
+    C: Codec<L> + Codec<R>,
+    L: Clone,
+    R: Clone,
+{
+    /// Join two streams, also sending every left item which was never paired up.
+    pub fn left_outer(
+        name: String,
+        left: RL,
+        right: RR,
+        tx: S,
+        left_key: KL,
+        right_key: KR,
+        codec: C,
+    ) -> Self {
+        Self {
+            name,
+            left,
+            right,
+            tx,
+            left_key,
+            right_key,
+            rules: JoinRules::new(JoinMode::LeftOuter),
+            combine: |l, r| (l, r),
+            state: Mutex::new(JoinState::new()),
+            codec,
+            store: None,
+            errors: None,
+        }
+    }
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R, O> StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, O>
+where
+    RL: ChannelReceiver<Item = Record<L>>,
+    RR: ChannelReceiver<Item = Record<R>>,
+    S: ChannelSender<Item = Record<O>>,
+    KL: Fn(&L) -> K,
+    KR: Fn(&R) -> K,
+    K: Eq + Hash + Clone,
+    C: Codec<L> + Codec<R>,
+    L: Clone,
+    R: Clone,
+{
+    /// Set how long unmatched items are held for, as with `Join::window`.
+    pub fn window(mut self, window: JoinWindow) -> Self {
+        self.rules = self.rules.window(window);
+        self
+    }
+
+    /// Hold items for at most this many keys from each input, as with `Join::max_keys`.
+    pub fn max_keys(mut self, keys: usize) -> Self {
+        self.rules = self.rules.max_keys(keys);
+        self
+    }
+
+    /// Save snapshots to this store.
+    pub fn store(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JoinState<K, L, R>> {
+        self.state.lock().expect("Thread was poisoned")
+    }
+}
+
+/// Pass everything from one input of a checkpointed join on to the node, waiting at each barrier
+/// for the other input to reach it, followed by `end` once the input is corked.
+fn forward_records<RX, T, L, R, W>(
+    rx: &RX,
+    events: &Sender<Event<L, R>>,
+    alignment: &BarrierAlignment<u64>,
+    wrap: W,
+    end: Event<L, R>,
+) where
+    RX: ChannelReceiver<Item = Record<T>>,
+    L: Clone,
+    R: Clone,
+    W: Fn(T) -> Event<L, R>,
+{
+    let pass_on = |id| {
+        let _ = events.send(Event::Barrier(id));
+    };
+    while let Some(record) = recv_or_end(rx) {
+        match record {
+            Record::Item(v) => {
+                if events.send(wrap(v)).is_err() {
+                    break;
+                }
+            }
+            Record::Barrier(id) => alignment.arrive(id, pass_on),
+        }
+    }
+    alignment.end(pass_on);
+    let _ = events.send(end);
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R, O> ComputeNode
+    for StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, O>
+where
+    RL: ChannelReceiver<Item = Record<L>> + Send + Sync,
+    RR: ChannelReceiver<Item = Record<R>> + Send + Sync,
+    S: ChannelSender<Item = Record<O>> + Send,
+    KL: Fn(&L) -> K + Send,
+    KR: Fn(&R) -> K + Send,
+    K: Eq + Hash + Clone + Send,
+    C: Codec<L> + Codec<R>,
+    L: Clone + Send,
+    R: Clone + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let (events_tx, events) = sync_channel(EVENT_BOUND);
+        let alignment = BarrierAlignment::new(2);
+        let (left, right, alignment) = (&self.left, &self.right, &alignment);
+        thread::scope(|scope| {
+            let left_tx = events_tx.clone();
+            scope.spawn(move || {
+                forward_records(left, &left_tx, alignment, Event::Left, Event::LeftEnd)
+            });
+            let right_tx = events_tx.clone();
+            scope.spawn(move || {
+                forward_records(right, &right_tx, alignment, Event::Right, Event::RightEnd)
+            });
+            let send = &mut |l, r| self.tx.send(Record::Item((self.combine)(l, r)));
+            let barrier = |id| {
+                checkpoint(self, &self.store, &self.errors, id);
+                self.tx.send(Record::Barrier(id))
+            };
+            // an error only means the output was corked
+            let _ = join_events(
+                &self.rules,
+                &events,
+                &self.state,
+                (&self.left_key, &self.right_key),
+                send,
+                barrier,
+            );
+            // let the readers go if we stopped early
+            events_tx.cork();
+        });
+        self.tx.cork();
+    }
+}
+
+impl<RL, RR, S, KL, KR, K, C, L, R, O> StatefulNode
+    for StatefulJoin<RL, RR, S, KL, KR, K, C, L, R, O>
+where
+    RL: ChannelReceiver<Item = Record<L>> + Send + Sync,
+    RR: ChannelReceiver<Item = Record<R>> + Send + Sync,
+    S: ChannelSender<Item = Record<O>> + Send,
+    KL: Fn(&L) -> K + Send,
+    KR: Fn(&R) -> K + Send,
+    K: Eq + Hash + Clone + Send,
+    C: Codec<L> + Codec<R>,
+    L: Clone + Send,
+    R: Clone + Send,
+{
+    fn snapshot(&self) -> io::Result<Vec<u8>> {
+        self.lock().save(&self.codec)
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        let keys = (&self.left_key, &self.right_key);
+        let state = JoinState::load(&self.rules, &self.codec, keys, snapshot)?;
+        *self.lock() = state;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::mpmc::RawLeCodec;
+    use crate::nodes::test_util::{drain, input, temp_dir};
+
+    /// Join `left` and `right` by their last digit, restoring from checkpoint `restore` first.
+    fn join_digits(
+        store: &Arc<CheckpointStore>,
+        left: &[Record<u32>],
+        right: &[Record<u32>],
+        restore: Option<u64>,
+    ) -> Vec<Record<(u32, u32)>> {
+        let (tx, rx) = sync_channel(16);
+        let join = StatefulJoin::inner(
+            "join".into(),
+            input(left),
+            input(right),
+            tx,
+            |l: &u32| l % 10,
+            |r: &u32| r % 10,
+            RawLeCodec,
+        )
+        .window(JoinWindow::Count(4))
+        .store(store.clone());
+        if let Some(id) = restore {
+            join.restore_from(store, id).unwrap();
+        }
+        join.run();
+        drain(&rx)
+    }
+
+    #[test]
+    fn inner_join_latest_per_key() {
+        let (audio_tx, audio_rx) = sync_channel(8);
+        let (meta_tx, meta_rx) = sync_channel(8);
+        let (tx, rx) = sync_channel(16);
+        let join = Join::inner(
+            "join".into(),
+            audio_rx,
+            meta_rx,
+            tx,
+            |a: &(u8, u32)| a.0,
+            |m: &(u8, &'static str)| m.0,
+        );
+        let node = thread::spawn(move || join.run());
+
+        meta_tx.send((1, "first")).unwrap();
+        // make sure the metadata is seen first
+        thread::sleep(Duration::from_millis(20));
+        audio_tx.send((1, 10)).unwrap();
+        audio_tx.send((2, 20)).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        // pairs up with the packet held for key 1 and replaces the old metadata
+        meta_tx.send((1, "second")).unwrap();
+        meta_tx.send((2, "other")).unwrap();
+        drop(meta_tx);
+        drop(audio_tx);
+        node.join().unwrap();
+
+        let pairs = drain(&rx);
+        assert_eq!(
+            pairs,
+            [
+                ((1, 10), (1, "first")),
+                ((1, 10), (1, "second")),
+                ((2, 20), (2, "other")),
+            ]
+        );
+    }
+
+    #[test]
+    fn left_outer_time_window() {
+        let (left_tx, left_rx) = sync_channel(8);
+        let (right_tx, right_rx) = sync_channel(8);
+        let (tx, rx) = sync_channel(16);
+        let join = Join::left_outer(
+            "join".into(),
+            left_rx,
+            right_rx,
+            tx,
+            |l: &u8| *l,
+            |r: &u8| *r,
+        )
+        .window(JoinWindow::Time(Duration::from_millis(30)));
+        let node = thread::spawn(move || join.run());
+
+        left_tx.send(1).unwrap();
+        left_tx.send(2).unwrap();
+        // 1 goes unmatched for longer than the window
+        thread::sleep(Duration::from_millis(60));
+        let first = rx.recv().unwrap();
+        assert!(first == (1, None) || first == (2, None));
+        left_tx.send(3).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        right_tx.send(3).unwrap();
+        // nothing can pair with 4 once the right is corked
+        drop(right_tx);
+        left_tx.send(4).unwrap();
+        drop(left_tx);
+        node.join().unwrap();
+
+        let mut rest = drain(&rx);
+        rest.insert(0, first);
+        rest[..2].sort_unstable();
+        assert_eq!(rest, [(1, None), (2, None), (3, Some(3)), (4, None)]);
+    }
+
+    #[test]
+    fn count_window_key_limit() {
+        let (left_tx, left_rx) = sync_channel(8);
+        let (right_tx, right_rx) = sync_channel(8);
+        let (tx, rx) = sync_channel(16);
+        let join = Join::left_outer(
+            "join".into(),
+            left_rx,
+            right_rx,
+            tx,
+            |l: &u8| *l,
+            |r: &u8| *r,
+        )
+        .max_keys(2);
+        let node = thread::spawn(move || join.run());
+
+        left_tx.send(1).unwrap();
+        left_tx.send(2).unwrap();
+        // the first item for key 1 is replaced by the second
+        left_tx.send(1).unwrap();
+        assert_eq!(rx.recv(), Ok((1, None)));
+        // key 2 went the longest without an item, so it is let go to make room
+        left_tx.send(3).unwrap();
+        assert_eq!(rx.recv(), Ok((2, None)));
+        right_tx.send(2).unwrap();
+        right_tx.send(3).unwrap();
+        assert_eq!(rx.recv(), Ok((3, Some(3))));
+        drop(right_tx);
+        drop(left_tx);
+        node.join().unwrap();
+        assert_eq!(drain(&rx), [(1, None)]);
+    }
+
+    #[test]
+    fn join_checkpoint() {
+        let dir = temp_dir("join");
+        let store = Arc::new(CheckpointStore::open(&dir).unwrap());
+        let left = [1, 12].map(Record::Item);
+        let left = [&left[..], &[Record::Barrier(0), Record::Item(21)]].concat();
+        let right = [Record::Item(11), Record::Barrier(0)];
+        let right = [&right[..], &[2, 31].map(Record::Item)].concat();
+        let out = join_digits(&store, &left, &right, None);
+        // everything from before the barrier on either side is dealt with ahead of it
+        assert_eq!(out[..2], [Record::Item((1, 11)), Record::Barrier(0)]);
+        let mut after = out[2..].to_vec();
+        after.sort_unstable_by_key(|r| r.clone().into_item());
+        let expected = [(1, 31), (12, 2), (21, 11), (21, 31)].map(Record::Item);
+        assert_eq!(after, expected);
+
+        // the restored join still holds the items from before the barrier
+        let mut out = join_digits(&store, &left[3..], &right[2..], Some(0));
+        out.sort_unstable_by_key(|r| r.clone().into_item());
+        assert_eq!(out, expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
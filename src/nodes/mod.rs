@@ -1,5 +1,11 @@
 //! Generic compute nodes which form the building blocks of a compute graph.
 
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::time::Instant;
+
+use crate::mpmc::{ChannelError, ChannelReceiver, ChannelSender, Sender};
+
 /// Primary building block of a compute graph. Compute nodes are run in their own threads and pull
 /// data in from channels and publish to other channels. They can also interact with the console,
 /// files, the network, or any other source or sink of data.
@@ -17,6 +23,170 @@ pub trait ComputeNode: Send {
     fn run(&self);
 }
 
+/// An error a node ran into and could not deal with itself, such as failing to read a file. Nodes
+/// which can fail this way take a channel to report these on, and stop rather than panic when they
+/// do.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NodeError {
+    /// Name of the node which failed.
+    pub node: String,
+    pub kind: io::ErrorKind,
+    pub message: String,
+}
+
+impl NodeError {
+    pub fn new(node: &str, e: &io::Error) -> Self {
+        Self {
+            node: node.to_string(),
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl Display for NodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.node, self.message)
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+/// Report an error on a node's error channel, or on standard error if it does not have one (or
+/// nobody is listening to it anymore).
+fn report_error(errors: &Option<Sender<NodeError>>, node: &str, e: &io::Error) {
+    let error = NodeError::new(node, e);
+    if let Some(tx) = errors {
+        if tx.send(error.clone()).is_ok() {
+            return;
+        }
+    }
+    eprintln!("{}", error);
+}
+
+/// Turn a channel or codec error into one a node can report, saying what it was doing.
+fn channel_error(e: ChannelError, what: &str) -> io::Error {
+    match e {
+        ChannelError::Io(kind) => io::Error::new(kind, what),
+        e => io::Error::other(format!("{}: {:?}", what, e)),
+    }
+}
+
+/// Receive the next input for a node, or None once the input has been corked. Any other error
+/// means the graph is broken, so it panics.
+fn recv_or_end<R: ChannelReceiver>(rx: &R) -> Option<R::Item> {
+    match rx.recv() {
+        Ok(v) => Some(v),
+        Err(ChannelError::IsCorked) => None,
+        Err(ChannelError::Poisoned) => panic!("Thread was poisoned"),
+        Err(e) => panic!("Unable to receive: {:?}", e),
+    }
+}
+
+/// What a node waiting on its input with a deadline got.
+enum Input<T> {
+    Item(T),
+    /// The deadline passed first.
+    Timeout,
+    /// The input has been corked.
+    End,
+}
+
+/// Receive the next input for a node, waiting no later than `deadline` if there is one. Any error
+/// other than the input being corked panics, as with `recv_or_end`.
+fn recv_until<R: ChannelReceiver>(rx: &R, deadline: Option<Instant>) -> Input<R::Item> {
+    let deadline = match deadline {
+        None => return recv_or_end(rx).map_or(Input::End, Input::Item),
+        Some(deadline) => deadline,
+    };
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(Some(v)) => Input::Item(v),
+        Ok(None) => Input::Timeout,
+        Err(ChannelError::IsCorked) => Input::End,
+        Err(ChannelError::Poisoned) => panic!("Thread was poisoned"),
+        Err(e) => panic!("Unable to receive: {:?}", e),
+    }
+}
+
 // TODO: make a macro to generate variously sized generic nodes.
 mod generic_compute_1_1;
 pub use generic_compute_1_1::GenericComputeNode_1_1;
+
+mod nats;
+#[cfg(any(test, feature = "testing"))]
+pub use nats::FakeNatsBroker;
+pub use nats::{NatsPublishNode, NatsSubscribeNode};
+
+mod graph;
+pub use graph::{spawn, spawn_partitioned};
+
+mod combinators;
+pub use combinators::{
+    Filter, FilterMap, FlatMap, Fold, Inspect, Map, Scan, Skip, Take, TakeWhile,
+};
+
+mod window;
+pub use window::{StatefulWindow, Window, WindowSpec};
+
+mod batch;
+pub use batch::{Batch, Rechunk, Unbatch};
+
+mod fan_in;
+pub use fan_in::{AlignBarriers, Merge, RoundRobin, Zip, ZipInputs};
+
+mod fan_out;
+pub use fan_out::{Router, Tee, TeeMode, Unzip, UnzipOutputs};
+
+mod join;
+pub use join::{Join, JoinWindow, StatefulJoin};
+
+mod timing;
+pub use timing::{Debounce, Pace, Sample, Throttle};
+
+mod source;
+pub use source::{FromIterator, Interval, ReadDirectory, ReadFile, ReadStdin, ReadUnit};
+
+mod sink;
+pub use sink::{Collect, Collected, ForEach, WriteFile, WriteTo};
+
+mod checkpoint;
+pub use checkpoint::{
+    CheckpointSource, CheckpointStore, Record, StatefulFold, StatefulNode, StatefulScan,
+};
+
+/// Helpers shared by the tests of the nodes.
+#[cfg(test)]
+mod test_util {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use crate::mpmc::{sync_channel, ChannelReceiver, ChannelSender, Receiver};
+
+    /// A corked channel holding `items`.
+    pub fn input<T: Clone>(items: &[T]) -> Receiver<T> {
+        let (tx, rx) = sync_channel(items.len().max(1));
+        for v in items {
+            tx.send(v.clone()).unwrap();
+        }
+        rx
+    }
+
+    /// Everything a receiver gets until its channel is corked.
+    pub fn drain<T: Clone>(rx: &Receiver<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Ok(v) = rx.recv() {
+            out.push(v);
+        }
+        out
+    }
+
+    /// An empty directory for a test to use, clearing out whatever an earlier run left in it.
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cgraph-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
@@ -0,0 +1,686 @@
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+use crate::mpmc::{ChannelReceiver, ChannelSender, Codec, Sender};
+
+use super::{channel_error, recv_or_end, report_error, ComputeNode, NodeError};
+
+/// How often a subscriber waiting for messages checks whether its output has been corked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Minimal client for the NATS text protocol, just enough to publish and subscribe.
+struct NatsClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// A message delivered to a subscription.
+struct NatsMessage {
+    payload: Vec<u8>,
+}
+
+fn protocol_error() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "invalid NATS protocol line")
+}
+
+/// Subjects are made of tokens separated by `.` and may not contain whitespace.
+fn valid_subject(subject: &str) -> bool {
+    !subject.is_empty() && !subject.contains(char::is_whitespace)
+}
+
+impl NatsClient {
+    fn connect(addr: &str) -> io::Result<Self> {
+        let writer = TcpStream::connect(addr)?;
+        writer.set_nodelay(true)?;
+        let mut client = Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        };
+        // the server starts by telling us about itself, which we have no use for
+        if !client.read_line()?.starts_with("INFO") {
+            return Err(protocol_error());
+        }
+        client
+            .writer
+            .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false}\r\n")?;
+        Ok(client)
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    fn publish(&mut self, subject: &str, payload: &[u8]) -> io::Result<()> {
+        let mut frame = format!("PUB {} {}\r\n", subject, payload.len()).into_bytes();
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        self.writer.write_all(&frame)
+    }
+
+    /// Subscribe to a subject, optionally unsubscribing automatically after `max` messages.
+    fn subscribe(&mut self, subject: &str, sid: u64, max: Option<u64>) -> io::Result<()> {
+        let mut cmd = format!("SUB {} {}\r\n", subject, sid);
+        if let Some(max) = max {
+            cmd.push_str(&format!("UNSUB {} {}\r\n", sid, max));
+        }
+        self.writer.write_all(cmd.as_bytes())
+    }
+
+    /// Make sure everything we sent has been processed by the server.
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(b"PING\r\n")?;
+        loop {
+            let line = self.read_line()?;
+            if line == "PONG" {
+                return Ok(());
+            }
+            self.handle_control(&line)?;
+        }
+    }
+
+    /// Wait up to `timeout` for something to arrive from the server, without consuming any of it.
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        if !self.reader.buffer().is_empty() {
+            return Ok(true);
+        }
+        self.reader.get_ref().set_read_timeout(Some(timeout))?;
+        let filled = self.reader.fill_buf().map(|_| ());
+        // the rest of a line or payload which has started arriving is waited for however long
+        self.reader.get_ref().set_read_timeout(None)?;
+        match filled {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Wait for the next message, answering the server's keep-alives along the way. Returns
+    /// nothing if no message started arriving within `poll`.
+    fn next_message(&mut self, poll: Duration) -> io::Result<Option<NatsMessage>> {
+        loop {
+            if !self.wait_for_data(poll)? {
+                return Ok(None);
+            }
+            let line = self.read_line()?;
+            if !line.starts_with("MSG ") {
+                self.handle_control(&line)?;
+                continue;
+            }
+            // MSG <subject> <sid> [reply-to] <#bytes>
+            let len: usize = line
+                .rsplit(' ')
+                .next()
+                .and_then(|len| len.parse().ok())
+                .ok_or_else(protocol_error)?;
+            let mut payload = vec![0u8; len + 2];
+            self.reader.read_exact(&mut payload)?;
+            payload.truncate(len);
+            return Ok(Some(NatsMessage { payload }));
+        }
+    }
+
+    fn handle_control(&mut self, line: &str) -> io::Result<()> {
+        if line == "PING" {
+            self.writer.write_all(b"PONG\r\n")
+        } else if line.starts_with("-ERR") {
+            Err(io::Error::other(line.to_string()))
+        } else {
+            // +OK, INFO updates and anything else we don't care about
+            Ok(())
+        }
+    }
+
+    fn close(&self) {
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+}
+
+/// Publishes everything received on a channel to a subject on a NATS compatible broker, so other
+/// systems on the messaging fabric can consume the output of a pipeline. Finishes once the input
+/// has been corked and everything published has reached the broker. Errors connecting,
+/// publishing or encoding are reported on the error channel, or standard error without one, after
+/// which the rest of the input is discarded.
+pub struct NatsPublishNode<T, R, C> {
+    name: String,
+    rx: R,
+    addr: String,
+    subject: String,
+    codec: C,
+    errors: Option<Sender<NodeError>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, R, C> Debug for NatsPublishNode<T, R, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<T, R, C> NatsPublishNode<T, R, C>
+where
+    T: Clone,
+    R: ChannelReceiver<Item = T>,
+    C: Codec<T>,
+{
+    /// `addr` is the `host:port` of the broker.
+    ///
+    /// Panics if the subject is empty or contains whitespace.
+    pub fn new(name: String, rx: R, addr: String, subject: String, codec: C) -> Self {
+        assert!(valid_subject(&subject), "Invalid subject {:?}", subject);
+        Self {
+            name,
+            rx,
+            addr,
+            subject,
+            codec,
+            errors: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn publish(&self) -> io::Result<()> {
+        let mut client = NatsClient::connect(&self.addr)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.addr, e)))?;
+        let mut payload = Vec::new();
+        let published = (|| {
+            while let Some(v) = recv_or_end(&self.rx) {
+                payload.clear();
+                self.codec
+                    .encode(&v, &mut payload)
+                    .map_err(|e| channel_error(e, "unable to encode"))?;
+                client.publish(&self.subject, &payload)?;
+            }
+            client.flush()
+        })();
+        client.close();
+        published
+    }
+}
+
+impl<T, R, C> ComputeNode for NatsPublishNode<T, R, C>
+where
+    T: Clone + Send,
+    R: ChannelReceiver<Item = T> + Send,
+    C: Codec<T>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        if let Err(e) = self.publish() {
+            report_error(&self.errors, &self.name, &e);
+            // don't leave the nodes sending to us blocked on a full channel
+            while recv_or_end(&self.rx).is_some() {}
+        }
+    }
+}
+
+/// Feeds messages published to a subject on a NATS compatible broker into a channel, so a pipeline
+/// can consume from the messaging fabric. Runs until the output is corked or the connection to the
+/// broker is closed, or until `limit` messages have been received if one was given. Errors
+/// connecting, receiving or decoding are reported on the error channel, or standard error without
+/// one, and stop the node. The output is corked however it stops.
+pub struct NatsSubscribeNode<T, S, C> {
+    name: String,
+    tx: S,
+    addr: String,
+    subject: String,
+    codec: C,
+    limit: Option<u64>,
+    errors: Option<Sender<NodeError>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, S, C> Debug for NatsSubscribeNode<T, S, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<T, S, C> NatsSubscribeNode<T, S, C>
+where
+    T: Clone,
+    S: ChannelSender<Item = T>,
+    C: Codec<T>,
+{
+    /// `addr` is the `host:port` of the broker and `subject` may contain wildcards.
+    ///
+    /// Panics if the subject is empty or contains whitespace.
+    pub fn new(name: String, tx: S, addr: String, subject: String, codec: C) -> Self {
+        assert!(valid_subject(&subject), "Invalid subject {:?}", subject);
+        Self {
+            name,
+            tx,
+            addr,
+            subject,
+            codec,
+            limit: None,
+            errors: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Stop after this many messages.
+    pub fn limit(mut self, messages: u64) -> Self {
+        self.limit = Some(messages);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn subscribe(&self) -> io::Result<()> {
+        let mut client = NatsClient::connect(&self.addr)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.addr, e)))?;
+        let subscribed = (|| {
+            client.subscribe(&self.subject, 1, self.limit)?;
+            let mut received = 0;
+            while Some(received) != self.limit {
+                let msg = match client.next_message(POLL_INTERVAL) {
+                    Ok(Some(msg)) => msg,
+                    Ok(None) if self.tx.is_corked() => break,
+                    Ok(None) => continue,
+                    // the broker went away so there will be no more messages
+                    Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e),
+                };
+                let v = self
+                    .codec
+                    .decode(&msg.payload)
+                    .map_err(|e| channel_error(e, "unable to decode"))?;
+                if self.tx.send(v).is_err() {
+                    // output has been corked so there is no point in continuing
+                    break;
+                }
+                received += 1;
+            }
+            Ok(())
+        })();
+        client.close();
+        subscribed
+    }
+}
+
+impl<T, S, C> ComputeNode for NatsSubscribeNode<T, S, C>
+where
+    T: Clone + Send,
+    S: ChannelSender<Item = T> + Send,
+    C: Codec<T>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        if self.limit != Some(0) {
+            if let Err(e) = self.subscribe() {
+                report_error(&self.errors, &self.name, &e);
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// An in-process broker for testing, which is only built for this crate's tests or with the
+/// `testing` feature.
+#[cfg(any(test, feature = "testing"))]
+mod broker {
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::protocol_error;
+
+    struct Subscription {
+        conn: usize,
+        sid: String,
+        subject: String,
+        delivered: u64,
+        max: Option<u64>,
+    }
+
+    #[derive(Default)]
+    struct BrokerState {
+        subs: Vec<Subscription>,
+        conns: HashMap<usize, Arc<Mutex<TcpStream>>>,
+    }
+
+    /// Does `subject` match `pattern`, where `*` in the pattern matches any one token and `>` at
+    /// the end matches one or more.
+    pub(super) fn subject_matches(pattern: &str, subject: &str) -> bool {
+        let mut subject = subject.split('.');
+        for p in pattern.split('.') {
+            match (p, subject.next()) {
+                (">", Some(_)) => return true,
+                ("*", Some(_)) => {}
+                (p, Some(s)) if p == s => {}
+                _ => return false,
+            }
+        }
+        subject.next().is_none()
+    }
+
+    /// In-process stand-in for a NATS server which supports enough of the protocol to test
+    /// pipelines using the NATS nodes: publishing, subscribing with wildcards, unsubscribing and
+    /// keep-alives. Messages are only delivered to subscriptions which exist when they are
+    /// published.
+    pub struct FakeNatsBroker {
+        addr: SocketAddr,
+        state: Arc<Mutex<BrokerState>>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl FakeNatsBroker {
+        /// Start a broker listening on a free port on localhost.
+        pub fn start() -> io::Result<Self> {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let addr = listener.local_addr()?;
+            let state = Arc::new(Mutex::new(BrokerState::default()));
+            let stopped = Arc::new(AtomicBool::new(false));
+            {
+                let state = state.clone();
+                let stopped = stopped.clone();
+                let next_conn = AtomicUsize::new(0);
+                thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        if stopped.load(Ordering::Acquire) {
+                            break;
+                        }
+                        if let Ok(stream) = stream {
+                            let id = next_conn.fetch_add(1, Ordering::Relaxed);
+                            let state = state.clone();
+                            thread::spawn(move || {
+                                let _ = Self::serve(id, stream, &state);
+                                let mut state = state.lock().unwrap();
+                                state.subs.retain(|s| s.conn != id);
+                                state.conns.remove(&id);
+                            });
+                        }
+                    }
+                });
+            }
+            Ok(Self {
+                addr,
+                state,
+                stopped,
+            })
+        }
+
+        /// The `host:port` clients should connect to.
+        pub fn addr(&self) -> String {
+            self.addr.to_string()
+        }
+
+        /// The number of subscriptions currently active, useful for waiting until subscribers are
+        /// ready before publishing.
+        pub fn subscriptions(&self) -> usize {
+            self.state.lock().unwrap().subs.len()
+        }
+
+        fn serve(id: usize, stream: TcpStream, state: &Mutex<BrokerState>) -> io::Result<()> {
+            let writer = Arc::new(Mutex::new(stream.try_clone()?));
+            state.lock().unwrap().conns.insert(id, writer.clone());
+            writer.lock().unwrap().write_all(
+                b"INFO {\"server_id\":\"cgraph-fake\",\"version\":\"0.0.0\",\"max_payload\":1048576}\r\n",
+            )?;
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 {
+                    return Ok(());
+                }
+                let args: Vec<&str> = line.split_whitespace().collect();
+                match args.first().map(|op| op.to_ascii_uppercase()).as_deref() {
+                    Some("PUB") if args.len() == 3 || args.len() == 4 => {
+                        let len: usize =
+                            args[args.len() - 1].parse().map_err(|_| protocol_error())?;
+                        let mut payload = vec![0u8; len + 2];
+                        reader.read_exact(&mut payload)?;
+                        Self::route(state, args[1], &payload);
+                    }
+                    Some("SUB") if args.len() == 3 || args.len() == 4 => {
+                        state.lock().unwrap().subs.push(Subscription {
+                            conn: id,
+                            sid: args[args.len() - 1].to_string(),
+                            subject: args[1].to_string(),
+                            delivered: 0,
+                            max: None,
+                        });
+                    }
+                    Some("UNSUB") if args.len() == 2 || args.len() == 3 => {
+                        let max = args.get(2).and_then(|max| max.parse().ok());
+                        let mut state = state.lock().unwrap();
+                        state.subs.retain(|s| {
+                            s.conn != id
+                                || s.sid != args[1]
+                                || max.is_some_and(|max| s.delivered < max)
+                        });
+                        for s in state.subs.iter_mut() {
+                            if s.conn == id && s.sid == args[1] {
+                                s.max = max;
+                            }
+                        }
+                    }
+                    Some("PING") => writer.lock().unwrap().write_all(b"PONG\r\n")?,
+                    Some("CONNECT") | Some("PONG") => {}
+                    _ => writer
+                        .lock()
+                        .unwrap()
+                        .write_all(b"-ERR 'Unknown Protocol Operation'\r\n")?,
+                }
+            }
+        }
+
+        /// Deliver a published payload, still followed by its `\r\n`, to every matching
+        /// subscription.
+        fn route(state: &Mutex<BrokerState>, subject: &str, payload: &[u8]) {
+            let mut state = state.lock().unwrap();
+            let BrokerState { subs, conns } = &mut *state;
+            for s in subs.iter_mut() {
+                if !subject_matches(&s.subject, subject) {
+                    continue;
+                }
+                let mut frame =
+                    format!("MSG {} {} {}\r\n", subject, s.sid, payload.len() - 2).into_bytes();
+                frame.extend_from_slice(payload);
+                if let Some(conn) = conns.get(&s.conn) {
+                    let _ = conn.lock().unwrap().write_all(&frame);
+                }
+                s.delivered += 1;
+            }
+            subs.retain(|s| s.max.map_or(true, |max| s.delivered < max));
+        }
+    }
+
+    /// Stop accepting connections and disconnect every client, which ends any subscriptions.
+    impl Drop for FakeNatsBroker {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::Release);
+            let _ = TcpStream::connect(self.addr);
+            if let Ok(state) = self.state.lock() {
+                for conn in state.conns.values() {
+                    if let Ok(conn) = conn.lock() {
+                        let _ = conn.shutdown(Shutdown::Both);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub use broker::FakeNatsBroker;
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::mpmc::{sync_channel, BytesCodec, ChannelError, RawLeCodec};
+
+    fn wait_for_subscriptions(broker: &FakeNatsBroker, n: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while broker.subscriptions() < n {
+            assert!(Instant::now() < deadline, "subscribers never showed up");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn subjects() {
+        use super::broker::subject_matches;
+
+        assert!(subject_matches("audio.left", "audio.left"));
+        assert!(!subject_matches("audio.left", "audio.right"));
+        assert!(subject_matches("audio.*", "audio.right"));
+        assert!(!subject_matches("audio.*", "audio.right.raw"));
+        assert!(subject_matches("audio.>", "audio.right.raw"));
+        assert!(!subject_matches("audio.>", "audio"));
+        assert!(!subject_matches("audio", "audio.left"));
+    }
+
+    #[test]
+    fn bridge_through_broker() {
+        let broker = FakeNatsBroker::start().unwrap();
+
+        // pipeline A -> broker -> pipeline B
+        let (out_tx, out_rx) = sync_channel::<Vec<f32>>(8);
+        let subscriber = NatsSubscribeNode::new(
+            "subscriber".into(),
+            out_tx,
+            broker.addr(),
+            "pcm.*".into(),
+            RawLeCodec,
+        )
+        .limit(50);
+        let sub_thread = thread::spawn(move || subscriber.run());
+        wait_for_subscriptions(&broker, 1);
+
+        let (in_tx, in_rx) = sync_channel::<Vec<f32>>(8);
+        let publisher = NatsPublishNode::new(
+            "publisher".into(),
+            in_rx,
+            broker.addr(),
+            "pcm.left".into(),
+            RawLeCodec,
+        );
+        let pub_thread = thread::spawn(move || publisher.run());
+        for i in 0..50 {
+            in_tx.send(vec![i as f32, -(i as f32)]).unwrap();
+        }
+        drop(in_tx);
+
+        for i in 0..50 {
+            assert_eq!(out_rx.recv().unwrap(), vec![i as f32, -(i as f32)]);
+        }
+        pub_thread.join().unwrap();
+        sub_thread.join().unwrap();
+        // the subscriber stopped after its limit, which corked its output
+        assert_eq!(out_rx.recv(), Err(ChannelError::IsCorked));
+        assert_eq!(broker.subscriptions(), 0);
+    }
+
+    #[test]
+    fn broker_going_away_ends_subscription() {
+        let broker = FakeNatsBroker::start().unwrap();
+        let (tx, rx) = sync_channel::<String>(4);
+        let subscriber =
+            NatsSubscribeNode::new("sub".into(), tx, broker.addr(), "logs".into(), BytesCodec);
+        let sub_thread = thread::spawn(move || subscriber.run());
+        wait_for_subscriptions(&broker, 1);
+
+        let mut client = NatsClient::connect(&broker.addr()).unwrap();
+        let mut payload = Vec::new();
+        BytesCodec
+            .encode(&String::from("hello"), &mut payload)
+            .unwrap();
+        client.publish("logs", &payload).unwrap();
+        client.publish("other", &payload).unwrap();
+        client.flush().unwrap();
+        assert_eq!(rx.recv().unwrap(), "hello");
+
+        drop(broker);
+        sub_thread.join().unwrap();
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn corked_output_ends_quiet_subscription() {
+        let broker = FakeNatsBroker::start().unwrap();
+        let (tx, rx) = sync_channel::<String>(4);
+        let subscriber = NatsSubscribeNode::new(
+            "sub".into(),
+            tx.clone(),
+            broker.addr(),
+            "logs".into(),
+            BytesCodec,
+        );
+        let sub_thread = thread::spawn(move || subscriber.run());
+        wait_for_subscriptions(&broker, 1);
+
+        // nothing is ever published, so only noticing the cork stops the subscriber
+        tx.cork();
+        sub_thread.join().unwrap();
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+    }
+
+    #[test]
+    fn unreachable_broker_is_reported() {
+        // nothing listens on a port which was just released
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let (errors, errors_rx) = sync_channel(4);
+
+        let (tx, rx) = sync_channel::<String>(4);
+        let subscriber =
+            NatsSubscribeNode::new("sub".into(), tx, addr.clone(), "logs".into(), BytesCodec)
+                .errors(errors.clone());
+        subscriber.run();
+        assert_eq!(rx.recv(), Err(ChannelError::IsCorked));
+        let error = errors_rx.try_recv().unwrap().unwrap();
+        assert_eq!(error.node, "sub");
+        assert_eq!(error.kind, ErrorKind::ConnectionRefused);
+
+        // more than the channel holds, which would block forever if nobody kept reading
+        let (tx, rx) = sync_channel::<String>(2);
+        let publisher =
+            NatsPublishNode::new("pub".into(), rx, addr, "logs".into(), BytesCodec).errors(errors);
+        let pub_thread = thread::spawn(move || publisher.run());
+        for i in 0..10 {
+            tx.send(i.to_string()).unwrap();
+        }
+        drop(tx);
+        pub_thread.join().unwrap();
+        let error = errors_rx.try_recv().unwrap().unwrap();
+        assert_eq!(error.node, "pub");
+        assert_eq!(error.kind, ErrorKind::ConnectionRefused);
+    }
+}
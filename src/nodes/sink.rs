@@ -0,0 +1,412 @@
+use std::fmt::{self, Debug, Formatter};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::mpmc::{ChannelReceiver, Sender};
+
+use super::{recv_or_end, report_error, ComputeNode, NodeError};
+
+/// Items gathered by a `Collect` node, which can be looked at while it runs and after.
+pub struct Collected<T> {
+    items: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T> Clone for Collected<T> {
+    fn clone(&self) -> Self {
+        Self {
+            items: self.items.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Collected<T> {
+    /// A copy of everything collected so far.
+    pub fn items(&self) -> Vec<T> {
+        self.lock().clone()
+    }
+
+    /// Take everything collected so far, leaving it empty.
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// The number of items collected so far.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<T>> {
+        self.items.lock().expect("Thread was poisoned")
+    }
+}
+
+/// Gathers every item it receives so they can be looked at through a `Collected` handle, which is
+/// mostly useful for tests and small pipelines.
+pub struct Collect<R: ChannelReceiver> {
+    name: String,
+    rx: R,
+    items: Collected<R::Item>,
+}
+
+impl<R: ChannelReceiver> Debug for Collect<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R: ChannelReceiver> Collect<R> {
+    pub fn new(name: String, rx: R) -> Self {
+        Self {
+            name,
+            rx,
+            items: Collected {
+                items: Arc::new(Mutex::new(Vec::new())),
+            },
+        }
+    }
+
+    /// Get a handle to the items collected by this node.
+    pub fn handle(&self) -> Collected<R::Item> {
+        self.items.clone()
+    }
+}
+
+impl<R> ComputeNode for Collect<R>
+where
+    R: ChannelReceiver + Send,
+    R::Item: Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            self.items.lock().push(v);
+        }
+    }
+}
+
+/// Calls `f` with every item it receives.
+#[derive(Clone)]
+pub struct ForEach<R, F> {
+    name: String,
+    rx: R,
+    f: F,
+}
+
+impl<R, F> Debug for ForEach<R, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, F> ForEach<R, F>
+where
+    R: ChannelReceiver,
+    F: Fn(R::Item),
+{
+    pub fn new(name: String, rx: R, f: F) -> Self {
+        Self { name, rx, f }
+    }
+}
+
+impl<R, F> ComputeNode for ForEach<R, F>
+where
+    R: ChannelReceiver + Send,
+    F: Fn(R::Item) + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        while let Some(v) = recv_or_end(&self.rx) {
+            (self.f)(v);
+        }
+    }
+}
+
+/// Write every item to `w` and flush it once the input is corked. If writing fails the rest of the
+/// input is discarded, so the nodes sending to us are not left blocked on a full channel.
+fn write_items<R, W>(rx: &R, w: &mut W) -> io::Result<()>
+where
+    R: ChannelReceiver,
+    R::Item: AsRef<[u8]>,
+    W: Write,
+{
+    let result = (|| {
+        while let Some(v) = recv_or_end(rx) {
+            w.write_all(v.as_ref())?;
+        }
+        w.flush()
+    })();
+    if result.is_err() {
+        while recv_or_end(rx).is_some() {}
+    }
+    result
+}
+
+/// Writes the bytes of every item it receives to a writer, which can be anything from a socket to
+/// a `Vec<u8>` in a test. The writer is flushed once the input is corked and can be taken back
+/// afterwards with `into_inner`. Errors writing are reported on the error channel, or standard
+/// error without one.
+pub struct WriteTo<R, W> {
+    name: String,
+    rx: R,
+    w: Mutex<W>,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<R, W> Debug for WriteTo<R, W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, W> WriteTo<R, W>
+where
+    R: ChannelReceiver,
+    R::Item: AsRef<[u8]>,
+    W: Write,
+{
+    pub fn new(name: String, rx: R, w: W) -> Self {
+        Self {
+            name,
+            rx,
+            w: Mutex::new(w),
+            errors: None,
+        }
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    /// Take the writer back.
+    pub fn into_inner(self) -> W {
+        self.w.into_inner().expect("Thread was poisoned")
+    }
+}
+
+impl<R> WriteTo<R, Stdout>
+where
+    R: ChannelReceiver,
+    R::Item: AsRef<[u8]>,
+{
+    /// Write to standard output. It is only locked while writing each item, so other threads
+    /// can still print in between.
+    pub fn stdout(name: String, rx: R) -> Self {
+        Self::new(name, rx, io::stdout())
+    }
+}
+
+impl<R, W> ComputeNode for WriteTo<R, W>
+where
+    R: ChannelReceiver + Send,
+    R::Item: AsRef<[u8]>,
+    W: Write + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut w = self.w.lock().expect("Thread was poisoned");
+        if let Err(e) = write_items(&self.rx, &mut *w) {
+            report_error(&self.errors, &self.name, &e);
+        }
+    }
+}
+
+/// Writes the bytes of every item it receives to a file, replacing anything already in it unless
+/// appending. Errors opening or writing the file are reported on the error channel, or standard
+/// error without one.
+pub struct WriteFile<R> {
+    name: String,
+    rx: R,
+    path: PathBuf,
+    append: bool,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<R> Debug for WriteFile<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R> WriteFile<R>
+where
+    R: ChannelReceiver,
+    R::Item: AsRef<[u8]>,
+{
+    pub fn new<P: AsRef<Path>>(name: String, rx: R, path: P) -> Self {
+        Self {
+            name,
+            rx,
+            path: path.as_ref().to_path_buf(),
+            append: false,
+            errors: None,
+        }
+    }
+
+    /// Add to the end of the file instead of replacing it.
+    pub fn append(mut self) -> Self {
+        self.append = true;
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+}
+
+impl<R> ComputeNode for WriteFile<R>
+where
+    R: ChannelReceiver + Send,
+    R::Item: AsRef<[u8]>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path);
+        let written = match file {
+            Ok(file) => write_items(&self.rx, &mut BufWriter::new(file)),
+            Err(e) => {
+                while recv_or_end(&self.rx).is_some() {}
+                Err(e)
+            }
+        };
+        if let Err(e) = written {
+            let e = io::Error::new(e.kind(), format!("{}: {}", self.path.display(), e));
+            report_error(&self.errors, &self.name, &e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, ChannelReceiver, ChannelSender};
+    use crate::nodes::test_util::temp_dir;
+
+    /// A writer which fails after accepting `room` bytes.
+    struct Full {
+        room: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "no room"));
+            }
+            let n = buf.len().min(self.room);
+            self.room -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn collect_and_for_each() {
+        let (tx, rx) = sync_channel(8);
+        let collect = Collect::new("collect".into(), rx.clone());
+        let seen = Mutex::new(0);
+        let for_each = ForEach::new("count".into(), rx, |v: u32| *seen.lock().unwrap() += v);
+        for i in 1..=4 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let items = collect.handle();
+        collect.run();
+        for_each.run();
+        assert_eq!(items.len(), 4);
+        assert_eq!(items.take(), [1, 2, 3, 4]);
+        assert!(items.is_empty());
+        assert_eq!(*seen.lock().unwrap(), 10);
+    }
+
+    #[test]
+    fn write_to_any_writer() {
+        let (tx, rx) = sync_channel(8);
+        tx.send("hello ".to_string()).unwrap();
+        tx.send("world".to_string()).unwrap();
+        drop(tx);
+        let node = WriteTo::new("write".into(), rx, Vec::new());
+        node.run();
+        assert_eq!(node.into_inner(), b"hello world");
+    }
+
+    #[test]
+    fn write_errors_drain_input() {
+        let (errors, errors_rx) = sync_channel(4);
+        let (tx, rx) = sync_channel(2);
+        let node = WriteTo::new("full".into(), rx, Full { room: 3 }).errors(errors);
+        let writer = std::thread::spawn(move || node.run());
+        // more than the channel holds, which would block forever if nobody kept reading
+        for _ in 0..10 {
+            tx.send(vec![0u8; 2]).unwrap();
+        }
+        drop(tx);
+        writer.join().unwrap();
+        let error = errors_rx.try_recv().unwrap().unwrap();
+        assert_eq!(error.node, "full");
+        assert_eq!(error.kind, io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn write_file() {
+        let dir = temp_dir("write-file");
+        let path = dir.join("out.bin");
+        for (items, append) in [(vec![vec![1u8, 2], vec![3]], false), (vec![vec![4]], true)] {
+            let (tx, rx) = sync_channel(4);
+            for v in items {
+                tx.send(v).unwrap();
+            }
+            drop(tx);
+            let node = WriteFile::new("file".into(), rx, &path);
+            if append {
+                node.append().run();
+            } else {
+                node.run();
+            }
+        }
+        assert_eq!(fs::read(&path).unwrap(), [1, 2, 3, 4]);
+
+        // a directory which doesn't exist is reported rather than panicking
+        let (errors, errors_rx) = sync_channel(4);
+        let (tx, rx) = sync_channel::<Vec<u8>>(4);
+        drop(tx);
+        WriteFile::new("file".into(), rx, dir.join("missing").join("out.bin"))
+            .errors(errors)
+            .run();
+        assert_eq!(
+            errors_rx.try_recv().unwrap().unwrap().kind,
+            io::ErrorKind::NotFound
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{ChannelSender, Sender};
+
+use super::{report_error, ComputeNode, NodeError};
+
+/// Sends everything an iterator yields. The iterator is used up by the first run, so running the
+/// node again sends nothing.
+pub struct FromIterator<I, S> {
+    name: String,
+    iter: Mutex<Option<I>>,
+    tx: S,
+}
+
+impl<I, S> Debug for FromIterator<I, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<I, S> FromIterator<I, S>
+where
+    I: IntoIterator<Item = S::Item>,
+    S: ChannelSender,
+{
+    pub fn new(name: String, iter: I, tx: S) -> Self {
+        Self {
+            name,
+            iter: Mutex::new(Some(iter)),
+            tx,
+        }
+    }
+}
+
+impl<I, S> ComputeNode for FromIterator<I, S>
+where
+    I: IntoIterator<Item = S::Item> + Send,
+    S: ChannelSender + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let iter = match self.iter.lock() {
+            Ok(mut iter) => iter.take(),
+            Err(_) => panic!("Thread was poisoned"),
+        };
+        for v in iter.into_iter().flatten() {
+            if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Sends a tick every `period`, counting up from 0 with the first sent right away. Ticks are kept
+/// on schedule, so a slow receiver does not make later ones drift. Runs until the output is corked
+/// or until `limit` ticks have been sent if one was given.
+#[derive(Clone, Debug)]
+pub struct Interval<S> {
+    name: String,
+    tx: S,
+    period: Duration,
+    limit: Option<u64>,
+}
+
+impl<S: ChannelSender<Item = u64>> Interval<S> {
+    pub fn new(name: String, tx: S, period: Duration) -> Self {
+        Self {
+            name,
+            tx,
+            period,
+            limit: None,
+        }
+    }
+
+    /// Stop after sending `n` ticks.
+    pub fn limit(mut self, n: u64) -> Self {
+        self.limit = Some(n);
+        self
+    }
+}
+
+impl<S: ChannelSender<Item = u64> + Send> ComputeNode for Interval<S> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut due = Instant::now();
+        let mut tick = 0;
+        while Some(tick) != self.limit {
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+            if self.tx.send(tick).is_err() {
+                break;
+            }
+            tick += 1;
+            due += self.period;
+        }
+        self.tx.cork();
+    }
+}
+
+/// Something a reader node can split its input into.
+pub trait ReadUnit: Clone + Sized {
+    /// Read the next unit, or None at the end of the input. `chunk_size` is the size chunks of
+    /// bytes should be, and is ignored by anything else.
+    fn read_next<B: BufRead>(reader: &mut B, chunk_size: usize) -> io::Result<Option<Self>>;
+}
+
+/// Chunks of `chunk_size` bytes, except possibly the last one.
+impl ReadUnit for Vec<u8> {
+    fn read_next<B: BufRead>(reader: &mut B, chunk_size: usize) -> io::Result<Option<Self>> {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        reader.take(chunk_size as u64).read_to_end(&mut chunk)?;
+        Ok(Some(chunk).filter(|c| !c.is_empty()))
+    }
+}
+
+/// Lines of UTF-8 text without their line endings.
+impl ReadUnit for String {
+    fn read_next<B: BufRead>(reader: &mut B, _: usize) -> io::Result<Option<Self>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+/// Send everything in `reader` until it runs out or the output is corked.
+fn read_into<B, S>(mut reader: B, tx: &S, chunk_size: usize) -> io::Result<()>
+where
+    B: BufRead,
+    S: ChannelSender,
+    S::Item: ReadUnit,
+{
+    while let Some(v) = S::Item::read_next(&mut reader, chunk_size)? {
+        if tx.send(v).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a file as chunks of bytes (`Vec<u8>`) or as lines of text (`String`), depending on what
+/// the output takes. Errors opening or reading the file are reported on the error channel, or
+/// standard error without one, after which the output is corked.
+#[derive(Clone)]
+pub struct ReadFile<S> {
+    name: String,
+    path: PathBuf,
+    tx: S,
+    chunk_size: usize,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<S> Debug for ReadFile<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<S: ChannelSender<Item = Vec<u8>>> ReadFile<S> {
+    /// Read the file in chunks of `chunk_size` bytes. Panics if `chunk_size` is 0.
+    pub fn chunks<P: AsRef<Path>>(name: String, path: P, tx: S, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size must not be 0");
+        Self {
+            name,
+            path: path.as_ref().to_path_buf(),
+            tx,
+            chunk_size,
+            errors: None,
+        }
+    }
+}
+
+impl<S: ChannelSender<Item = String>> ReadFile<S> {
+    /// Read the file line by line.
+    pub fn lines<P: AsRef<Path>>(name: String, path: P, tx: S) -> Self {
+        Self {
+            name,
+            path: path.as_ref().to_path_buf(),
+            tx,
+            chunk_size: 0,
+            errors: None,
+        }
+    }
+}
+
+impl<S: ChannelSender> ReadFile<S> {
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+}
+
+impl<S> ComputeNode for ReadFile<S>
+where
+    S: ChannelSender + Send,
+    S::Item: ReadUnit,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let read = File::open(&self.path)
+            .and_then(|f| read_into(BufReader::new(f), &self.tx, self.chunk_size));
+        if let Err(e) = read {
+            report_error(&self.errors, &self.name, &e);
+        }
+        self.tx.cork();
+    }
+}
+
+/// Reads standard input as chunks of bytes (`Vec<u8>`) or as lines of text (`String`), depending
+/// on what the output takes. Errors reading are reported on the error channel, or standard error
+/// without one, after which the output is corked.
+#[derive(Clone)]
+pub struct ReadStdin<S> {
+    name: String,
+    tx: S,
+    chunk_size: usize,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<S> Debug for ReadStdin<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<S: ChannelSender<Item = Vec<u8>>> ReadStdin<S> {
+    /// Read in chunks of `chunk_size` bytes. Panics if `chunk_size` is 0.
+    pub fn chunks(name: String, tx: S, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size must not be 0");
+        Self {
+            name,
+            tx,
+            chunk_size,
+            errors: None,
+        }
+    }
+}
+
+impl<S: ChannelSender<Item = String>> ReadStdin<S> {
+    /// Read line by line.
+    pub fn lines(name: String, tx: S) -> Self {
+        Self {
+            name,
+            tx,
+            chunk_size: 0,
+            errors: None,
+        }
+    }
+}
+
+impl<S: ChannelSender> ReadStdin<S> {
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+}
+
+impl<S> ComputeNode for ReadStdin<S>
+where
+    S: ChannelSender + Send,
+    S::Item: ReadUnit,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        if let Err(e) = read_into(io::stdin().lock(), &self.tx, self.chunk_size) {
+            report_error(&self.errors, &self.name, &e);
+        }
+        self.tx.cork();
+    }
+}
+
+/// Reads a numbered set of files from a directory, such as one file for each channel of a
+/// recording. The name of file `i` is `pattern` with `{i}` replaced by `i`, and it is read into
+/// output `i` in chunks of `chunk_size` bytes. Every file is read on a thread of its own so that
+/// they keep pace with each other when the outputs are combined further down.
+///
+/// A file which can't be read is reported on the error channel, or standard error without one, and
+/// its output is corked while the other files carry on.
+#[derive(Clone)]
+pub struct ReadDirectory<S> {
+    name: String,
+    dir: PathBuf,
+    pattern: String,
+    outputs: Vec<S>,
+    chunk_size: usize,
+    errors: Option<Sender<NodeError>>,
+}
+
+impl<S> Debug for ReadDirectory<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<S: ChannelSender<Item = Vec<u8>>> ReadDirectory<S> {
+    /// Panics if `chunk_size` is 0.
+    pub fn new<P: AsRef<Path>>(
+        name: String,
+        dir: P,
+        pattern: &str,
+        outputs: Vec<S>,
+        chunk_size: usize,
+    ) -> Self {
+        assert!(chunk_size > 0, "Chunk size must not be 0");
+        Self {
+            name,
+            dir: dir.as_ref().to_path_buf(),
+            pattern: pattern.to_string(),
+            outputs,
+            chunk_size,
+            errors: None,
+        }
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    /// The path of file `i`.
+    pub fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(self.pattern.replace("{i}", &i.to_string()))
+    }
+}
+
+impl<S: ChannelSender<Item = Vec<u8>> + Send + Sync> ComputeNode for ReadDirectory<S> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        thread::scope(|scope| {
+            for (i, tx) in self.outputs.iter().enumerate() {
+                let path = self.path(i);
+                scope.spawn(move || {
+                    let read = File::open(&path)
+                        .and_then(|f| read_into(BufReader::new(f), tx, self.chunk_size));
+                    if let Err(e) = read {
+                        let e = io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+                        report_error(&self.errors, &self.name, &e);
+                    }
+                    tx.cork();
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, ChannelReceiver};
+    use crate::nodes::test_util::{drain, temp_dir};
+
+    #[test]
+    fn iterator_and_interval() {
+        let (tx, rx) = sync_channel(8);
+        let node = FromIterator::new("iter".into(), 1..4, tx);
+        node.run();
+        assert_eq!(drain(&rx), [1, 2, 3]);
+
+        let (tx, rx) = sync_channel(8);
+        let start = Instant::now();
+        Interval::new("tick".into(), tx, Duration::from_millis(10))
+            .limit(4)
+            .run();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(drain(&rx), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn read_file_chunks_and_lines() {
+        let dir = temp_dir("read-file");
+        let path = dir.join("input.txt");
+        fs::write(&path, "one\r\ntwo\n\nthree").unwrap();
+
+        let (tx, rx) = sync_channel(8);
+        ReadFile::chunks("chunks".into(), &path, tx, 6).run();
+        assert_eq!(
+            drain(&rx),
+            [b"one\r\nt".to_vec(), b"wo\n\nth".to_vec(), b"ree".to_vec()]
+        );
+
+        let (tx, rx) = sync_channel(8);
+        ReadFile::lines("lines".into(), &path, tx).run();
+        assert_eq!(drain(&rx), ["one", "two", "", "three"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_errors_are_reported() {
+        let dir = temp_dir("read-errors");
+        let (errors, errors_rx) = sync_channel(4);
+        let (tx, rx) = sync_channel::<String>(4);
+        ReadFile::lines("missing".into(), dir.join("nope"), tx)
+            .errors(errors.clone())
+            .run();
+        assert!(rx.recv().is_err());
+        let error = errors_rx.try_recv().unwrap().unwrap();
+        assert_eq!(error.node, "missing");
+        assert_eq!(error.kind, io::ErrorKind::NotFound);
+
+        // invalid text is an error as well
+        fs::write(dir.join("bad.txt"), [b'o', b'k', b'\n', 0xff, b'\n']).unwrap();
+        let (tx, rx) = sync_channel::<String>(4);
+        ReadFile::lines("bad".into(), dir.join("bad.txt"), tx)
+            .errors(errors)
+            .run();
+        assert_eq!(drain(&rx), ["ok"]);
+        assert_eq!(
+            errors_rx.try_recv().unwrap().unwrap().kind,
+            io::ErrorKind::InvalidData
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_numbered_files() {
+        let dir = temp_dir("read-directory");
+        fs::write(dir.join("0.pcm"), [0u8, 1, 2]).unwrap();
+        fs::write(dir.join("1.pcm"), [3u8]).unwrap();
+        let (errors, errors_rx) = sync_channel(4);
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..3).map(|_| sync_channel(4)).unzip();
+        let node = ReadDirectory::new("pcm".into(), &dir, "{i}.pcm", txs, 2).errors(errors);
+        assert_eq!(node.path(1), dir.join("1.pcm"));
+        node.run();
+        assert_eq!(drain(&rxs[0]), [vec![0, 1], vec![2]]);
+        assert_eq!(drain(&rxs[1]), [vec![3]]);
+        // there is no third file
+        assert!(drain(&rxs[2]).is_empty());
+        let error = errors_rx.try_recv().unwrap().unwrap();
+        assert_eq!(error.kind, io::ErrorKind::NotFound);
+        assert!(error.message.contains("2.pcm"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt::{self, Debug, Formatter};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{ChannelReceiver, ChannelSender};
+
+use super::{recv_or_end, recv_until, ComputeNode, Input};
+
+/// Function which determines how much an item counts towards a rate limit.
+type RateWeigher<T> = Box<dyn Fn(&T) -> f64 + Send>;
+
+/// Limits how fast items are passed on with a token bucket. The bucket holds up to `burst` tokens
+/// and refills at `rate` tokens per second, and every item takes one token (or its weight, when
+/// there is a weight function) before it is sent, waiting for the bucket to refill if there are not
+/// enough. An item weighing more than the whole bucket is sent once the bucket is full, leaving it
+/// in debt.
+pub struct Throttle<R: ChannelReceiver, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    rate: f64,
+    burst: f64,
+    weigher: Option<RateWeigher<R::Item>>,
+}
+
+impl<R: ChannelReceiver, S> Debug for Throttle<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S> Throttle<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    /// Pass on at most `rate` items per second on average, with bursts of up to one second's
+    /// worth. Panics if the rate is not positive.
+    pub fn new(name: String, rx: R, tx: S, rate: f64) -> Self {
+        assert!(rate > 0.0, "Rate must be positive");
+        Self {
+            name,
+            rx,
+            tx,
+            rate,
+            burst: rate.max(1.0),
+            weigher: None,
+        }
+    }
+
+    /// Allow bursts of up to `burst` tokens. Panics if it is not positive.
+    pub fn burst(mut self, burst: f64) -> Self {
+        assert!(burst > 0.0, "Burst must be positive");
+        self.burst = burst;
+        self
+    }
+
+    /// Limit the total weight of the items per second rather than how many there are, such as the
+    /// number of samples in a packet.
+    pub fn weigher<F>(mut self, weigher: F) -> Self
+    where
+        F: Fn(&R::Item) -> f64 + Send + 'static,
+    {
+        self.weigher = Some(Box::new(weigher));
+        self
+    }
+}
+
+impl<R, S> ComputeNode for Throttle<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut tokens = self.burst;
+        let mut refilled = Instant::now();
+        while let Some(v) = recv_or_end(&self.rx) {
+            let weight = self.weigher.as_ref().map_or(1.0, |f| f(&v));
+            loop {
+                let now = Instant::now();
+                tokens = (tokens + (now - refilled).as_secs_f64() * self.rate).min(self.burst);
+                refilled = now;
+                let needed = weight.min(self.burst);
+                if tokens >= needed {
+                    break;
+                }
+                thread::sleep(Duration::from_secs_f64((needed - tokens) / self.rate));
+            }
+            tokens -= weight;
+            if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Releases items at the pace given by a timestamp in each of them, such as the offset of a packet
+/// in a recording, so a stream can be replayed in real time. The first item is sent right away and
+/// each item after it once as much time has passed as between its timestamp and the first one.
+/// Items which are already late are sent right away.
+#[derive(Clone)]
+pub struct Pace<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    timestamp: F,
+}
+
+impl<R, S, F> Debug for Pace<R, S, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, F> Pace<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+    F: Fn(&R::Item) -> Duration,
+{
+    pub fn new(name: String, rx: R, tx: S, timestamp: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            timestamp,
+        }
+    }
+}
+
+impl<R, S, F> ComputeNode for Pace<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+    F: Fn(&R::Item) -> Duration + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut epoch = None;
+        while let Some(v) = recv_or_end(&self.rx) {
+            let ts = (self.timestamp)(&v);
+            let (start, first) = *epoch.get_or_insert((Instant::now(), ts));
+            let due = start + ts.saturating_sub(first);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+            if self.tx.send(v).is_err() {
+                break;
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Only passes on an item once nothing newer has arrived for `quiet`, so a burst of updates turns
+/// into just the last of them. The latest item is passed on right away when the input is corked.
+#[derive(Clone, Debug)]
+pub struct Debounce<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    quiet: Duration,
+}
+
+impl<R, S> Debounce<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    pub fn new(name: String, rx: R, tx: S, quiet: Duration) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            quiet,
+        }
+    }
+}
+
+impl<R, S> ComputeNode for Debounce<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut latest = None;
+        let mut deadline = None;
+        loop {
+            match recv_until(&self.rx, deadline) {
+                Input::Item(v) => {
+                    latest = Some(v);
+                    deadline = Some(Instant::now() + self.quiet);
+                }
+                Input::Timeout => {
+                    deadline = None;
+                    if let Some(v) = latest.take() {
+                        if self.tx.send(v).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Input::End => {
+                    if let Some(v) = latest {
+                        let _ = self.tx.send(v);
+                    }
+                    break;
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+/// Passes on the latest item received during each `period`, if there was one, at the end of the
+/// period. Anything else received during the period is discarded. The latest item is passed on
+/// right away when the input is corked.
+#[derive(Clone, Debug)]
+pub struct Sample<R, S> {
+    name: String,
+    rx: R,
+    tx: S,
+    period: Duration,
+}
+
+impl<R, S> Sample<R, S>
+where
+    R: ChannelReceiver,
+    S: ChannelSender<Item = R::Item>,
+{
+    /// Panics if the period is 0.
+    pub fn new(name: String, rx: R, tx: S, period: Duration) -> Self {
+        assert!(period > Duration::ZERO, "Period must not be 0");
+        Self {
+            name,
+            rx,
+            tx,
+            period,
+        }
+    }
+}
+
+impl<R, S> ComputeNode for Sample<R, S>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender<Item = R::Item> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let mut latest = None;
+        let mut tick = Instant::now() + self.period;
+        loop {
+            match recv_until(&self.rx, Some(tick)) {
+                Input::Item(v) => latest = Some(v),
+                Input::Timeout => {
+                    let now = Instant::now();
+                    while tick <= now {
+                        tick += self.period;
+                    }
+                    if let Some(v) = latest.take() {
+                        if self.tx.send(v).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Input::End => {
+                    if let Some(v) = latest {
+                        let _ = self.tx.send(v);
+                    }
+                    break;
+                }
+            }
+        }
+        self.tx.cork();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mpmc::{sync_channel, Receiver};
+    use crate::nodes::test_util::drain;
+
+    /// Send each item after sleeping for the given number of milliseconds, then cork.
+    fn send_slowly<T: Clone + Send + 'static>(items: Vec<(T, u64)>) -> Receiver<T> {
+        let (tx, rx) = sync_channel(16);
+        thread::spawn(move || {
+            for (v, delay) in items {
+                thread::sleep(Duration::from_millis(delay));
+                tx.send(v).unwrap();
+            }
+        });
+        rx
+    }
+
+    #[test]
+    fn throttle_rate() {
+        let (tx, rx) = sync_channel(16);
+        let (out_tx, out_rx) = sync_channel(16);
+        for i in 0..6 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let start = Instant::now();
+        // two go right away and the other four at 100 per second
+        Throttle::new("throttle".into(), rx, out_tx, 100.0)
+            .burst(2.0)
+            .run();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(35), "{:?}", elapsed);
+        assert_eq!(drain(&out_rx), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn throttle_weight() {
+        let (tx, rx) = sync_channel(4);
+        let (out_tx, out_rx) = sync_channel(4);
+        tx.send(vec![0u8; 10]).unwrap();
+        tx.send(vec![0u8; 5]).unwrap();
+        drop(tx);
+        let start = Instant::now();
+        // the first packet empties the bucket so the second waits for it to refill by 5
+        Throttle::new("throttle".into(), rx, out_tx, 200.0)
+            .burst(10.0)
+            .weigher(|v: &Vec<u8>| v.len() as f64)
+            .run();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(drain(&out_rx).len(), 2);
+    }
+
+    #[test]
+    fn pace_by_timestamp() {
+        let (tx, rx) = sync_channel(4);
+        let (out_tx, out_rx) = sync_channel(4);
+        for ms in [1000, 1020, 1010, 1040] {
+            tx.send(Duration::from_millis(ms)).unwrap();
+        }
+        drop(tx);
+        let start = Instant::now();
+        Pace::new("pace".into(), rx, out_tx, |ts: &Duration| *ts).run();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(40), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        assert_eq!(drain(&out_rx).len(), 4);
+    }
+
+    #[test]
+    fn debounce_bursts() {
+        // gaps within a burst are far shorter than the quiet time and the gap between bursts far
+        // longer, so a slow machine doesn't split or join them
+        let rx = send_slowly(vec![(1, 0), (2, 10), (3, 10), (4, 600), (5, 10)]);
+        let (tx, out) = sync_channel(8);
+        Debounce::new("debounce".into(), rx, tx, Duration::from_millis(300)).run();
+        assert_eq!(drain(&out), [3, 5]);
+    }
+
+    #[test]
+    fn sample_latest() {
+        // the periods end at 400 and 800ms, well clear of the items at 0-20 and 610-620ms
+        let rx = send_slowly(vec![(1, 0), (2, 10), (3, 600), (4, 10)]);
+        let (tx, out) = sync_channel(8);
+        Sample::new("sample".into(), rx, tx, Duration::from_millis(400)).run();
+        assert_eq!(drain(&out), [2, 4]);
+    }
+}
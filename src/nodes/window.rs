@@ -0,0 +1,689 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::mpmc::{ChannelError, ChannelReceiver, ChannelSender, Codec, Sender};
+
+use super::checkpoint::{
+    checkpoint, invalid_snapshot, CheckpointStore, Record, SnapshotReader, SnapshotWriter,
+    StatefulNode,
+};
+use super::{recv_until, ComputeNode, Input, NodeError};
+
+/// How a `Window` node groups the items it receives.
+///
+/// Time is measured from when items are received by the node rather than when they were created,
+/// and time windows only start once there is an item to put in them, so quiet periods do not
+/// produce empty windows.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum WindowSpec {
+    /// Windows of `size` items, with a new one starting every `step` items. Windows overlap when
+    /// `step` is smaller than `size`, and items are skipped when it is larger.
+    Count { size: usize, step: usize },
+    /// Windows covering `size` worth of time, with a new one starting every `step`.
+    Time { size: Duration, step: Duration },
+    /// Windows of items which arrive no more than `gap` apart. A window ends once nothing has
+    /// arrived for `gap`.
+    Session { gap: Duration },
+}
+
+impl WindowSpec {
+    /// Consecutive windows of `n` items which do not overlap.
+    pub fn tumbling_count(n: usize) -> Self {
+        Self::sliding_count(n, n)
+    }
+
+    /// Windows of `size` items starting every `step` items.
+    pub fn sliding_count(size: usize, step: usize) -> Self {
+        assert!(size > 0 && step > 0, "Window size and step must not be 0");
+        WindowSpec::Count { size, step }
+    }
+
+    /// Consecutive windows covering `size` worth of time which do not overlap.
+    pub fn tumbling_time(size: Duration) -> Self {
+        Self::sliding_time(size, size)
+    }
+
+    /// Windows covering `size` worth of time starting every `step`.
+    pub fn sliding_time(size: Duration, step: Duration) -> Self {
+        assert!(
+            size > Duration::ZERO && step > Duration::ZERO,
+            "Window size and step must not be 0"
+        );
+        WindowSpec::Time { size, step }
+    }
+
+    /// Windows of items which arrive no more than `gap` apart.
+    pub fn session(gap: Duration) -> Self {
+        WindowSpec::Session { gap }
+    }
+}
+
+/// The windows a node has started but not sent yet, kept apart from the node so `Window` and
+/// `StatefulWindow` can share them.
+enum Windows<T> {
+    Count {
+        size: usize,
+        step: usize,
+        window: VecDeque<T>,
+        seen: usize,
+    },
+    Time {
+        size: Duration,
+        step: Duration,
+        items: VecDeque<(Instant, T)>,
+        /// When the oldest window started, if there is one.
+        start: Option<Instant>,
+    },
+    Session {
+        gap: Duration,
+        window: Vec<T>,
+        last: Option<Instant>,
+    },
+}
+
+impl<T: Clone> Windows<T> {
+    fn new(spec: WindowSpec) -> Self {
+        match spec {
+            WindowSpec::Count { size, step } => Windows::Count {
+                size,
+                step,
+                window: VecDeque::with_capacity(size),
+                seen: 0,
+            },
+            WindowSpec::Time { size, step } => Windows::Time {
+                size,
+                step,
+                items: VecDeque::new(),
+                start: None,
+            },
+            WindowSpec::Session { gap } => Windows::Session {
+                gap,
+                window: Vec::new(),
+                last: None,
+            },
+        }
+    }
+
+    /// When the oldest window ends, for windows which end with time.
+    fn deadline(&self) -> Option<Instant> {
+        match self {
+            Windows::Count { .. } => None,
+            Windows::Time { size, start, .. } => start.map(|s| s + *size),
+            Windows::Session { gap, last, .. } => last.map(|t| t + *gap),
+        }
+    }
+
+    /// Add an item received at `now`, emitting any window it fills up.
+    fn push<E>(&mut self, v: T, now: Instant, emit: &mut E) -> Result<(), ChannelError>
+    where
+        E: FnMut(Vec<T>) -> Result<(), ChannelError>,
+    {
+        match self {
+            Windows::Count {
+                size,
+                step,
+                window,
+                seen,
+            } => {
+                window.push_back(v);
+                *seen += 1;
+                if window.len() > *size {
+                    window.pop_front();
+                }
+                if *seen >= *size && (*seen - *size) % *step == 0 {
+                    if step == size {
+                        // nothing is shared with the next window
+                        emit(window.drain(..).collect())?;
+                    } else {
+                        emit(window.iter().cloned().collect())?;
+                    }
+                }
+            }
+            Windows::Time { items, start, .. } => {
+                start.get_or_insert(now);
+                items.push_back((now, v));
+            }
+            Windows::Session { window, last, .. } => {
+                window.push(v);
+                *last = Some(now);
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit every window which has ended by `now`.
+    fn close<E>(&mut self, now: Instant, emit: &mut E) -> Result<(), ChannelError>
+    where
+        E: FnMut(Vec<T>) -> Result<(), ChannelError>,
+    {
+        match self {
+            Windows::Count { .. } => {}
+            Windows::Time {
+                size,
+                step,
+                items,
+                start,
+            } => {
+                while let Some(s) = *start {
+                    if now < s + *size {
+                        break;
+                    }
+                    *start = close_time_window(items, s, *size, *step, emit)?;
+                }
+            }
+            Windows::Session { gap, window, last } => {
+                if last.is_some_and(|t| t + *gap <= now) {
+                    *last = None;
+                    emit(mem::take(window))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit every window which has started, oldest first, for when the input has been corked.
+    fn flush<E>(&mut self, emit: &mut E) -> Result<(), ChannelError>
+    where
+        E: FnMut(Vec<T>) -> Result<(), ChannelError>,
+    {
+        match self {
+            Windows::Count {
+                size,
+                step,
+                window,
+                seen,
+            } => {
+                let (size, step) = (*size, *step);
+                // flush every window which started but never filled up
+                let mut start = if *seen < size {
+                    0
+                } else {
+                    (*seen - size) / step * step + step
+                };
+                while start < *seen {
+                    let unsent = *seen - start;
+                    emit(window.iter().skip(window.len() - unsent).cloned().collect())?;
+                    start += step;
+                }
+                window.clear();
+                *seen = 0;
+            }
+            Windows::Time {
+                size,
+                step,
+                items,
+                start,
+            } => {
+                while let Some(s) = *start {
+                    *start = close_time_window(items, s, *size, *step, emit)?;
+                }
+            }
+            Windows::Session { window, last, .. } => {
+                *last = None;
+                emit(mem::take(window))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the windows, which starts with the kind of window so a snapshot isn't restored into
+    /// windows of another kind.
+    fn save<C: Codec<T>>(&self, codec: &C) -> io::Result<Vec<u8>> {
+        let mut snapshot = SnapshotWriter::new();
+        match self {
+            Windows::Count { window, seen, .. } => {
+                snapshot.u64(0);
+                snapshot.u64(*seen as u64);
+                snapshot.u64(window.len() as u64);
+                for v in window {
+                    snapshot.item(codec, v)?;
+                }
+            }
+            Windows::Time { items, start, .. } => {
+                snapshot.u64(1);
+                snapshot.optional_time(*start);
+                snapshot.u64(items.len() as u64);
+                for (t, v) in items {
+                    snapshot.time(*t);
+                    snapshot.item(codec, v)?;
+                }
+            }
+            Windows::Session { window, last, .. } => {
+                snapshot.u64(2);
+                snapshot.optional_time(*last);
+                snapshot.u64(window.len() as u64);
+                for v in window {
+                    snapshot.item(codec, v)?;
+                }
+            }
+        }
+        Ok(snapshot.finish())
+    }
+
+    /// Replace the windows with ones `save` encoded, leaving them as they were if the snapshot
+    /// can't be read.
+    fn load<C: Codec<T>>(&mut self, codec: &C, snapshot: &[u8]) -> io::Result<()> {
+        let mut snapshot = SnapshotReader::new(snapshot);
+        let kind = snapshot.u64()?;
+        match self {
+            Windows::Count {
+                size, window, seen, ..
+            } if kind == 0 => {
+                let restored_seen = snapshot.u64()? as usize;
+                let mut restored = VecDeque::new();
+                for _ in 0..snapshot.u64()? {
+                    restored.push_back(snapshot.item(codec)?);
+                }
+                snapshot.finish()?;
+                if restored.len() > *size || restored.len() > restored_seen {
+                    return Err(invalid_snapshot());
+                }
+                *seen = restored_seen;
+                *window = restored;
+            }
+            Windows::Time { items, start, .. } if kind == 1 => {
+                let restored_start = snapshot.optional_time()?;
+                let mut restored = VecDeque::new();
+                for _ in 0..snapshot.u64()? {
+                    let t = snapshot.time()?;
+                    restored.push_back((t, snapshot.item(codec)?));
+                }
+                snapshot.finish()?;
+                *start = restored_start;
+                *items = restored;
+            }
+            Windows::Session { window, last, .. } if kind == 2 => {
+                let restored_last = snapshot.optional_time()?;
+                let mut restored = Vec::new();
+                for _ in 0..snapshot.u64()? {
+                    restored.push(snapshot.item(codec)?);
+                }
+                snapshot.finish()?;
+                *last = restored_last;
+                *window = restored;
+            }
+            _ => return Err(invalid_snapshot()),
+        }
+        Ok(())
+    }
+}
+
+/// Emit the time window starting at `start` and drop anything which won't be in the next one.
+/// Returns when the next window containing any items starts, if there is one yet.
+fn close_time_window<T, E>(
+    items: &mut VecDeque<(Instant, T)>,
+    start: Instant,
+    size: Duration,
+    step: Duration,
+    emit: &mut E,
+) -> Result<Option<Instant>, ChannelError>
+where
+    T: Clone,
+    E: FnMut(Vec<T>) -> Result<(), ChannelError>,
+{
+    let end = start + size;
+    let window = items
+        .iter()
+        .take_while(|(t, _)| *t < end)
+        .map(|(_, v)| v.clone())
+        .collect();
+    emit(window)?;
+
+    let mut next = start + step;
+    while items.front().is_some_and(|(t, _)| *t < next) {
+        items.pop_front();
+    }
+    Ok(match items.front() {
+        Some((first, _)) => {
+            // skip windows which would be empty
+            while next + size <= *first {
+                next += step;
+            }
+            Some(next)
+        }
+        None => None,
+    })
+}
+
+/// Groups items into windows as described by a `WindowSpec` and sends `f(window)` for each of them,
+/// where the window holds its items in the order they were received. Pass the window through
+/// unchanged to emit the items themselves, or reduce it to emit an aggregate.
+///
+/// When the input is corked, every window which has started but not filled up yet is flushed,
+/// oldest first, before the output is corked. Overlapping windows may each be flushed with only
+/// part of what they would have held.
+#[derive(Clone)]
+pub struct Window<R, S, F> {
+    name: String,
+    rx: R,
+    tx: S,
+    spec: WindowSpec,
+    f: F,
+}
+
+impl<R, S, F> Debug for Window<R, S, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, F> Window<R, S, F>
+where
+    R: ChannelReceiver,
+    S: ChannelSender,
+    F: Fn(Vec<R::Item>) -> S::Item,
+{
+    pub fn new(name: String, rx: R, tx: S, spec: WindowSpec, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            spec,
+            f,
+        }
+    }
+
+    fn emit(&self, window: Vec<R::Item>) -> Result<(), ChannelError> {
+        if window.is_empty() {
+            return Ok(());
+        }
+        self.tx.send((self.f)(window))
+    }
+
+    fn windows(&self, windows: &mut Windows<R::Item>) -> Result<(), ChannelError> {
+        let emit = &mut |window| self.emit(window);
+        loop {
+            windows.close(Instant::now(), emit)?;
+            match recv_until(&self.rx, windows.deadline()) {
+                Input::Item(v) => windows.push(v, Instant::now(), emit)?,
+                Input::Timeout => {}
+                Input::End => break,
+            }
+        }
+        windows.flush(emit)
+    }
+}
+
+impl<R, S, F> ComputeNode for Window<R, S, F>
+where
+    R: ChannelReceiver + Send,
+    S: ChannelSender + Send,
+    F: Fn(Vec<R::Item>) -> S::Item + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        // an error here only means the output was corked, so there is nothing left to do
+        let _ = self.windows(&mut Windows::new(self.spec));
+        self.tx.cork();
+    }
+}
+
+/// A `Window` over a checkpointed stream which saves the windows it has started with every barrier
+/// it receives, using `codec` to turn their items into bytes. Barriers are passed on once the
+/// state has been saved, so a window sent after a barrier may still hold items from before it. As
+/// with `StatefulScan`, the windows are kept between runs and failing to save them is reported
+/// while the barrier is still passed on. Times are saved as how long ago they were, so restored
+/// time and session windows carry on as if no time had passed while the graph was stopped.
+pub struct StatefulWindow<R, S, F, C, T, O> {
+    name: String,
+    rx: R,
+    tx: S,
+    windows: Mutex<Windows<T>>,
+    codec: C,
+    f: F,
+    store: Option<Arc<CheckpointStore>>,
+    errors: Option<Sender<NodeError>>,
+    _items: PhantomData<fn(T) -> O>,
+}
+
+impl<R, S, F, C, T, O> Debug for StatefulWindow<R, S, F, C, T, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+impl<R, S, F, C, T, O> StatefulWindow<R, S, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>>,
+    S: ChannelSender<Item = Record<O>>,
+    F: Fn(Vec<T>) -> O,
+    C: Codec<T>,
+    T: Clone,
+{
+    pub fn new(name: String, rx: R, tx: S, spec: WindowSpec, codec: C, f: F) -> Self {
+        Self {
+            name,
+            rx,
+            tx,
+            windows: Mutex::new(Windows::new(spec)),
+            codec,
+            f,
+            store: None,
+            errors: None,
+            _items: PhantomData,
+        }
+    }
+
+    /// Save snapshots to this store.
+    pub fn store(mut self, store: Arc<CheckpointStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Report errors on this channel rather than printing them to standard error.
+    pub fn errors(mut self, errors: Sender<NodeError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Windows<T>> {
+        self.windows.lock().expect("Thread was poisoned")
+    }
+}
+
+impl<R, S, F, C, T, O> ComputeNode for StatefulWindow<R, S, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<O>> + Send,
+    F: Fn(Vec<T>) -> O + Send,
+    C: Codec<T>,
+    T: Clone + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self) {
+        let emit = &mut |window: Vec<T>| {
+            if window.is_empty() {
+                return Ok(());
+            }
+            self.tx.send(Record::Item((self.f)(window)))
+        };
+        // an error here only means the output was corked, so there is nothing left to do
+        let _ = (|| {
+            loop {
+                let deadline = {
+                    let mut windows = self.lock();
+                    windows.close(Instant::now(), emit)?;
+                    windows.deadline()
+                };
+                match recv_until(&self.rx, deadline) {
+                    Input::Item(Record::Item(v)) => self.lock().push(v, Instant::now(), emit)?,
+                    Input::Item(Record::Barrier(id)) => {
+                        checkpoint(self, &self.store, &self.errors, id);
+                        self.tx.send(Record::Barrier(id))?;
+                    }
+                    Input::Timeout => {}
+                    Input::End => break,
+                }
+            }
+            self.lock().flush(emit)
+        })();
+        self.tx.cork();
+    }
+}
+
+impl<R, S, F, C, T, O> StatefulNode for StatefulWindow<R, S, F, C, T, O>
+where
+    R: ChannelReceiver<Item = Record<T>> + Send,
+    S: ChannelSender<Item = Record<O>> + Send,
+    F: Fn(Vec<T>) -> O + Send,
+    C: Codec<T>,
+    T: Clone + Send,
+{
+    fn snapshot(&self) -> io::Result<Vec<u8>> {
+        self.lock().save(&self.codec)
+    }
+
+    fn restore(&self, snapshot: &[u8]) -> io::Result<()> {
+        self.lock().load(&self.codec, snapshot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use std::fs;
+
+    use super::*;
+    use crate::mpmc::{sync_channel, RawLeCodec, Receiver};
+    use crate::nodes::test_util::{drain, input, temp_dir};
+
+    fn windows<T: Clone + Send + 'static>(spec: WindowSpec, items: Vec<(T, u64)>) -> Vec<Vec<T>> {
+        let (tx, rx) = sync_channel(16);
+        let (out_tx, out_rx): (_, Receiver<Vec<T>>) = sync_channel(16);
+        let sender = thread::spawn(move || {
+            // each item is sent after sleeping for the given number of milliseconds
+            for (v, delay) in items {
+                thread::sleep(Duration::from_millis(delay));
+                tx.send(v).unwrap();
+            }
+        });
+        Window::new("window".into(), rx, out_tx, spec, |w| w).run();
+        sender.join().unwrap();
+        drain(&out_rx)
+    }
+
+    #[test]
+    fn count_windows() {
+        let items: Vec<_> = (1..=7).map(|i| (i, 0)).collect();
+        let tumbling = windows(WindowSpec::tumbling_count(3), items.clone());
+        assert_eq!(tumbling, [vec![1, 2, 3], vec![4, 5, 6], vec![7]]);
+
+        let sliding = windows(WindowSpec::sliding_count(3, 2), items.clone());
+        // the last window only got as far as its first item
+        assert_eq!(
+            sliding,
+            [vec![1, 2, 3], vec![3, 4, 5], vec![5, 6, 7], vec![7]]
+        );
+
+        let overlapping = windows(WindowSpec::sliding_count(3, 1), items.clone());
+        // each of the windows which started at the last two items is flushed
+        assert_eq!(
+            overlapping,
+            [
+                vec![1, 2, 3],
+                vec![2, 3, 4],
+                vec![3, 4, 5],
+                vec![4, 5, 6],
+                vec![5, 6, 7],
+                vec![6, 7],
+                vec![7]
+            ]
+        );
+
+        let gapped = windows(WindowSpec::sliding_count(2, 3), items);
+        assert_eq!(gapped, [vec![1, 2], vec![4, 5], vec![7]]);
+    }
+
+    #[test]
+    fn aggregate_windows() {
+        let (tx, rx) = sync_channel(8);
+        let (out_tx, out_rx) = sync_channel(8);
+        for v in [3.0f32, 4.0, 1.0] {
+            tx.send(v).unwrap();
+        }
+        drop(tx);
+        let rms = |w: Vec<f32>| (w.iter().map(|v| v * v).sum::<f32>() / w.len() as f32).sqrt();
+        Window::new("rms".into(), rx, out_tx, WindowSpec::tumbling_count(2), rms).run();
+        assert_eq!(out_rx.recv(), Ok(12.5f32.sqrt()));
+        assert_eq!(out_rx.recv(), Ok(1.0));
+        assert!(out_rx.recv().is_err());
+    }
+
+    #[test]
+    fn time_windows() {
+        // gaps are well clear of the window size so a slow machine doesn't move items between
+        // windows
+        let items = vec![(1, 0), (2, 0), (3, 600), (4, 0)];
+        let tumbling = windows(WindowSpec::tumbling_time(Duration::from_millis(200)), items);
+        assert_eq!(tumbling, [vec![1, 2], vec![3, 4]]);
+
+        // windows start at 0, 300 and 600ms, and every item is 150ms from the nearest boundary
+        let items = vec![(1, 0), (2, 450), (3, 300)];
+        let sliding = windows(
+            WindowSpec::sliding_time(Duration::from_millis(600), Duration::from_millis(300)),
+            items,
+        );
+        assert_eq!(sliding, [vec![1, 2], vec![2, 3], vec![3]]);
+
+        // windows cover 0-300 and 900-1200ms, with nothing arriving between them
+        let items = vec![(1, 0), (2, 150), (3, 900)];
+        let gapped = windows(
+            WindowSpec::sliding_time(Duration::from_millis(300), Duration::from_millis(900)),
+            items,
+        );
+        assert_eq!(gapped, [vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn session_windows() {
+        let items = vec![(1, 0), (2, 5), (3, 600), (4, 5), (5, 600)];
+        let sessions = windows(WindowSpec::session(Duration::from_millis(200)), items);
+        assert_eq!(sessions, [vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn window_checkpoint() {
+        let dir = temp_dir("window");
+        let store = Arc::new(CheckpointStore::open(&dir).unwrap());
+        let spec = WindowSpec::tumbling_count(3);
+        let records = [Record::Item(1u32), Record::Item(2), Record::Barrier(0)];
+        let rx = input(&[&records[..], &[Record::Item(3), Record::Item(4)]].concat());
+        let (tx, out_rx) = sync_channel(8);
+        StatefulWindow::new("window".into(), rx, tx, spec, RawLeCodec, |w| w)
+            .store(store.clone())
+            .run();
+        // the barrier is passed on without closing the window it arrived in
+        assert_eq!(
+            drain(&out_rx),
+            [
+                Record::Barrier(0),
+                Record::Item(vec![1, 2, 3]),
+                Record::Item(vec![4])
+            ]
+        );
+
+        let rx = input(&[Record::Item(3), Record::Item(4)]);
+        let (tx, out_rx) = sync_channel(8);
+        let window = StatefulWindow::new("window".into(), rx, tx, spec, RawLeCodec, |w| w);
+        window.restore_from(&store, 0).unwrap();
+        window.run();
+        assert_eq!(
+            drain(&out_rx),
+            [Record::Item(vec![1, 2, 3]), Record::Item(vec![4])]
+        );
+        assert!(window.restore(&[9]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}